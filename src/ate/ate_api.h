@@ -64,9 +64,8 @@ enum {
    * The max size is defined in the OpenTitan repository in:
    * sw/device/lib/testing/test_framework/ottf_console_internal.h
    *
-   * DO NOT MODIFY without also modifying this size in:
-   *   1. src/ate/test_programs/otlib_wrapper/src/lib.rs (BUFFER_SIZE), and
-   *   2. the personalization firmware.
+   * DO NOT MODIFY without also modifying this size in the personalization
+   * firmware. The opentitanlib wrapper picks it up through bindgen.
    */
   kDutTxSpiFrameHeaderSizeInBytes = 12,
   kDutTxMaxSpiFrameSizeInBytes =
@@ -225,6 +225,10 @@ int main(int argc, char **argv) {
 
   // Init session with FPGA DUT and load CP provisioning firmware.
   auto dut = DutLib::Create(absl::GetFlag(FLAGS_fpga));
+  if (dut == nullptr) {
+    LOG(ERROR) << "DutLib::Create failed.";
+    return -1;
+  }
   dut->DutFpgaLoadBitstream(fpga_bitstream_path);
   dut->DutLoadSramElf(openocd_path, sram_elf_path, /*wait_for_done=*/false,
                       /*timeout_ms=*/1000);
@@ -8,6 +8,7 @@
 
 #include <iomanip>
 #include <string>
+#include <vector>
 
 #include "absl/log/log.h"
 #include "absl/status/status.h"
@@ -18,86 +19,1252 @@ namespace provisioning {
 namespace test_programs {
 
 extern "C" {
-void* OtLibFpgaTransportInit(const char* fpga);
-void OtLibFpgaLoadBitstream(void* transport, const char* fpga_bitstream);
-void OtLibLoadSramElf(void* transport, const char* openocd, const char* elf,
-                      bool wait_for_done, uint64_t timeout_ms);
-void OtLibBootstrap(void* transport, const char* bin);
-void OtLibConsoleWaitForRx(void* transport, const char* msg,
+// NOTE: must match the definition of OtLibStatus in
+// src/ate/test_programs/otlib_wrapper/src/status.rs
+enum class OtLibStatus : int {
+  kOk = 0,
+  kInvalidArgument = 1,
+  kTransportError = 2,
+  kJtagError = 3,
+  kBootstrapError = 4,
+  kConsoleError = 5,
+  kConsoleTimeout = 6,
+  kCrcMismatch = 7,
+  kSramProgramError = 8,
+  kLcTransitionError = 9,
+  kBootError = 10,
+  kInternalError = 11,
+  kPanic = 12,
+  kBitstreamNotFound = 13,
+  kFpgaProgramError = 14,
+  kRomTimeout = 15,
+  kUnsupported = 16,
+  kBufferTooSmall = 17,
+  kDeviceError = 18,
+  kLcMismatch = 19,
+  kBusy = 20,
+  kWouldBlock = 21,
+  kJtagTeardownError = 22,
+  kCancelled = 23,
+};
+
+// NOTE: must match the definition of OtLibVerilatorOpts in
+// src/ate/test_programs/otlib_wrapper/src/transport.rs
+struct OtLibVerilatorOpts {
+  const char* verilator_bin;
+  const char* verilator_rom;
+  const char* const* verilator_flash;
+  size_t verilator_flash_count;
+  const char* verilator_otp;
+  uint64_t verilator_timeout_ms;
+  const char* const* verilator_args;
+  size_t verilator_args_count;
+};
+
+size_t OtLibGetLastError(char* buf, size_t buf_len);
+size_t OtLibGetVersion(char* buf, size_t buf_len);
+void OtLibSetDryRun(bool enabled);
+void OtLibSetLogCallback(void (*cb)(int32_t level, const char* msg));
+OtLibStatus OtLibSetLogFile(const char* path);
+uint64_t OtLibGetLastOpDurationUs(DutOp op);
+OtLibTransport* OtLibFpgaTransportInit(const char* fpga,
+                                       bool disable_dft_on_reset,
+                                       uint16_t usb_vid, uint16_t usb_pid,
+                                       const char* usb_serial,
+                                       const char* openocd_adapter_config,
+                                       const char* const* conf, size_t conf_len,
+                                       uint64_t timeout_ms, uint32_t retries,
+                                       uint64_t retry_delay_ms,
+                                       uint64_t init_retry_window_ms);
+OtLibTransport* OtLibVerilatorTransportInit(const OtLibVerilatorOpts* opts,
+                                            uint64_t timeout_ms);
+OtLibTransport* OtLibProxyTransportInit(const char* proxy, uint32_t port,
+                                        uint64_t timeout_ms);
+OtLibTransport* OtLibTi50EmulatorTransportInit(const char* instance_prefix,
+                                               const char* executable_directory,
+                                               const char* executable,
+                                               uint64_t timeout_ms);
+void OtLibFpgaTransportDestroy(OtLibTransport* transport);
+OtLibStatus OtLibRequestCancel(OtLibTransport* transport);
+OtLibStatus OtLibResetTarget(OtLibTransport* transport,
+                             uint64_t reset_delay_ms);
+OtLibStatus OtLibApplyStrapping(OtLibTransport* transport, const char* name);
+OtLibStatus OtLibRemoveStrapping(OtLibTransport* transport, const char* name);
+OtLibStatus OtLibGpioRead(OtLibTransport* transport, const char* pin,
+                          bool* out_level);
+OtLibStatus OtLibGpioWrite(OtLibTransport* transport, const char* pin,
+                           bool level);
+OtLibStatus OtLibSetPinMode(OtLibTransport* transport, const char* pin,
+                            DutPinMode mode);
+OtLibStatus OtLibFpgaLoadBitstream(OtLibTransport* transport,
+                                   const char* fpga_bitstream,
+                                   uint64_t rom_reset_pulse_ms,
+                                   uint64_t rom_timeout_ms,
+                                   bool clear_bitstream,
+                                   void (*progress_cb)(uint32_t percent));
+OtLibStatus OtLibFpgaLoadBitstreamBuf(OtLibTransport* transport,
+                                      const uint8_t* data, size_t len,
+                                      uint64_t rom_reset_pulse_ms,
+                                      uint64_t rom_timeout_ms,
+                                      bool clear_bitstream,
+                                      void (*progress_cb)(uint32_t percent));
+OtLibStatus OtLibLoadSramElf(OtLibTransport* transport, const char* openocd,
+                             uint32_t adapter_speed_khz, bool jtag_log_stdio,
+                             const char* elf, bool skip_crc,
+                             bool wait_for_done, uint64_t timeout_ms,
+                             DutSramExecution* out_execution,
+                             uint32_t* out_result);
+OtLibStatus OtLibLoadSramVmem(OtLibTransport* transport, const char* openocd,
+                              uint32_t adapter_speed_khz, bool jtag_log_stdio,
+                              const char* vmem, uint32_t load_addr,
+                              bool skip_crc, bool wait_for_done,
+                              uint64_t timeout_ms,
+                              DutSramExecution* out_execution,
+                              uint32_t* out_result);
+OtLibStatus OtLibBootstrap(OtLibTransport* transport, const char* bin,
+                           uint32_t baudrate, bool flow_control,
+                           DutBootstrapProtocol protocol, uint32_t retries,
+                           uint64_t retry_delay_ms,
+                           uint64_t inter_frame_delay_us,
+                           uint64_t flash_erase_delay_ms,
+                           uint32_t spi_speed_hz, DutSpiMode spi_mode);
+OtLibStatus OtLibConsoleWaitForRx(OtLibTransport* transport,
+                                  DutConsoleKind kind,
+                                  const char* spi_interface,
+                                  const char* tx_ready_pin,
+                                  uint32_t spi_speed_hz, DutSpiMode spi_mode,
+                                  DutPullMode pull_mode, bool active_low,
+                                  const char* msg, uint64_t timeout_ms,
+                                  void (*on_heartbeat)(uint64_t elapsed_ms));
+OtLibStatus OtLibConsoleWaitForRxCapture(
+    OtLibTransport* transport, DutConsoleKind kind, const char* spi_interface,
+    const char* tx_ready_pin, uint32_t spi_speed_hz, DutSpiMode spi_mode,
+    DutPullMode pull_mode, bool active_low, const char* msg, size_t group,
+    char* buf, size_t buf_len, size_t* out_len, uint64_t timeout_ms);
+OtLibStatus OtLibConsoleWaitForAnyOf(OtLibTransport* transport,
+                                     DutConsoleKind kind,
+                                     const char* spi_interface,
+                                     const char* tx_ready_pin,
+                                     uint32_t spi_speed_hz, DutSpiMode spi_mode,
+                                     DutPullMode pull_mode, bool active_low,
+                                     const char* const* patterns,
+                                     size_t patterns_count, size_t* out_index,
+                                     uint64_t timeout_ms);
+OtLibStatus OtLibConsoleRx(OtLibTransport* transport, DutConsoleKind kind,
+                           const char* spi_interface, const char* tx_ready_pin,
+                           uint32_t spi_speed_hz, DutSpiMode spi_mode,
+                           DutPullMode pull_mode, bool active_low,
+                           const char* sync_msg, dut_spi_frame_t* spi_frames,
+                           size_t* num_frames, size_t frame_size,
+                           bool skip_crc_check, DutCrcKind crc_kind,
+                           bool binary, const char* success_regex,
+                           const char* failure_regex, bool quiet,
+                           bool timestamp, bool newline, uint64_t timeout_ms,
+                           void (*on_heartbeat)(uint64_t elapsed_ms));
+OtLibStatus OtLibConsoleRxCapture(
+    OtLibTransport* transport, DutConsoleKind kind, const char* spi_interface,
+    const char* tx_ready_pin, uint32_t spi_speed_hz, DutSpiMode spi_mode,
+    DutPullMode pull_mode, bool active_low, const char* sync_msg,
+    dut_spi_frame_t* spi_frames, size_t* num_frames, size_t frame_size,
+    bool skip_crc_check, DutCrcKind crc_kind, bool binary,
+    const char* success_regex, const char* failure_regex, int capture_fd,
+    bool timestamp, bool newline, uint64_t timeout_ms,
+    void (*on_heartbeat)(uint64_t elapsed_ms));
+OtLibStatus OtLibConsoleRxStream(
+    OtLibTransport* transport, DutConsoleKind kind, const char* spi_interface,
+    const char* tx_ready_pin, uint32_t spi_speed_hz, DutSpiMode spi_mode,
+    DutPullMode pull_mode, bool active_low, const char* sync_msg,
+    void (*on_frame)(const uint8_t* data, size_t len), size_t frame_size,
+    bool skip_crc_check, DutCrcKind crc_kind, bool binary,
+    const char* success_regex, const char* failure_regex, bool quiet,
+    uint64_t timeout_ms);
+OtLibStatus OtLibConsoleTransact(
+    OtLibTransport* transport, DutConsoleKind kind, const char* spi_interface,
+    const char* tx_ready_pin, uint32_t spi_speed_hz, DutSpiMode spi_mode,
+    DutPullMode pull_mode, bool active_low, const uint8_t* tx_frame,
+    size_t tx_frame_size, dut_spi_frame_t* spi_frames, size_t* num_frames,
+    size_t frame_size, bool skip_crc_check, DutCrcKind crc_kind, bool quiet,
+    uint64_t timeout_ms);
+OtLibStatus OtLibConsoleTx(OtLibTransport* transport, DutConsoleKind kind,
+                           const char* spi_interface, const char* tx_ready_pin,
+                           uint32_t spi_speed_hz, DutSpiMode spi_mode,
+                           DutPullMode pull_mode, bool active_low,
+                           const char* sync_msg, const uint8_t* spi_frame,
+                           size_t spi_frame_size, bool compress,
                            uint64_t timeout_ms);
-void OtLibConsoleRx(void* transport, const char* sync_msg,
-                    dut_spi_frame_t* spi_frames, size_t* num_frames,
-                    bool skip_crc_check, bool quiet, uint64_t timeout_ms);
-void OtLibConsoleTx(void* transport, const char* sync_msg,
-                    const uint8_t* spi_frame, size_t spi_frame_size,
-                    uint64_t timeout_ms);
-void OtLibResetAndLock(void* transport, const char* openocd);
-void OtLibLcTransition(void* transport, const char* openocd,
-                       const uint8_t* token, size_t token_size,
-                       uint32_t target_lc_state);
-void OtLibCheckTransportImgBoot(void* transport, const char* owner_fw_boot_msg,
+OtLibStatus OtLibConsoleTxFrames(OtLibTransport* transport, DutConsoleKind kind,
+                                 const char* spi_interface,
+                                 const char* tx_ready_pin,
+                                 uint32_t spi_speed_hz, DutSpiMode spi_mode,
+                                 DutPullMode pull_mode, bool active_low,
+                                 const char* sync_msg,
+                                 const dut_spi_frame_t* frames,
+                                 size_t num_frames, uint64_t timeout_ms);
+OtLibStatus OtLibConsoleFlush(OtLibTransport* transport, DutConsoleKind kind,
+                              const char* spi_interface,
+                              const char* tx_ready_pin, uint32_t spi_speed_hz,
+                              DutSpiMode spi_mode, DutPullMode pull_mode,
+                              bool active_low, uint64_t drain_ms);
+void* OtLibConsoleOpen(OtLibTransport* transport, const char* spi_interface,
+                       const char* tx_ready_pin, uint32_t spi_speed_hz,
+                       DutSpiMode spi_mode, DutPullMode pull_mode,
+                       bool active_low);
+void OtLibConsoleClose(void* handle);
+OtLibStatus OtLibConsoleHandleWaitForRx(
+    void* handle, const char* msg, uint64_t timeout_ms,
+    void (*on_heartbeat)(uint64_t elapsed_ms));
+OtLibStatus OtLibConsoleHandleWaitForRxCapture(void* handle, const char* msg,
+                                               size_t group, char* buf,
+                                               size_t buf_len, size_t* out_len,
+                                               uint64_t timeout_ms);
+OtLibStatus OtLibExtractUdsCert(OtLibTransport* transport, DutConsoleKind kind,
+                                const char* spi_interface,
+                                const char* tx_ready_pin, uint32_t spi_speed_hz,
+                                DutSpiMode spi_mode, DutPullMode pull_mode,
+                                bool active_low, const char* uds_cert_regex,
+                                uint8_t* out, size_t len, size_t* out_len,
                                 uint64_t timeout_ms);
+OtLibStatus OtLibConsoleHandleExtractUdsCert(void* handle,
+                                             const char* uds_cert_regex,
+                                             uint8_t* out, size_t len,
+                                             size_t* out_len,
+                                             uint64_t timeout_ms);
+OtLibStatus OtLibConsoleHandleWaitForAnyOf(void* handle,
+                                           const char* const* patterns,
+                                           size_t patterns_count,
+                                           size_t* out_index,
+                                           uint64_t timeout_ms);
+OtLibStatus OtLibConsoleHandleRx(void* handle, const char* sync_msg,
+                                 dut_spi_frame_t* spi_frames,
+                                 size_t* num_frames, size_t frame_size,
+                                 bool skip_crc_check, DutCrcKind crc_kind,
+                                 bool binary,
+                                 const char* success_regex,
+                                 const char* failure_regex, bool quiet,
+                                 bool timestamp, bool newline,
+                                 uint64_t timeout_ms,
+                                 void (*on_heartbeat)(uint64_t elapsed_ms));
+OtLibStatus OtLibConsoleHandleRxStream(
+    void* handle, const char* sync_msg,
+    void (*on_frame)(const uint8_t* data, size_t len), size_t frame_size,
+    bool skip_crc_check, DutCrcKind crc_kind, bool binary,
+    const char* success_regex, const char* failure_regex, bool quiet,
+    uint64_t timeout_ms);
+OtLibStatus OtLibConsoleRxPoll(void* handle, dut_spi_frame_t* spi_frames,
+                               size_t* num_frames, size_t frame_size,
+                               bool skip_crc_check, DutCrcKind crc_kind,
+                               bool binary,
+                               const char* success_regex,
+                               const char* failure_regex, bool quiet,
+                               uint64_t slice_ms);
+OtLibStatus OtLibConsoleHandleTransact(void* handle, const uint8_t* tx_frame,
+                                       size_t tx_frame_size,
+                                       dut_spi_frame_t* spi_frames,
+                                       size_t* num_frames, size_t frame_size,
+                                       bool skip_crc_check, DutCrcKind crc_kind,
+                                       bool quiet, uint64_t timeout_ms);
+OtLibStatus OtLibConsoleHandleTx(void* handle, const char* sync_msg,
+                                 const uint8_t* spi_frame,
+                                 size_t spi_frame_size, bool compress,
+                                 uint64_t timeout_ms);
+OtLibStatus OtLibConsoleHandleTxFrames(void* handle, const char* sync_msg,
+                                       const dut_spi_frame_t* frames,
+                                       size_t num_frames, uint64_t timeout_ms);
+OtLibStatus OtLibConsoleHandleFlush(void* handle, uint64_t drain_ms);
+OtLibStatus OtLibResetAndLock(OtLibTransport* transport, const char* openocd,
+                              uint32_t adapter_speed_khz, bool jtag_log_stdio);
+OtLibStatus OtLibResetNoLock(OtLibTransport* transport, const char* openocd,
+                             uint32_t adapter_speed_khz, bool jtag_log_stdio,
+                             uint64_t reset_delay_ms);
+OtLibStatus OtLibLcTransition(OtLibTransport* transport, const char* openocd,
+                              uint32_t adapter_speed_khz, bool jtag_log_stdio,
+                              const uint8_t* token, size_t token_size,
+                              bool token_is_hashed, uint32_t target_lc_state,
+                              bool use_external_clk, bool force,
+                              DutJtagTap reset_tap_straps, uint32_t* out_state);
+OtLibStatus OtLibLcTransitionHex(OtLibTransport* transport, const char* openocd,
+                                 uint32_t adapter_speed_khz,
+                                 bool jtag_log_stdio, const char* token_hex,
+                                 bool token_is_hashed, uint32_t target_lc_state,
+                                 bool use_external_clk, bool force,
+                                 DutJtagTap reset_tap_straps,
+                                 uint32_t* out_state);
+OtLibStatus OtLibVolatileRawUnlock(
+    OtLibTransport* transport, const char* openocd, uint32_t adapter_speed_khz,
+    bool jtag_log_stdio, const uint8_t* token, size_t token_size,
+    uint32_t target_lc_state, bool use_external_clk,
+    DutJtagTap post_transition_tap, uint32_t* out_state);
+OtLibStatus OtLibLcTransitionSequence(
+    OtLibTransport* transport, const char* openocd, uint32_t adapter_speed_khz,
+    bool jtag_log_stdio, const uint32_t* states, const dut_spi_frame_t* tokens,
+    size_t n, bool use_external_clk, size_t* out_step, uint32_t* out_state);
+OtLibStatus OtLibRmaUnlock(OtLibTransport* transport, const char* openocd,
+                           uint32_t adapter_speed_khz, bool jtag_log_stdio,
+                           const uint8_t* token, size_t token_size,
+                           uint32_t* out_state);
+OtLibStatus OtLibReadLcState(OtLibTransport* transport, const char* openocd,
+                             uint32_t adapter_speed_khz, bool jtag_log_stdio,
+                             uint32_t* out_state);
+OtLibStatus OtLibVerifyLcState(OtLibTransport* transport, const char* openocd,
+                               uint32_t adapter_speed_khz, bool jtag_log_stdio,
+                               uint32_t expected_state, uint32_t* out_state);
+OtLibStatus OtLibReadLcTransitionCount(OtLibTransport* transport,
+                                       const char* openocd,
+                                       uint32_t adapter_speed_khz,
+                                       bool jtag_log_stdio,
+                                       uint32_t* out_count);
+OtLibStatus OtLibReadDeviceId(OtLibTransport* transport, const char* openocd,
+                              uint32_t adapter_speed_khz, bool jtag_log_stdio,
+                              uint8_t* out, size_t len);
+OtLibStatus OtLibReadJtagIdcode(OtLibTransport* transport, const char* openocd,
+                                uint32_t adapter_speed_khz,
+                                bool jtag_log_stdio, uint32_t* out_idcode);
+OtLibStatus OtLibJtagReadReg(OtLibTransport* transport, const char* openocd,
+                             uint32_t adapter_speed_khz, bool jtag_log_stdio,
+                             uint32_t addr, uint32_t* out);
+OtLibStatus OtLibJtagWriteReg(OtLibTransport* transport, const char* openocd,
+                              uint32_t adapter_speed_khz, bool jtag_log_stdio,
+                              uint32_t addr, uint32_t val);
+OtLibStatus OtLibJtagMemRead(OtLibTransport* transport, const char* openocd,
+                             uint32_t adapter_speed_khz, bool jtag_log_stdio,
+                             uint32_t addr, uint8_t* buf, size_t len);
+OtLibStatus OtLibJtagMemWrite(OtLibTransport* transport, const char* openocd,
+                              uint32_t adapter_speed_khz, bool jtag_log_stdio,
+                              uint32_t addr, const uint8_t* buf, size_t len);
+OtLibStatus OtLibReadOtp(OtLibTransport* transport, const char* openocd,
+                         uint32_t adapter_speed_khz, bool jtag_log_stdio,
+                         const char* partition, uint8_t* buf, size_t len);
+OtLibStatus OtLibCpuHalt(OtLibTransport* transport, const char* openocd,
+                         uint32_t adapter_speed_khz, bool jtag_log_stdio);
+OtLibStatus OtLibCpuResume(OtLibTransport* transport, const char* openocd,
+                           uint32_t adapter_speed_khz, bool jtag_log_stdio);
+OtLibStatus OtLibCheckTransportImgBoot(OtLibTransport* transport,
+                                       const char* owner_fw_boot_msg,
+                                       uint64_t timeout_ms,
+                                       const char* rom_ext_regex,
+                                       const char* boot_success_regex,
+                                       const char* const* boot_failure_regexes,
+                                       size_t boot_failure_regexes_count,
+                                       DutBootResult* out_result,
+                                       uint64_t* out_rom_ext_boot_us,
+                                       uint64_t* out_owner_fw_boot_us);
+OtLibStatus OtLibCheckTransportImgBootWithTimeouts(
+    OtLibTransport* transport, const char* owner_fw_boot_msg,
+    uint64_t rom_ext_timeout_ms, uint64_t owner_fw_timeout_ms,
+    const char* rom_ext_regex, const char* boot_success_regex,
+    const char* const* boot_failure_regexes, size_t boot_failure_regexes_count,
+    DutBootResult* out_result, uint64_t* out_rom_ext_boot_us,
+    uint64_t* out_owner_fw_boot_us);
+}
+
+namespace {
+// Returns the message of the last error reported by the opentitanlib wrapper.
+std::string OtLibLastError() {
+  std::string msg(OtLibGetLastError(nullptr, 0), '\0');
+  OtLibGetLastError(msg.data(), msg.size() + 1);
+  return msg;
+}
+
+// Forwards a message logged by the opentitanlib wrapper to the test program
+// log. The factory methods install it on the thread creating the `DutLib`
+// only, since the wrapper keeps a log callback per thread.
+void OtLibLog(int32_t level, const char* msg) {
+  switch (static_cast<DutLogLevel>(level)) {
+    case DutLogLevel::kWarn:
+      LOG(WARNING) << msg;
+      break;
+    case DutLogLevel::kError:
+      LOG(ERROR) << msg;
+      break;
+    default:
+      LOG(INFO) << msg;
+      break;
+  }
+}
+
+// Aborts the test program if a call into the opentitanlib wrapper failed. A
+// failure to close the JTAG connection after an otherwise successful call is
+// only logged, so that its results are still used.
+void CheckOtLibStatus(OtLibStatus status, const char* fn) {
+  if (status == OtLibStatus::kJtagTeardownError) {
+    LOG(WARNING) << fn << " succeeded, but its JTAG teardown failed: "
+                 << OtLibLastError();
+    return;
+  }
+  if (status != OtLibStatus::kOk) {
+    LOG(FATAL) << fn << " failed with status " << static_cast<int>(status)
+               << ": " << OtLibLastError();
+  }
+}
+}  // namespace
+
+std::unique_ptr<DutLib> DutLib::Create(
+    const std::string& fpga, uint64_t timeout_ms, bool disable_dft_on_reset,
+    uint16_t usb_vid, uint16_t usb_pid, const std::string& usb_serial,
+    uint32_t retries, uint64_t retry_delay_ms,
+    const std::string& openocd_adapter_config,
+    const std::vector<std::string>& conf, uint64_t init_retry_window_ms) {
+  OtLibSetLogCallback(OtLibLog);
+  std::vector<const char*> conf_paths;
+  for (const std::string& path : conf) {
+    conf_paths.push_back(path.c_str());
+  }
+  OtLibTransport* transport = OtLibFpgaTransportInit(
+      fpga.c_str(), disable_dft_on_reset, usb_vid, usb_pid, usb_serial.c_str(),
+      openocd_adapter_config.c_str(), conf_paths.data(), conf_paths.size(),
+      timeout_ms, retries, retry_delay_ms, init_retry_window_ms);
+  if (transport == nullptr) {
+    LOG(ERROR) << "OtLibFpgaTransportInit failed: " << OtLibLastError();
+    return nullptr;
+  }
+  return absl::WrapUnique<DutLib>(new DutLib(transport));
+}
+
+std::unique_ptr<DutLib> DutLib::CreateVerilator(const DutVerilatorOpts& opts,
+                                                uint64_t timeout_ms) {
+  OtLibSetLogCallback(OtLibLog);
+  // Collect the C string views of `opts`, which outlive the init call below.
+  std::vector<const char*> flash;
+  for (const std::string& image : opts.flash) {
+    flash.push_back(image.c_str());
+  }
+  std::vector<const char*> args;
+  for (const std::string& arg : opts.args) {
+    args.push_back(arg.c_str());
+  }
+  OtLibVerilatorOpts otlib_opts = {
+      .verilator_bin = opts.bin.c_str(),
+      .verilator_rom = opts.rom.c_str(),
+      .verilator_flash = flash.data(),
+      .verilator_flash_count = flash.size(),
+      .verilator_otp = opts.otp.c_str(),
+      .verilator_timeout_ms = opts.timeout_ms,
+      .verilator_args = args.data(),
+      .verilator_args_count = args.size(),
+  };
+  OtLibTransport* transport =
+      OtLibVerilatorTransportInit(&otlib_opts, timeout_ms);
+  if (transport == nullptr) {
+    LOG(ERROR) << "OtLibVerilatorTransportInit failed: " << OtLibLastError();
+    return nullptr;
+  }
+  return absl::WrapUnique<DutLib>(new DutLib(transport));
+}
+
+std::unique_ptr<DutLib> DutLib::CreateProxy(const std::string& proxy,
+                                            uint32_t port,
+                                            uint64_t timeout_ms) {
+  OtLibSetLogCallback(OtLibLog);
+  OtLibTransport* transport =
+      OtLibProxyTransportInit(proxy.c_str(), port, timeout_ms);
+  if (transport == nullptr) {
+    LOG(ERROR) << "OtLibProxyTransportInit failed: " << OtLibLastError();
+    return nullptr;
+  }
+  return absl::WrapUnique<DutLib>(new DutLib(transport));
+}
+
+std::unique_ptr<DutLib> DutLib::CreateTi50Emulator(
+    const std::string& instance_prefix, const std::string& executable_directory,
+    const std::string& executable, uint64_t timeout_ms) {
+  OtLibSetLogCallback(OtLibLog);
+  OtLibTransport* transport = OtLibTi50EmulatorTransportInit(
+      instance_prefix.c_str(), executable_directory.c_str(),
+      executable.c_str(), timeout_ms);
+  if (transport == nullptr) {
+    LOG(ERROR) << "OtLibTi50EmulatorTransportInit failed: " << OtLibLastError();
+    return nullptr;
+  }
+  return absl::WrapUnique<DutLib>(new DutLib(transport));
+}
+
+DutLib::~DutLib() {
+  // The console borrows the transport, so it must be closed first.
+  OtLibConsoleClose(console_);
+  OtLibFpgaTransportDestroy(transport_);
+}
+
+void DutLib::DutSetLogFile(const std::string& path) {
+  LOG(INFO) << "in DutLib::DutSetLogFile";
+  CheckOtLibStatus(OtLibSetLogFile(path.c_str()), "OtLibSetLogFile");
+}
+
+std::string DutLib::DutGetVersion() {
+  std::string version(OtLibGetVersion(nullptr, 0), '\0');
+  OtLibGetVersion(version.data(), version.size() + 1);
+  return version;
+}
+
+void DutLib::DutSetDryRun(bool enabled) { OtLibSetDryRun(enabled); }
+
+uint64_t DutLib::DutGetLastOpDurationUs(DutOp op) {
+  return OtLibGetLastOpDurationUs(op);
 }
 
-std::unique_ptr<DutLib> DutLib::Create(const std::string& fpga) {
-  return absl::WrapUnique<DutLib>(
-      new DutLib(OtLibFpgaTransportInit(fpga.c_str())));
+void DutLib::DutRequestCancel() {
+  LOG(INFO) << "in DutLib::DutRequestCancel";
+  CheckOtLibStatus(OtLibRequestCancel(transport_), "OtLibRequestCancel");
 }
 
-void DutLib::DutFpgaLoadBitstream(const std::string& fpga_bitstream) {
+void DutLib::DutResetTarget(uint64_t reset_delay_ms) {
+  LOG(INFO) << "in DutLib::DutResetTarget";
+  CheckOtLibStatus(OtLibResetTarget(transport_, reset_delay_ms),
+                   "OtLibResetTarget");
+}
+
+void DutLib::DutApplyStrapping(const std::string& name) {
+  LOG(INFO) << "in DutLib::DutApplyStrapping";
+  CheckOtLibStatus(OtLibApplyStrapping(transport_, name.c_str()),
+                   "OtLibApplyStrapping");
+}
+
+void DutLib::DutRemoveStrapping(const std::string& name) {
+  LOG(INFO) << "in DutLib::DutRemoveStrapping";
+  CheckOtLibStatus(OtLibRemoveStrapping(transport_, name.c_str()),
+                   "OtLibRemoveStrapping");
+}
+
+bool DutLib::DutGpioRead(const std::string& pin) {
+  LOG(INFO) << "in DutLib::DutGpioRead";
+  bool level = false;
+  CheckOtLibStatus(OtLibGpioRead(transport_, pin.c_str(), &level),
+                   "OtLibGpioRead");
+  return level;
+}
+
+void DutLib::DutGpioWrite(const std::string& pin, bool level) {
+  LOG(INFO) << "in DutLib::DutGpioWrite";
+  CheckOtLibStatus(OtLibGpioWrite(transport_, pin.c_str(), level),
+                   "OtLibGpioWrite");
+}
+
+void DutLib::DutSetPinMode(const std::string& pin, DutPinMode mode) {
+  LOG(INFO) << "in DutLib::DutSetPinMode";
+  CheckOtLibStatus(OtLibSetPinMode(transport_, pin.c_str(), mode),
+                   "OtLibSetPinMode");
+}
+
+void DutLib::DutFpgaLoadBitstream(const std::string& fpga_bitstream,
+                                  uint64_t rom_reset_pulse_ms,
+                                  uint64_t rom_timeout_ms,
+                                  bool clear_bitstream,
+                                  void (*progress_cb)(uint32_t percent)) {
   LOG(INFO) << "in DutLib::DutFpgaLoadBitstream";
-  OtLibFpgaLoadBitstream(transport_, fpga_bitstream.c_str());
+  OtLibStatus status =
+      OtLibFpgaLoadBitstream(transport_, fpga_bitstream.c_str(),
+                             rom_reset_pulse_ms, rom_timeout_ms,
+                             clear_bitstream, progress_cb);
+  CheckOtLibStatus(status, "OtLibFpgaLoadBitstream");
 }
 
-void DutLib::DutLoadSramElf(const std::string& openocd, const std::string& elf,
-                            bool wait_for_done, uint64_t timeout_ms) {
+void DutLib::DutFpgaLoadBitstreamBuf(const uint8_t* data, size_t len,
+                                     uint64_t rom_reset_pulse_ms,
+                                     uint64_t rom_timeout_ms,
+                                     bool clear_bitstream,
+                                     void (*progress_cb)(uint32_t percent)) {
+  LOG(INFO) << "in DutLib::DutFpgaLoadBitstreamBuf";
+  OtLibStatus status =
+      OtLibFpgaLoadBitstreamBuf(transport_, data, len, rom_reset_pulse_ms,
+                                rom_timeout_ms, clear_bitstream, progress_cb);
+  CheckOtLibStatus(status, "OtLibFpgaLoadBitstreamBuf");
+}
+
+DutSramResult DutLib::DutLoadSramElf(const std::string& openocd,
+                                     const std::string& elf,
+                                     bool wait_for_done, uint64_t timeout_ms,
+                                     uint32_t adapter_speed_khz,
+                                     bool skip_crc, bool jtag_log_stdio) {
   LOG(INFO) << "in DutLib::DutLoadSramElf";
-  OtLibLoadSramElf(transport_, openocd.c_str(), elf.c_str(), wait_for_done,
-                   timeout_ms);
+  DutSramResult result = {DutSramExecution::kExecuting, 0};
+  OtLibStatus status = OtLibLoadSramElf(
+      transport_, openocd.c_str(), adapter_speed_khz, jtag_log_stdio,
+      elf.c_str(), skip_crc, wait_for_done, timeout_ms, &result.execution,
+      &result.value);
+  if (status == OtLibStatus::kSramProgramError) {
+    LOG(ERROR) << "OtLibLoadSramElf failed to load or execute the program: "
+               << OtLibLastError();
+    result.execution = DutSramExecution::kError;
+    return result;
+  }
+  CheckOtLibStatus(status, "OtLibLoadSramElf");
+  return result;
+}
+
+DutSramResult DutLib::DutLoadSramVmem(const std::string& openocd,
+                                      const std::string& vmem,
+                                      uint32_t load_addr, bool wait_for_done,
+                                      uint64_t timeout_ms,
+                                      uint32_t adapter_speed_khz,
+                                      bool skip_crc, bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutLoadSramVmem";
+  DutSramResult result = {DutSramExecution::kExecuting, 0};
+  OtLibStatus status = OtLibLoadSramVmem(
+      transport_, openocd.c_str(), adapter_speed_khz, jtag_log_stdio,
+      vmem.c_str(), load_addr, skip_crc, wait_for_done, timeout_ms,
+      &result.execution, &result.value);
+  if (status == OtLibStatus::kSramProgramError) {
+    LOG(ERROR) << "OtLibLoadSramVmem failed to load or execute the program: "
+               << OtLibLastError();
+    result.execution = DutSramExecution::kError;
+    return result;
+  }
+  CheckOtLibStatus(status, "OtLibLoadSramVmem");
+  return result;
 }
 
-void DutLib::DutBootstrap(const std::string& bin) {
+void DutLib::DutBootstrap(const std::string& bin, uint32_t baudrate,
+                          bool flow_control, DutBootstrapProtocol protocol,
+                          uint32_t retries, uint64_t retry_delay_ms,
+                          uint64_t inter_frame_delay_us,
+                          uint64_t flash_erase_delay_ms, uint32_t spi_speed_hz,
+                          DutSpiMode spi_mode) {
   LOG(INFO) << "in DutLib::DutBootstrap";
-  OtLibBootstrap(transport_, bin.c_str());
+  CheckOtLibStatus(
+      OtLibBootstrap(transport_, bin.c_str(), baudrate, flow_control, protocol,
+                     retries, retry_delay_ms, inter_frame_delay_us,
+                     flash_erase_delay_ms, spi_speed_hz, spi_mode),
+      "OtLibBootstrap");
+}
+
+void DutLib::DutConsoleOpen(const std::string& tx_ready_pin,
+                            const std::string& spi_interface,
+                            uint32_t spi_speed_hz, DutSpiMode spi_mode,
+                            DutPullMode pull_mode, bool active_low) {
+  LOG(INFO) << "in DutLib::DutConsoleOpen";
+  DutConsoleClose();
+  console_ =
+      OtLibConsoleOpen(transport_, spi_interface.c_str(), tx_ready_pin.c_str(),
+                       spi_speed_hz, spi_mode, pull_mode, active_low);
+  if (console_ == nullptr) {
+    LOG(FATAL) << "OtLibConsoleOpen failed: " << OtLibLastError();
+  }
+}
+
+void DutLib::DutConsoleClose() {
+  OtLibConsoleClose(console_);
+  console_ = nullptr;
 }
 
-void DutLib::DutConsoleWaitForRx(const char* msg, uint64_t timeout_ms) {
+void DutLib::DutConsoleWaitForRx(const char* msg, uint64_t timeout_ms,
+                                 const std::string& tx_ready_pin,
+                                 const std::string& spi_interface,
+                                 DutConsoleKind kind,
+                                 void (*on_heartbeat)(uint64_t elapsed_ms),
+                                 uint32_t spi_speed_hz, DutSpiMode spi_mode,
+                                 DutPullMode pull_mode, bool active_low) {
   LOG(INFO) << "in DutLib::DutConsoleWaitForRx";
-  OtLibConsoleWaitForRx(transport_, msg, timeout_ms);
+  if (console_ != nullptr && kind == DutConsoleKind::kSpi) {
+    CheckOtLibStatus(
+        OtLibConsoleHandleWaitForRx(console_, msg, timeout_ms, on_heartbeat),
+        "OtLibConsoleHandleWaitForRx");
+    return;
+  }
+  OtLibStatus status = OtLibConsoleWaitForRx(
+      transport_, kind, spi_interface.c_str(), tx_ready_pin.c_str(),
+      spi_speed_hz, spi_mode, pull_mode, active_low, msg, timeout_ms,
+      on_heartbeat);
+  CheckOtLibStatus(status, "OtLibConsoleWaitForRx");
+}
+
+std::string DutLib::DutConsoleWaitForRxCapture(
+    const char* msg, uint64_t timeout_ms, size_t group,
+    const std::string& tx_ready_pin, const std::string& spi_interface,
+    DutConsoleKind kind, uint32_t spi_speed_hz, DutSpiMode spi_mode,
+    DutPullMode pull_mode, bool active_low) {
+  LOG(INFO) << "in DutLib::DutConsoleWaitForRxCapture";
+  // The capture is part of a console message, which cannot be longer.
+  std::string capture(kMaxRxMsgSizeInBytes, '\0');
+  size_t len = 0;
+  if (console_ != nullptr && kind == DutConsoleKind::kSpi) {
+    CheckOtLibStatus(OtLibConsoleHandleWaitForRxCapture(
+                         console_, msg, group, capture.data(),
+                         capture.size() + 1, &len, timeout_ms),
+                     "OtLibConsoleHandleWaitForRxCapture");
+  } else {
+    OtLibStatus status = OtLibConsoleWaitForRxCapture(
+        transport_, kind, spi_interface.c_str(), tx_ready_pin.c_str(),
+        spi_speed_hz, spi_mode, pull_mode, active_low, msg, group,
+        capture.data(), capture.size() + 1, &len, timeout_ms);
+    CheckOtLibStatus(status, "OtLibConsoleWaitForRxCapture");
+  }
+  capture.resize(len);
+  return capture;
 }
 
-void DutLib::DutConsoleRx(const std::string& sync_msg,
+std::vector<uint8_t> DutLib::DutExtractUdsCert(
+    const std::string& uds_cert_regex, uint64_t timeout_ms,
+    const std::string& tx_ready_pin,
+    const std::string& spi_interface, DutConsoleKind kind,
+    uint32_t spi_speed_hz, DutSpiMode spi_mode, DutPullMode pull_mode,
+    bool active_low) {
+  LOG(INFO) << "in DutLib::DutExtractUdsCert";
+  // The certificate is encoded in a console message, which cannot be longer.
+  std::vector<uint8_t> cert(kMaxRxMsgSizeInBytes);
+  size_t len = 0;
+  if (console_ != nullptr && kind == DutConsoleKind::kSpi) {
+    CheckOtLibStatus(OtLibConsoleHandleExtractUdsCert(
+                         console_, uds_cert_regex.c_str(), cert.data(),
+                         cert.size(), &len, timeout_ms),
+                     "OtLibConsoleHandleExtractUdsCert");
+  } else {
+    OtLibStatus status = OtLibExtractUdsCert(
+        transport_, kind, spi_interface.c_str(), tx_ready_pin.c_str(),
+        spi_speed_hz, spi_mode, pull_mode, active_low, uds_cert_regex.c_str(),
+        cert.data(), cert.size(), &len, timeout_ms);
+    CheckOtLibStatus(status, "OtLibExtractUdsCert");
+  }
+  cert.resize(len);
+  return cert;
+}
+
+size_t DutLib::DutConsoleWaitForAnyOf(const std::vector<std::string>& patterns,
+                                      uint64_t timeout_ms,
+                                      const std::string& tx_ready_pin,
+                                      const std::string& spi_interface,
+                                      DutConsoleKind kind,
+                                      uint32_t spi_speed_hz,
+                                      DutSpiMode spi_mode,
+                                      DutPullMode pull_mode, bool active_low) {
+  LOG(INFO) << "in DutLib::DutConsoleWaitForAnyOf";
+  std::vector<const char*> c_patterns;
+  for (const std::string& pattern : patterns) {
+    c_patterns.push_back(pattern.c_str());
+  }
+  size_t index = 0;
+  if (console_ != nullptr && kind == DutConsoleKind::kSpi) {
+    CheckOtLibStatus(
+        OtLibConsoleHandleWaitForAnyOf(console_, c_patterns.data(),
+                                       c_patterns.size(), &index, timeout_ms),
+        "OtLibConsoleHandleWaitForAnyOf");
+    return index;
+  }
+  OtLibStatus status = OtLibConsoleWaitForAnyOf(
+      transport_, kind, spi_interface.c_str(), tx_ready_pin.c_str(),
+      spi_speed_hz, spi_mode, pull_mode, active_low, c_patterns.data(),
+      c_patterns.size(), &index, timeout_ms);
+  CheckOtLibStatus(status, "OtLibConsoleWaitForAnyOf");
+  return index;
+}
+
+bool DutLib::DutConsoleRx(const std::string& sync_msg,
                           dut_spi_frame_t* spi_frames, size_t* num_frames,
-                          bool skip_crc_check, bool quiet,
-                          uint64_t timeout_ms) {
+                          bool skip_crc_check, bool quiet, uint64_t timeout_ms,
+                          const std::string& tx_ready_pin,
+                          const std::string& spi_interface, DutConsoleKind kind,
+                          DutCrcKind crc_kind, bool binary,
+                          const std::string& success_regex,
+                          const std::string& failure_regex, size_t frame_size,
+                          bool timestamp, bool newline,
+                          void (*on_heartbeat)(uint64_t elapsed_ms),
+                          uint32_t spi_speed_hz, DutSpiMode spi_mode,
+                          DutPullMode pull_mode, bool active_low) {
   LOG(INFO) << "in DutLib::DutConsoleRx";
-  OtLibConsoleRx(transport_, sync_msg.c_str(), spi_frames, num_frames,
-                 skip_crc_check, quiet, timeout_ms);
+  OtLibStatus status;
+  const char* fn;
+  if (console_ != nullptr && kind == DutConsoleKind::kSpi) {
+    status = OtLibConsoleHandleRx(
+        console_, sync_msg.c_str(), spi_frames, num_frames, frame_size,
+        skip_crc_check, crc_kind, binary, success_regex.c_str(),
+        failure_regex.c_str(), quiet, timestamp, newline, timeout_ms,
+        on_heartbeat);
+    fn = "OtLibConsoleHandleRx";
+  } else {
+    status = OtLibConsoleRx(
+        transport_, kind, spi_interface.c_str(), tx_ready_pin.c_str(),
+        spi_speed_hz, spi_mode, pull_mode, active_low, sync_msg.c_str(),
+        spi_frames, num_frames, frame_size, skip_crc_check, crc_kind, binary,
+        success_regex.c_str(), failure_regex.c_str(), quiet, timestamp, newline,
+        timeout_ms, on_heartbeat);
+    fn = "OtLibConsoleRx";
+  }
+  if (status == OtLibStatus::kDeviceError) {
+    LOG(ERROR) << fn << " received an error response: " << OtLibLastError();
+    return false;
+  }
+  CheckOtLibStatus(status, fn);
+  return true;
+}
+
+bool DutLib::DutConsoleRxCapture(
+    const std::string& sync_msg, dut_spi_frame_t* spi_frames,
+    size_t* num_frames, bool skip_crc_check, int capture_fd,
+    uint64_t timeout_ms, const std::string& tx_ready_pin,
+    const std::string& spi_interface, DutConsoleKind kind, DutCrcKind crc_kind,
+    bool binary, const std::string& success_regex,
+    const std::string& failure_regex, size_t frame_size, bool timestamp,
+    bool newline, void (*on_heartbeat)(uint64_t elapsed_ms),
+    uint32_t spi_speed_hz, DutSpiMode spi_mode, DutPullMode pull_mode,
+    bool active_low) {
+  LOG(INFO) << "in DutLib::DutConsoleRxCapture";
+  OtLibStatus status = OtLibConsoleRxCapture(
+      transport_, kind, spi_interface.c_str(), tx_ready_pin.c_str(),
+      spi_speed_hz, spi_mode, pull_mode, active_low, sync_msg.c_str(),
+      spi_frames, num_frames, frame_size, skip_crc_check, crc_kind, binary,
+      success_regex.c_str(), failure_regex.c_str(), capture_fd, timestamp,
+      newline, timeout_ms, on_heartbeat);
+  if (status == OtLibStatus::kDeviceError) {
+    LOG(ERROR) << "OtLibConsoleRxCapture received an error response: "
+               << OtLibLastError();
+    return false;
+  }
+  CheckOtLibStatus(status, "OtLibConsoleRxCapture");
+  return true;
+}
+
+bool DutLib::DutConsoleRxStream(
+    const std::string& sync_msg,
+    void (*on_frame)(const uint8_t* data, size_t len), bool skip_crc_check,
+    bool quiet, uint64_t timeout_ms, const std::string& tx_ready_pin,
+    const std::string& spi_interface, DutConsoleKind kind, DutCrcKind crc_kind,
+    bool binary, const std::string& success_regex,
+    const std::string& failure_regex, size_t frame_size, uint32_t spi_speed_hz,
+    DutSpiMode spi_mode, DutPullMode pull_mode, bool active_low) {
+  LOG(INFO) << "in DutLib::DutConsoleRxStream";
+  OtLibStatus status;
+  const char* fn;
+  if (console_ != nullptr && kind == DutConsoleKind::kSpi) {
+    status = OtLibConsoleHandleRxStream(
+        console_, sync_msg.c_str(), on_frame, frame_size, skip_crc_check,
+        crc_kind, binary, success_regex.c_str(), failure_regex.c_str(), quiet,
+        timeout_ms);
+    fn = "OtLibConsoleHandleRxStream";
+  } else {
+    status = OtLibConsoleRxStream(
+        transport_, kind, spi_interface.c_str(), tx_ready_pin.c_str(),
+        spi_speed_hz, spi_mode, pull_mode, active_low, sync_msg.c_str(),
+        on_frame, frame_size, skip_crc_check, crc_kind, binary,
+        success_regex.c_str(), failure_regex.c_str(), quiet, timeout_ms);
+    fn = "OtLibConsoleRxStream";
+  }
+  if (status == OtLibStatus::kDeviceError) {
+    LOG(ERROR) << fn << " received an error response: " << OtLibLastError();
+    return false;
+  }
+  CheckOtLibStatus(status, fn);
+  return true;
+}
+
+DutConsolePollResult DutLib::DutConsoleRxPoll(
+    dut_spi_frame_t* spi_frames, size_t* num_frames, bool skip_crc_check,
+    bool quiet, uint64_t slice_ms, DutCrcKind crc_kind, bool binary,
+    const std::string& success_regex, const std::string& failure_regex,
+    size_t frame_size) {
+  if (console_ == nullptr) {
+    LOG(FATAL) << "DutConsoleRxPoll requires a console opened with "
+                  "DutConsoleOpen";
+  }
+  OtLibStatus status = OtLibConsoleRxPoll(
+      console_, spi_frames, num_frames, frame_size, skip_crc_check, crc_kind,
+      binary, success_regex.c_str(), failure_regex.c_str(), quiet, slice_ms);
+  if (status == OtLibStatus::kWouldBlock) {
+    return DutConsolePollResult::kPending;
+  }
+  if (status == OtLibStatus::kDeviceError) {
+    LOG(ERROR) << "OtLibConsoleRxPoll received an error response: "
+               << OtLibLastError();
+    return DutConsolePollResult::kDeviceError;
+  }
+  CheckOtLibStatus(status, "OtLibConsoleRxPoll");
+  return DutConsolePollResult::kOk;
+}
+
+bool DutLib::DutConsoleTransact(const uint8_t* tx_frame, size_t tx_frame_size,
+                                dut_spi_frame_t* spi_frames, size_t* num_frames,
+                                bool skip_crc_check, bool quiet,
+                                uint64_t timeout_ms,
+                                const std::string& tx_ready_pin,
+                                const std::string& spi_interface,
+                                DutConsoleKind kind, DutCrcKind crc_kind,
+                                size_t frame_size, uint32_t spi_speed_hz,
+                                DutSpiMode spi_mode, DutPullMode pull_mode,
+                                bool active_low) {
+  LOG(INFO) << "in DutLib::DutConsoleTransact";
+  OtLibStatus status;
+  const char* fn;
+  if (console_ != nullptr && kind == DutConsoleKind::kSpi) {
+    status = OtLibConsoleHandleTransact(
+        console_, tx_frame, tx_frame_size, spi_frames, num_frames, frame_size,
+        skip_crc_check, crc_kind, quiet, timeout_ms);
+    fn = "OtLibConsoleHandleTransact";
+  } else {
+    status = OtLibConsoleTransact(
+        transport_, kind, spi_interface.c_str(), tx_ready_pin.c_str(),
+        spi_speed_hz, spi_mode, pull_mode, active_low, tx_frame, tx_frame_size,
+        spi_frames, num_frames, frame_size, skip_crc_check, crc_kind, quiet,
+        timeout_ms);
+    fn = "OtLibConsoleTransact";
+  }
+  if (status == OtLibStatus::kDeviceError) {
+    LOG(ERROR) << fn << " received an error response: " << OtLibLastError();
+    return false;
+  }
+  CheckOtLibStatus(status, fn);
+  return true;
 }
 
 void DutLib::DutConsoleTx(const std::string& sync_msg, const uint8_t* spi_frame,
-                          size_t spi_frame_size, uint64_t timeout_ms) {
+                          size_t spi_frame_size, uint64_t timeout_ms,
+                          const std::string& tx_ready_pin,
+                          const std::string& spi_interface, DutConsoleKind kind,
+                          bool compress, uint32_t spi_speed_hz,
+                          DutSpiMode spi_mode, DutPullMode pull_mode,
+                          bool active_low) {
   LOG(INFO) << "in DutLib::DutConsoleTx";
-  OtLibConsoleTx(transport_, sync_msg.c_str(), spi_frame, spi_frame_size,
-                 timeout_ms);
+  if (console_ != nullptr && kind == DutConsoleKind::kSpi) {
+    OtLibStatus status =
+        OtLibConsoleHandleTx(console_, sync_msg.c_str(), spi_frame,
+                             spi_frame_size, compress, timeout_ms);
+    CheckOtLibStatus(status, "OtLibConsoleHandleTx");
+    return;
+  }
+  OtLibStatus status = OtLibConsoleTx(
+      transport_, kind, spi_interface.c_str(), tx_ready_pin.c_str(),
+      spi_speed_hz, spi_mode, pull_mode, active_low, sync_msg.c_str(),
+      spi_frame, spi_frame_size, compress, timeout_ms);
+  CheckOtLibStatus(status, "OtLibConsoleTx");
+}
+
+void DutLib::DutConsoleTxFrames(const std::string& sync_msg,
+                                const dut_spi_frame_t* frames,
+                                size_t num_frames, uint64_t timeout_ms,
+                                const std::string& tx_ready_pin,
+                                const std::string& spi_interface,
+                                DutConsoleKind kind, uint32_t spi_speed_hz,
+                                DutSpiMode spi_mode, DutPullMode pull_mode,
+                                bool active_low) {
+  LOG(INFO) << "in DutLib::DutConsoleTxFrames";
+  if (console_ != nullptr && kind == DutConsoleKind::kSpi) {
+    OtLibStatus status = OtLibConsoleHandleTxFrames(
+        console_, sync_msg.c_str(), frames, num_frames, timeout_ms);
+    CheckOtLibStatus(status, "OtLibConsoleHandleTxFrames");
+    return;
+  }
+  OtLibStatus status = OtLibConsoleTxFrames(
+      transport_, kind, spi_interface.c_str(), tx_ready_pin.c_str(),
+      spi_speed_hz, spi_mode, pull_mode, active_low, sync_msg.c_str(), frames,
+      num_frames, timeout_ms);
+  CheckOtLibStatus(status, "OtLibConsoleTxFrames");
+}
+
+void DutLib::DutConsoleFlush(uint64_t drain_ms, const std::string& tx_ready_pin,
+                             const std::string& spi_interface,
+                             DutConsoleKind kind, uint32_t spi_speed_hz,
+                             DutSpiMode spi_mode, DutPullMode pull_mode,
+                             bool active_low) {
+  LOG(INFO) << "in DutLib::DutConsoleFlush";
+  if (console_ != nullptr && kind == DutConsoleKind::kSpi) {
+    CheckOtLibStatus(OtLibConsoleHandleFlush(console_, drain_ms),
+                     "OtLibConsoleHandleFlush");
+    return;
+  }
+  OtLibStatus status = OtLibConsoleFlush(transport_, kind,
+                                         spi_interface.c_str(),
+                                         tx_ready_pin.c_str(), spi_speed_hz,
+                                         spi_mode, pull_mode, active_low,
+                                         drain_ms);
+  CheckOtLibStatus(status, "OtLibConsoleFlush");
 }
 
-void DutLib::DutResetAndLock(const std::string& openocd) {
+void DutLib::DutResetAndLock(const std::string& openocd,
+                             uint32_t adapter_speed_khz, bool jtag_log_stdio) {
   LOG(INFO) << "in DutLib::DutResetAndLock";
-  OtLibResetAndLock(transport_, openocd.c_str());
+  CheckOtLibStatus(OtLibResetAndLock(transport_, openocd.c_str(),
+                                     adapter_speed_khz, jtag_log_stdio),
+                   "OtLibResetAndLock");
 }
 
-void DutLib::DutLcTransition(const std::string& openocd, const uint8_t* token,
-                             size_t token_size, uint32_t target_lc_state) {
+void DutLib::DutResetNoLock(const std::string& openocd, uint64_t reset_delay_ms,
+                            uint32_t adapter_speed_khz, bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutResetNoLock";
+  CheckOtLibStatus(OtLibResetNoLock(transport_, openocd.c_str(),
+                                    adapter_speed_khz, jtag_log_stdio,
+                                    reset_delay_ms),
+                   "OtLibResetNoLock");
+}
+
+bool DutLib::DutLcTransition(const std::string& openocd, const uint8_t* token,
+                             size_t token_size, uint32_t target_lc_state,
+                             uint32_t adapter_speed_khz, uint32_t* out_state,
+                             bool use_external_clk, bool force,
+                             DutJtagTap reset_tap_straps,
+                             bool jtag_log_stdio, bool token_is_hashed) {
   LOG(INFO) << "in DutLib::DutLcTransition";
-  OtLibLcTransition(transport_, openocd.c_str(), token, token_size,
-                    target_lc_state);
+  uint32_t lc_state = 0;
+  OtLibStatus status = OtLibLcTransition(
+      transport_, openocd.c_str(), adapter_speed_khz, jtag_log_stdio, token,
+      token_size, token_is_hashed, target_lc_state, use_external_clk, force,
+      reset_tap_straps, out_state != nullptr ? out_state : &lc_state);
+  if (status == OtLibStatus::kLcMismatch) {
+    LOG(ERROR) << "OtLibLcTransition did not reach the target state: "
+               << OtLibLastError();
+    return false;
+  }
+  CheckOtLibStatus(status, "OtLibLcTransition");
+  return true;
+}
+
+bool DutLib::DutLcTransitionHex(const std::string& openocd,
+                                const std::string& token_hex,
+                                uint32_t target_lc_state,
+                                uint32_t adapter_speed_khz,
+                                uint32_t* out_state, bool use_external_clk,
+                                bool force, DutJtagTap reset_tap_straps,
+                                bool jtag_log_stdio, bool token_is_hashed) {
+  LOG(INFO) << "in DutLib::DutLcTransitionHex";
+  uint32_t lc_state = 0;
+  OtLibStatus status = OtLibLcTransitionHex(
+      transport_, openocd.c_str(), adapter_speed_khz, jtag_log_stdio,
+      token_hex.c_str(), token_is_hashed, target_lc_state, use_external_clk,
+      force, reset_tap_straps, out_state != nullptr ? out_state : &lc_state);
+  if (status == OtLibStatus::kLcMismatch) {
+    LOG(ERROR) << "OtLibLcTransitionHex did not reach the target state: "
+               << OtLibLastError();
+    return false;
+  }
+  CheckOtLibStatus(status, "OtLibLcTransitionHex");
+  return true;
+}
+
+bool DutLib::DutVolatileRawUnlock(const std::string& openocd,
+                                  const uint8_t* token, size_t token_size,
+                                  uint32_t target_lc_state,
+                                  uint32_t adapter_speed_khz,
+                                  uint32_t* out_state, bool use_external_clk,
+                                  DutJtagTap post_transition_tap,
+                                  bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutVolatileRawUnlock";
+  uint32_t lc_state = 0;
+  OtLibStatus status = OtLibVolatileRawUnlock(
+      transport_, openocd.c_str(), adapter_speed_khz, jtag_log_stdio, token,
+      token_size, target_lc_state, use_external_clk, post_transition_tap,
+      out_state != nullptr ? out_state : &lc_state);
+  if (status == OtLibStatus::kLcMismatch) {
+    LOG(ERROR) << "OtLibVolatileRawUnlock did not reach the target state: "
+               << OtLibLastError();
+    return false;
+  }
+  CheckOtLibStatus(status, "OtLibVolatileRawUnlock");
+  return true;
+}
+
+bool DutLib::DutLcTransitionSequence(const std::string& openocd,
+                                     const uint32_t* states,
+                                     const dut_spi_frame_t* tokens,
+                                     size_t num_steps,
+                                     uint32_t adapter_speed_khz,
+                                     bool use_external_clk, size_t* out_step,
+                                     uint32_t* out_state,
+                                     bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutLcTransitionSequence";
+  size_t step = 0;
+  uint32_t lc_state = 0;
+  OtLibStatus status = OtLibLcTransitionSequence(
+      transport_, openocd.c_str(), adapter_speed_khz, jtag_log_stdio, states,
+      tokens, num_steps, use_external_clk,
+      out_step != nullptr ? out_step : &step,
+      out_state != nullptr ? out_state : &lc_state);
+  if (status == OtLibStatus::kLcMismatch) {
+    LOG(ERROR) << "OtLibLcTransitionSequence did not reach the target state: "
+               << OtLibLastError();
+    return false;
+  }
+  CheckOtLibStatus(status, "OtLibLcTransitionSequence");
+  return true;
+}
+
+bool DutLib::DutRmaUnlock(const std::string& openocd, const uint8_t* token,
+                          size_t token_size, uint32_t adapter_speed_khz,
+                          uint32_t* out_state, bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutRmaUnlock";
+  uint32_t lc_state = 0;
+  OtLibStatus status = OtLibRmaUnlock(
+      transport_, openocd.c_str(), adapter_speed_khz, jtag_log_stdio, token,
+      token_size, out_state != nullptr ? out_state : &lc_state);
+  if (status == OtLibStatus::kLcMismatch) {
+    LOG(ERROR) << "OtLibRmaUnlock did not reach RMA: " << OtLibLastError();
+    return false;
+  }
+  CheckOtLibStatus(status, "OtLibRmaUnlock");
+  return true;
+}
+
+uint32_t DutLib::DutReadLcState(const std::string& openocd,
+                                uint32_t adapter_speed_khz,
+                                bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutReadLcState";
+  uint32_t lc_state = 0;
+  OtLibStatus status =
+      OtLibReadLcState(transport_, openocd.c_str(), adapter_speed_khz,
+                       jtag_log_stdio, &lc_state);
+  CheckOtLibStatus(status, "OtLibReadLcState");
+  return lc_state;
 }
 
-void DutLib::DutCheckTransportImgBoot(const char* owner_fw_boot_msg,
-                                      uint64_t timeout_ms) {
+bool DutLib::DutVerifyLcState(const std::string& openocd,
+                              uint32_t expected_state,
+                              uint32_t adapter_speed_khz, uint32_t* out_state,
+                              bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutVerifyLcState";
+  uint32_t lc_state = 0;
+  OtLibStatus status = OtLibVerifyLcState(
+      transport_, openocd.c_str(), adapter_speed_khz, jtag_log_stdio,
+      expected_state, out_state != nullptr ? out_state : &lc_state);
+  if (status == OtLibStatus::kLcMismatch) {
+    LOG(ERROR) << "DUT is not in the expected LC state: " << OtLibLastError();
+    return false;
+  }
+  CheckOtLibStatus(status, "OtLibVerifyLcState");
+  return true;
+}
+
+uint32_t DutLib::DutReadLcTransitionCount(const std::string& openocd,
+                                          uint32_t adapter_speed_khz,
+                                          bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutReadLcTransitionCount";
+  uint32_t count = 0;
+  OtLibStatus status =
+      OtLibReadLcTransitionCount(transport_, openocd.c_str(), adapter_speed_khz,
+                                 jtag_log_stdio, &count);
+  CheckOtLibStatus(status, "OtLibReadLcTransitionCount");
+  return count;
+}
+
+std::vector<uint8_t> DutLib::DutReadDeviceId(const std::string& openocd,
+                                             uint32_t adapter_speed_khz,
+                                             bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutReadDeviceId";
+  std::vector<uint8_t> device_id(kDeviceIdSizeInBytes);
+  OtLibStatus status =
+      OtLibReadDeviceId(transport_, openocd.c_str(), adapter_speed_khz,
+                        jtag_log_stdio, device_id.data(), device_id.size());
+  CheckOtLibStatus(status, "OtLibReadDeviceId");
+  return device_id;
+}
+
+uint32_t DutLib::DutReadJtagIdcode(const std::string& openocd,
+                                   uint32_t adapter_speed_khz,
+                                   bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutReadJtagIdcode";
+  uint32_t idcode = 0;
+  OtLibStatus status =
+      OtLibReadJtagIdcode(transport_, openocd.c_str(), adapter_speed_khz,
+                          jtag_log_stdio, &idcode);
+  CheckOtLibStatus(status, "OtLibReadJtagIdcode");
+  return idcode;
+}
+
+uint32_t DutLib::DutJtagReadReg(const std::string& openocd, uint32_t addr,
+                                uint32_t adapter_speed_khz,
+                                bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutJtagReadReg";
+  uint32_t value = 0;
+  OtLibStatus status =
+      OtLibJtagReadReg(transport_, openocd.c_str(), adapter_speed_khz,
+                       jtag_log_stdio, addr, &value);
+  CheckOtLibStatus(status, "OtLibJtagReadReg");
+  return value;
+}
+
+void DutLib::DutJtagWriteReg(const std::string& openocd, uint32_t addr,
+                             uint32_t val, uint32_t adapter_speed_khz,
+                             bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutJtagWriteReg";
+  OtLibStatus status =
+      OtLibJtagWriteReg(transport_, openocd.c_str(), adapter_speed_khz,
+                        jtag_log_stdio, addr, val);
+  CheckOtLibStatus(status, "OtLibJtagWriteReg");
+}
+
+void DutLib::DutJtagMemRead(const std::string& openocd, uint32_t addr,
+                            uint8_t* buf, size_t len,
+                            uint32_t adapter_speed_khz, bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutJtagMemRead";
+  OtLibStatus status =
+      OtLibJtagMemRead(transport_, openocd.c_str(), adapter_speed_khz,
+                       jtag_log_stdio, addr, buf, len);
+  CheckOtLibStatus(status, "OtLibJtagMemRead");
+}
+
+void DutLib::DutJtagMemWrite(const std::string& openocd, uint32_t addr,
+                             const uint8_t* buf, size_t len,
+                             uint32_t adapter_speed_khz, bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutJtagMemWrite";
+  OtLibStatus status =
+      OtLibJtagMemWrite(transport_, openocd.c_str(), adapter_speed_khz,
+                        jtag_log_stdio, addr, buf, len);
+  CheckOtLibStatus(status, "OtLibJtagMemWrite");
+}
+
+void DutLib::DutReadOtp(const std::string& openocd,
+                        const std::string& partition, uint8_t* buf, size_t len,
+                        uint32_t adapter_speed_khz, bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutReadOtp";
+  OtLibStatus status =
+      OtLibReadOtp(transport_, openocd.c_str(), adapter_speed_khz,
+                   jtag_log_stdio, partition.c_str(), buf, len);
+  CheckOtLibStatus(status, "OtLibReadOtp");
+}
+
+void DutLib::DutCpuHalt(const std::string& openocd,
+                        uint32_t adapter_speed_khz, bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutCpuHalt";
+  OtLibStatus status = OtLibCpuHalt(transport_, openocd.c_str(),
+                                    adapter_speed_khz, jtag_log_stdio);
+  CheckOtLibStatus(status, "OtLibCpuHalt");
+}
+
+void DutLib::DutCpuResume(const std::string& openocd,
+                          uint32_t adapter_speed_khz, bool jtag_log_stdio) {
+  LOG(INFO) << "in DutLib::DutCpuResume";
+  OtLibStatus status = OtLibCpuResume(transport_, openocd.c_str(),
+                                      adapter_speed_khz, jtag_log_stdio);
+  CheckOtLibStatus(status, "OtLibCpuResume");
+}
+
+DutBootResult DutLib::DutCheckTransportImgBoot(
+    const char* owner_fw_boot_msg, uint64_t timeout_ms,
+    const std::string& rom_ext_regex, const std::string& boot_success_regex,
+    const std::vector<std::string>& boot_failure_regexes,
+    uint64_t* out_rom_ext_boot_us, uint64_t* out_owner_fw_boot_us) {
   LOG(INFO) << "in DutLib::DutCheckTransportImgBoot";
-  OtLibCheckTransportImgBoot(transport_, owner_fw_boot_msg, timeout_ms);
+  return DutCheckTransportImgBootWithTimeouts(
+      owner_fw_boot_msg, timeout_ms, timeout_ms, rom_ext_regex,
+      boot_success_regex, boot_failure_regexes, out_rom_ext_boot_us,
+      out_owner_fw_boot_us);
+}
+
+DutBootResult DutLib::DutCheckTransportImgBootWithTimeouts(
+    const char* owner_fw_boot_msg, uint64_t rom_ext_timeout_ms,
+    uint64_t owner_fw_timeout_ms, const std::string& rom_ext_regex,
+    const std::string& boot_success_regex,
+    const std::vector<std::string>& boot_failure_regexes,
+    uint64_t* out_rom_ext_boot_us, uint64_t* out_owner_fw_boot_us) {
+  LOG(INFO) << "in DutLib::DutCheckTransportImgBootWithTimeouts";
+  std::vector<const char*> failure_regexes;
+  for (const std::string& regex : boot_failure_regexes) {
+    failure_regexes.push_back(regex.c_str());
+  }
+  DutBootResult result = DutBootResult::kOwnerBooted;
+  uint64_t rom_ext_boot_us = 0;
+  uint64_t owner_fw_boot_us = 0;
+  OtLibStatus status = OtLibCheckTransportImgBootWithTimeouts(
+      transport_, owner_fw_boot_msg, rom_ext_timeout_ms, owner_fw_timeout_ms,
+      rom_ext_regex.c_str(), boot_success_regex.c_str(),
+      failure_regexes.data(), failure_regexes.size(), &result,
+      &rom_ext_boot_us, &owner_fw_boot_us);
+  if (out_rom_ext_boot_us != nullptr) {
+    *out_rom_ext_boot_us = rom_ext_boot_us;
+  }
+  if (out_owner_fw_boot_us != nullptr) {
+    *out_owner_fw_boot_us = owner_fw_boot_us;
+  }
+  if (status == OtLibStatus::kBootError ||
+      status == OtLibStatus::kConsoleTimeout) {
+    LOG(ERROR) << "DUT failed to boot: " << OtLibLastError();
+    return result;
+  }
+  CheckOtLibStatus(status, "OtLibCheckTransportImgBootWithTimeouts");
+  return result;
 }
 
 }  // namespace test_programs
@@ -7,69 +7,957 @@
 
 #include <memory>
 #include <string>
+#include <vector>
 
 #include "src/ate/ate_api.h"
 
 namespace provisioning {
 namespace test_programs {
 
+// Opaque handle of a transport created by the opentitanlib wrapper.
+//
+// NOTE: must match the definition of OtLibTransport in
+// src/ate/test_programs/otlib_wrapper/src/transport.rs
+struct OtLibTransport;
+
+// Console used to exchange messages with the DUT.
+//
+// NOTE: must match the definition of OtLibConsoleKind in
+// src/ate/test_programs/otlib_wrapper/src/console.rs
+enum class DutConsoleKind : int {
+  kSpi = 0,
+  kUart = 1,
+};
+
+// Direction and pull of a GPIO pin.
+//
+// NOTE: must match the definition of OtLibPinMode in
+// src/ate/test_programs/otlib_wrapper/src/gpio.rs
+enum class DutPinMode : int {
+  kInput = 0,
+  kInputPullUp = 1,
+  kInputPullDown = 2,
+  kPushPull = 3,
+  kOpenDrain = 4,
+};
+
+// Pull resistor of a GPIO input.
+//
+// NOTE: must match the definition of OtLibPullMode in
+// src/ate/test_programs/otlib_wrapper/src/gpio.rs
+enum class DutPullMode : int {
+  kNone = 0,
+  kPullUp = 1,
+  kPullDown = 2,
+};
+
+// CRC-32 variant the device uses to protect its console responses.
+//
+// NOTE: must match the definition of OtLibCrcKind in
+// src/ate/test_programs/otlib_wrapper/src/console.rs
+enum class DutCrcKind : int {
+  kIsoHdlc = 0,
+  kBzip2 = 1,
+  kMpeg2 = 2,
+};
+
+// Protocol used to bootstrap a binary into the DUT's flash.
+//
+// NOTE: must match the definition of OtLibBootstrapProtocol in
+// src/ate/test_programs/otlib_wrapper/src/bootstrap.rs
+enum class DutBootstrapProtocol : int {
+  kPrimitive = 0,
+  kLegacy = 1,
+  kLegacyRescue = 2,
+  kEeprom = 3,
+  kEmulator = 4,
+};
+
+// SPI clock polarity and phase.
+//
+// NOTE: must match the definition of OtLibSpiMode in
+// src/ate/test_programs/otlib_wrapper/src/spi.rs
+enum class DutSpiMode : int {
+  kDefault = 0,
+  kMode0 = 1,
+  kMode1 = 2,
+  kMode2 = 3,
+  kMode3 = 4,
+};
+
+// Optional JTAG TAP selection.
+//
+// NOTE: must match the definition of OtLibJtagTap in
+// src/ate/test_programs/otlib_wrapper/src/jtag.rs
+enum class DutJtagTap : int {
+  kNone = 0,
+  kLcTap = 1,
+  kRiscvTap = 2,
+};
+
+// Severity of a message logged by the opentitanlib wrapper.
+//
+// NOTE: must match the definition of OtLibLogLevel in
+// src/ate/test_programs/otlib_wrapper/src/log.rs
+enum class DutLogLevel : int {
+  kInfo = 0,
+  kWarn = 1,
+  kError = 2,
+};
+
+// Kind of an operation timed by the opentitanlib wrapper.
+//
+// NOTE: must match the definition of OtLibOp in
+// src/ate/test_programs/otlib_wrapper/src/metrics.rs
+enum class DutOp : int {
+  kBitstreamLoad = 0,
+  kBootstrap = 1,
+  kConsoleRx = 2,
+  kConsoleTx = 3,
+  kLcTransition = 4,
+  kSramLoad = 5,
+  kConsoleTransact = 6,
+};
+
+// Outcome of a boot checked by `DutCheckTransportImgBoot`.
+//
+// NOTE: must match the definition of OtLibBootResult in
+// src/ate/test_programs/otlib_wrapper/src/boot.rs
+enum class DutBootResult : int {
+  kOwnerBooted = 0,
+  kUdsCertInvalid = 1,
+  kBootFault = 2,
+  kTimeout = 3,
+  kRomExtFailed = 4,
+};
+
+// State of an SRAM program after it was loaded.
+//
+// NOTE: must match the definition of OtLibSramExecution in
+// src/ate/test_programs/otlib_wrapper/src/sram.rs
+enum class DutSramExecution : int {
+  kExecuting = 0,
+  kDone = 1,
+  kError = 2,
+};
+
+// State of an SRAM program after it was loaded, and the value opentitanlib
+// reported for it once its execution stopped.
+struct DutSramResult {
+  DutSramExecution execution;
+  uint32_t value;
+};
+
+// Outcome of a `DutLib::DutConsoleRxPoll` call.
+enum class DutConsolePollResult : int {
+  // No complete response was received yet; poll again.
+  kPending = 0,
+  // The response was received into the caller's frames.
+  kOk = 1,
+  // The DUT responded with an error, whose JSON is in the caller's frames.
+  kDeviceError = 2,
+};
+
+// Options of a Verilator simulation of the DUT.
+struct DutVerilatorOpts {
+  std::string bin;
+  std::string rom;
+  std::vector<std::string> flash;
+  std::string otp;
+  uint64_t timeout_ms;
+  std::vector<std::string> args;
+};
+
+// Drives one DUT through its own opentitanlib transport.
+//
+// A DutLib must only be used on the thread that created it, as the
+// opentitanlib transport it owns is not thread safe; only `DutRequestCancel`
+// may be called from another thread. Multiple DUTs can be driven in parallel
+// with one DutLib per DUT, each created and used on its own thread.
 class DutLib {
  public:
+  // Size of the device ID returned by `DutReadDeviceId`.
+  static constexpr size_t kDeviceIdSizeInBytes = 32;
+
   /**
    * Factory method for instantiating and initializing this object.
+   *
+   * All factory methods route the messages logged by the opentitanlib wrapper
+   * on the calling thread into the test program log. The routing is per
+   * thread, like the `DutLib` itself: messages the wrapper logs on a thread
+   * that did not create a `DutLib` go to its stdout or stderr instead.
+   *
+   * Opening the FPGA is retried `retries` times, `retry_delay_ms` apart, and
+   * for up to `timeout_ms` milliseconds. A `retry_delay_ms` of 0 selects the
+   * default delay. If `disable_dft_on_reset` is set, DFT is disabled whenever
+   * the DUT is reset. `usb_vid`, `usb_pid` and `usb_serial` select one of
+   * several FPGA boards; 0 or an empty string matches any board. A non-empty
+   * `openocd_adapter_config` replaces the default OpenOCD adapter config file,
+   * and is used by all JTAG functions of the returned object. `conf` lists
+   * transport config files, e.g. the pin map of a board with a non-standard
+   * pinout; each file must exist. Applying the default configuration of the
+   * opened board, which fails if the board is in a bad state, is retried for
+   * up to `init_retry_window_ms` milliseconds; 0 makes a single attempt. An
+   * attempt that blocks is not interrupted. Returns nullptr if the FPGA
+   * transport could not be initialized.
    */
-  static std::unique_ptr<DutLib> Create(const std::string& fpga);
+  static std::unique_ptr<DutLib> Create(const std::string& fpga,
+                                        uint64_t timeout_ms = 0,
+                                        bool disable_dft_on_reset = false,
+                                        uint16_t usb_vid = 0,
+                                        uint16_t usb_pid = 0,
+                                        const std::string& usb_serial = "",
+                                        uint32_t retries = 0,
+                                        uint64_t retry_delay_ms = 0,
+                                        const std::string&
+                                            openocd_adapter_config = "",
+                                        const std::vector<std::string>& conf =
+                                            {},
+                                        uint64_t init_retry_window_ms = 0);
+  /**
+   * Factory method for instantiating this object on top of a Verilator
+   * simulation of the DUT instead of an FPGA.
+   *
+   * Returns nullptr if the Verilator transport could not be initialized.
+   */
+  static std::unique_ptr<DutLib> CreateVerilator(const DutVerilatorOpts& opts,
+                                                 uint64_t timeout_ms = 0);
+  /**
+   * Factory method for instantiating this object on top of an FPGA shared
+   * through the session proxy listening on `proxy`:`port`.
+   *
+   * Returns nullptr if the proxy could not be reached within `timeout_ms`
+   * milliseconds.
+   */
+  static std::unique_ptr<DutLib> CreateProxy(const std::string& proxy,
+                                             uint32_t port,
+                                             uint64_t timeout_ms = 0);
+  /**
+   * Factory method for instantiating this object on top of an instance of the
+   * Ti50 emulator.
+   *
+   * Only the console methods and `DutCheckTransportImgBoot` are supported by
+   * the emulator; bitstream loading and the JTAG methods fail. Returns nullptr
+   * if the emulator transport could not be initialized.
+   */
+  static std::unique_ptr<DutLib> CreateTi50Emulator(
+      const std::string& instance_prefix,
+      const std::string& executable_directory, const std::string& executable,
+      uint64_t timeout_ms = 0);
   /**
    * Forbids copies or assignments of DutLib.
    */
   DutLib(const DutLib&) = delete;
   DutLib& operator=(const DutLib&) = delete;
   /**
-   * Calls opentitanlib backend transport init for FPGA.
+   * Releases the opentitanlib transport owned by this object.
+   */
+  ~DutLib();
+  /**
+   * Appends the messages logged by the opentitanlib wrapper to the file at
+   * `path`, each line prefixed with a timestamp. An empty `path` closes the
+   * current log file.
+   */
+  void DutSetLogFile(const std::string& path);
+  /**
+   * Returns the versions of the opentitanlib wrapper and of opentitanlib, to
+   * be recorded with the provisioning results.
+   */
+  static std::string DutGetVersion();
+  /**
+   * Enables or disables the dry-run mode of the whole process, in which the
+   * calls below validate their arguments (file paths, tokens, regexes and the
+   * names of pins and interfaces) and then return without driving the DUT,
+   * e.g. to lint provisioning recipes in CI. Their out-parameters are left
+   * untouched.
+   */
+  static void DutSetDryRun(bool enabled);
+  /**
+   * Returns the duration in microseconds of the most recent operation of kind
+   * `op` on the calling thread, whether it succeeded or not, or 0 if there was
+   * none.
+   */
+  uint64_t DutGetLastOpDurationUs(DutOp op);
+  /**
+   * Aborts the console wait or bootstrap in progress on another thread, which
+   * then fails as cancelled. Unlike the other methods, this one may be called
+   * while another thread is using the DUT. If no such operation is running,
+   * the next one is cancelled instead.
+   */
+  void DutRequestCancel();
+  /**
+   * Resets the DUT, holding reset for `reset_delay_ms` milliseconds.
+   */
+  void DutResetTarget(uint64_t reset_delay_ms);
+  /**
+   * Applies the pin strapping `name` defined in the transport configuration.
+   */
+  void DutApplyStrapping(const std::string& name);
+  /**
+   * Removes the pin strapping `name` defined in the transport configuration.
+   */
+  void DutRemoveStrapping(const std::string& name);
+  /**
+   * Reads the level of the GPIO pin `pin` defined in the transport
+   * configuration.
+   */
+  bool DutGpioRead(const std::string& pin);
+  /**
+   * Drives the GPIO pin `pin` defined in the transport configuration to
+   * `level`.
+   */
+  void DutGpioWrite(const std::string& pin, bool level);
+  /**
+   * Sets the direction and pull of the GPIO pin `pin` defined in the transport
+   * configuration.
+   */
+  void DutSetPinMode(const std::string& pin, DutPinMode mode);
+  /**
+   * Calls opentitanlib to load `fpga_bitstream` into the FPGA.
+   *
+   * A `rom_reset_pulse_ms` or `rom_timeout_ms` of 0 selects the default ROM
+   * reset pulse or ROM boot timeout.
+   *
+   * Without `clear_bitstream` the FPGA is not cleared and programming is
+   * skipped if the same bitstream is already loaded. The fabric then keeps the
+   * state left by the previous DUT (e.g. OTP and flash contents), so only skip
+   * clearing when the next part tolerates that state.
+   *
+   * If `progress_cb` is not null, it is called with 0 when loading starts,
+   * with 50 once the FPGA is cleared (if `clear_bitstream` is set) and with 100
+   * once the ROM has come up.
+   *
+   * Once the bitstream is loaded, the DUT is reset and its ROM banner awaited
+   * over the console; a ROM that does not come up within `rom_timeout_ms` is
+   * reported as a ROM timeout.
+   */
+  void DutFpgaLoadBitstream(const std::string& fpga_bitstream,
+                            uint64_t rom_reset_pulse_ms = 0,
+                            uint64_t rom_timeout_ms = 0,
+                            bool clear_bitstream = true,
+                            void (*progress_cb)(uint32_t percent) = nullptr);
+  /**
+   * Same as `DutFpgaLoadBitstream`, for a bitstream of `len` bytes held in
+   * memory at `data`.
+   *
+   * The bitstream is staged in a temporary file, which is removed once loading
+   * is done.
    */
-  void DutFpgaLoadBitstream(const std::string& fpga_bitstream);
+  void DutFpgaLoadBitstreamBuf(const uint8_t* data, size_t len,
+                               uint64_t rom_reset_pulse_ms = 0,
+                               uint64_t rom_timeout_ms = 0,
+                               bool clear_bitstream = true,
+                               void (*progress_cb)(uint32_t percent) = nullptr);
   /**
    * Calls opentitanlib test util to load an SRAM ELF into the DUT over JTAG.
+   *
+   * An `adapter_speed_khz` of 0 selects the default JTAG adapter speed. With
+   * `skip_crc` the CRC check of the loaded program is skipped. With
+   * `jtag_log_stdio` the OpenOCD output is logged, to debug the JTAG
+   * connection; the other JTAG functions take the same argument. The program
+   * is loaded over the RISC-V TAP, the only one that reaches the CPU.
+   *
+   * Returns the state of the program and, once it stopped, the value that
+   * opentitanlib reported for its execution. A program that failed to load or
+   * reported an error is returned with the `kError` state.
+   */
+  DutSramResult DutLoadSramElf(const std::string& openocd,
+                               const std::string& elf, bool wait_for_done,
+                               uint64_t timeout_ms,
+                               uint32_t adapter_speed_khz = 0,
+                               bool skip_crc = false,
+                               bool jtag_log_stdio = false);
+  /**
+   * Same as `DutLoadSramElf`, for a program built as a `.vmem` image to be
+   * loaded at `load_addr`.
+   */
+  DutSramResult DutLoadSramVmem(const std::string& openocd,
+                                const std::string& vmem, uint32_t load_addr,
+                                bool wait_for_done, uint64_t timeout_ms,
+                                uint32_t adapter_speed_khz = 0,
+                                bool skip_crc = false,
+                                bool jtag_log_stdio = false);
+  /**
+   * Calls opentitanlib to bootstrap a binary into the DUT's flash using
+   * `protocol`.
+   *
+   * A `baudrate` of 0 selects the default bootstrap console UART baudrate. The
+   * UART parameters may only be set for protocols that use the console UART.
+   *
+   * A failed bootstrap is re-attempted up to `retries` times, resetting the
+   * DUT `retry_delay_ms` after each failure.
+   *
+   * An `inter_frame_delay_us` or `flash_erase_delay_ms` of 0 selects the
+   * default delay of `protocol`. Slower flash parts may need a longer
+   * `flash_erase_delay_ms` for the bootstrap to verify.
+   *
+   * The SPI protocols run at `spi_speed_hz` in `spi_mode`. A speed of 0 and
+   * `DutSpiMode::kDefault` keep the defaults of the SPI interface. The SPI
+   * parameters may not be set for the protocol that uses the console UART.
+   */
+  void DutBootstrap(
+      const std::string& bin, uint32_t baudrate = 0, bool flow_control = false,
+      DutBootstrapProtocol protocol = DutBootstrapProtocol::kEeprom,
+      uint32_t retries = 0, uint64_t retry_delay_ms = 0,
+      uint64_t inter_frame_delay_us = 0, uint64_t flash_erase_delay_ms = 0,
+      uint32_t spi_speed_hz = 0, DutSpiMode spi_mode = DutSpiMode::kDefault);
+  /**
+   * Opens a SPI console that is kept open across the console calls below, so
+   * that buffered console state is not lost between them.
+   *
+   * While it is open, SPI console calls use it and ignore their
+   * `tx_ready_pin` and `spi_interface` arguments. An empty `tx_ready_pin` or
+   * `spi_interface` selects the default console TX-ready GPIO pin or SPI
+   * interface.
+   *
+   * The console SPI runs at `spi_speed_hz` in `spi_mode`, e.g. for a
+   * throughput the default clock does not reliably reach. A speed of 0 and
+   * `DutSpiMode::kDefault` keep the defaults of the SPI interface.
+   *
+   * The TX-ready pin is an input with `pull_mode`, and with `active_low`
+   * signals ready at a low level, for boards that invert it.
+   */
+  void DutConsoleOpen(const std::string& tx_ready_pin = "",
+                      const std::string& spi_interface = "",
+                      uint32_t spi_speed_hz = 0,
+                      DutSpiMode spi_mode = DutSpiMode::kDefault,
+                      DutPullMode pull_mode = DutPullMode::kNone,
+                      bool active_low = false);
+  /**
+   * Closes the SPI console opened by `DutConsoleOpen`, if any.
+   */
+  void DutConsoleClose();
+  /**
+   * Calls opentitanlib test util to wait for a message over the console.
+   *
+   * An empty `tx_ready_pin` or `spi_interface` selects the default console
+   * TX-ready GPIO pin or SPI interface. `spi_speed_hz` and `spi_mode`
+   * configure the SPI, and `pull_mode` and `active_low` the TX-ready pin, as
+   * for `DutConsoleOpen`. All are ignored when `kind` selects the UART
+   * console.
+   *
+   * While waiting, `on_heartbeat` is called about every 500 ms with the time
+   * elapsed, e.g. to show the operator that the station is not hung.
+   */
+  void DutConsoleWaitForRx(
+      const char* msg, uint64_t timeout_ms,
+      const std::string& tx_ready_pin = "",
+      const std::string& spi_interface = "",
+      DutConsoleKind kind = DutConsoleKind::kSpi,
+      void (*on_heartbeat)(uint64_t elapsed_ms) = nullptr,
+      uint32_t spi_speed_hz = 0, DutSpiMode spi_mode = DutSpiMode::kDefault,
+      DutPullMode pull_mode = DutPullMode::kNone, bool active_low = false);
+  /**
+   * Same as `DutConsoleWaitForRx`, but returns the text captured by the
+   * capture group `group` of `msg`, e.g. the value of a banner like
+   * `DEVICE_ID: (.*)`. Group 0 is the whole message.
    */
-  void DutLoadSramElf(const std::string& openocd, const std::string& elf,
-                      bool wait_for_done, uint64_t timeout_ms);
+  std::string DutConsoleWaitForRxCapture(
+      const char* msg, uint64_t timeout_ms, size_t group = 1,
+      const std::string& tx_ready_pin = "",
+      const std::string& spi_interface = "",
+      DutConsoleKind kind = DutConsoleKind::kSpi,
+      uint32_t spi_speed_hz = 0, DutSpiMode spi_mode = DutSpiMode::kDefault,
+      DutPullMode pull_mode = DutPullMode::kNone, bool active_low = false);
   /**
-   * Calls opentitanlib to bootstrap a binary into the DUT's flash over SPI.
+   * Waits for the DUT to dump its UDS certificate over the console and
+   * returns the decoded DER certificate.
+   *
+   * The message format is up to the DUT firmware: `uds_cert_regex` must match
+   * it, with the certificate hex or base64 encoded in its capture group 1.
+   *
+   * Takes the same optional arguments as `DutConsoleWaitForRx`.
    */
-  void DutBootstrap(const std::string& bin);
+  std::vector<uint8_t> DutExtractUdsCert(
+      const std::string& uds_cert_regex, uint64_t timeout_ms,
+      const std::string& tx_ready_pin = "",
+      const std::string& spi_interface = "",
+      DutConsoleKind kind = DutConsoleKind::kSpi,
+      uint32_t spi_speed_hz = 0, DutSpiMode spi_mode = DutSpiMode::kDefault,
+      DutPullMode pull_mode = DutPullMode::kNone, bool active_low = false);
   /**
-   * Calls opentitanlib test util to wait for a message over the SPI console.
+   * Waits for a message matching any of the regexes in `patterns` over the
+   * console, e.g. one of several possible test outcomes. A pattern that can
+   * match empty text is rejected as an invalid argument.
+   *
+   * The console arguments are the same as for `DutConsoleWaitForRx`. Returns
+   * the index of the pattern that matched; fails with a console timeout if
+   * none matched within `timeout_ms`.
    */
-  void DutConsoleWaitForRx(const char* msg, uint64_t timeout_ms);
+  size_t DutConsoleWaitForAnyOf(const std::vector<std::string>& patterns,
+                                uint64_t timeout_ms,
+                                const std::string& tx_ready_pin = "",
+                                const std::string& spi_interface = "",
+                                DutConsoleKind kind = DutConsoleKind::kSpi,
+                                uint32_t spi_speed_hz = 0,
+                                DutSpiMode spi_mode = DutSpiMode::kDefault,
+                                DutPullMode pull_mode = DutPullMode::kNone,
+                                bool active_low = false);
   /**
-   * Calls opentitanlib test util to receive a message over the SPI console.
+   * Calls opentitanlib test util to receive a message over the console.
+   *
+   * An empty `tx_ready_pin` or `spi_interface` selects the default console
+   * TX-ready GPIO pin or SPI interface. `spi_speed_hz` and `spi_mode`
+   * configure the SPI, and `pull_mode` and `active_low` the TX-ready pin, as
+   * for `DutConsoleOpen`. All are ignored when `kind` selects the UART
+   * console. `crc_kind` selects the CRC-32 variant used to
+   * check the response. With `binary` the response payload is received as raw
+   * bytes rather than UTF-8 text.
+   *
+   * A non-empty `success_regex` or `failure_regex` overrides the default
+   * RESP_OK or RESP_ERR response envelope. Each must have exactly two capture
+   * groups: the payload, then its CRC as a decimal number.
+   *
+   * Each frame is filled with up to `frame_size` bytes, which must match the
+   * `kDutTxMaxSpiFrameSizeInBytes` the DUT firmware was built with, and must
+   * not exceed the payload capacity of `dut_spi_frame_t`. 0 selects the
+   * default of 2020 bytes.
+   *
+   * Unless `quiet`, the console output is echoed to stdout. With `timestamp`
+   * each of its lines is prefixed with a timestamp, and with `newline` the
+   * start of the output counts as the start of a line. Clear `timestamp` when
+   * stdout is parsed as a machine-readable log.
+   *
+   * While waiting for the response, `on_heartbeat` is called about every
+   * 500 ms with the time elapsed, as for `DutConsoleWaitForRx`.
+   *
+   * Returns false if the DUT responded with an error, in which case
+   * `spi_frames` hold the error JSON.
    */
-  void DutConsoleRx(const std::string& sync_msg, dut_spi_frame_t* spi_frames,
+  bool DutConsoleRx(const std::string& sync_msg, dut_spi_frame_t* spi_frames,
                     size_t* num_frames, bool skip_crc_check, bool quiet,
-                    uint64_t timeout_ms);
+                    uint64_t timeout_ms, const std::string& tx_ready_pin = "",
+                    const std::string& spi_interface = "",
+                    DutConsoleKind kind = DutConsoleKind::kSpi,
+                    DutCrcKind crc_kind = DutCrcKind::kIsoHdlc,
+                    bool binary = false,
+                    const std::string& success_regex = "",
+                    const std::string& failure_regex = "",
+                    size_t frame_size = 0, bool timestamp = true,
+                    bool newline = true,
+                    void (*on_heartbeat)(uint64_t elapsed_ms) = nullptr,
+                    uint32_t spi_speed_hz = 0,
+                    DutSpiMode spi_mode = DutSpiMode::kDefault,
+                    DutPullMode pull_mode = DutPullMode::kNone,
+                    bool active_low = false);
+  /**
+   * Same as `DutConsoleRx`, but writes the console output to the open file
+   * descriptor `capture_fd` instead of stdout, so that each DUT can keep its
+   * own console log. The descriptor is not closed. The console is opened for
+   * the call, even if one was opened with `DutConsoleOpen`.
+   */
+  bool DutConsoleRxCapture(
+      const std::string& sync_msg, dut_spi_frame_t* spi_frames,
+      size_t* num_frames, bool skip_crc_check, int capture_fd,
+      uint64_t timeout_ms, const std::string& tx_ready_pin = "",
+      const std::string& spi_interface = "",
+      DutConsoleKind kind = DutConsoleKind::kSpi,
+      DutCrcKind crc_kind = DutCrcKind::kIsoHdlc, bool binary = false,
+      const std::string& success_regex = "",
+      const std::string& failure_regex = "", size_t frame_size = 0,
+      bool timestamp = true, bool newline = true,
+      void (*on_heartbeat)(uint64_t elapsed_ms) = nullptr,
+      uint32_t spi_speed_hz = 0, DutSpiMode spi_mode = DutSpiMode::kDefault,
+      DutPullMode pull_mode = DutPullMode::kNone, bool active_low = false);
   /**
-   * Calls opentitanlib test util to send a message over the SPI console.
+   * Same as `DutConsoleRx`, but calls `on_frame` with each chunk of at most
+   * `frame_size` bytes of the payload, in order, so that the size of the
+   * response need not be known in advance.
+   *
+   * The chunks are handed over once the whole response was received and its
+   * CRC checked. Returns false if the DUT responded with an error, whose JSON
+   * was handed to `on_frame`.
+   */
+  bool DutConsoleRxStream(const std::string& sync_msg,
+                          void (*on_frame)(const uint8_t* data, size_t len),
+                          bool skip_crc_check, bool quiet, uint64_t timeout_ms,
+                          const std::string& tx_ready_pin = "",
+                          const std::string& spi_interface = "",
+                          DutConsoleKind kind = DutConsoleKind::kSpi,
+                          DutCrcKind crc_kind = DutCrcKind::kIsoHdlc,
+                          bool binary = false,
+                          const std::string& success_regex = "",
+                          const std::string& failure_regex = "",
+                          size_t frame_size = 0,
+                          uint32_t spi_speed_hz = 0,
+                          DutSpiMode spi_mode = DutSpiMode::kDefault,
+                          DutPullMode pull_mode = DutPullMode::kNone,
+                          bool active_low = false);
+  /**
+   * Same as `DutConsoleRx`, but returns `DutConsolePollResult::kPending`
+   * instead of blocking if no complete response arrives within `slice_ms`
+   * milliseconds (50 if 0), so that the caller can keep e.g. a UI responsive
+   * while the DUT is busy.
+   *
+   * Requires the console opened with `DutConsoleOpen`, which keeps the
+   * partial response between calls. There is no sync message; wait for it
+   * with `DutConsoleWaitForRx` first if needed.
+   */
+  DutConsolePollResult DutConsoleRxPoll(
+      dut_spi_frame_t* spi_frames, size_t* num_frames, bool skip_crc_check,
+      bool quiet, uint64_t slice_ms = 0,
+      DutCrcKind crc_kind = DutCrcKind::kIsoHdlc, bool binary = false,
+      const std::string& success_regex = "",
+      const std::string& failure_regex = "", size_t frame_size = 0);
+  /**
+   * Sends the request `tx_frame` over the console, then receives the response
+   * into `spi_frames` as `DutConsoleRx` does without a sync message.
+   *
+   * Both steps use the same console, so a response the DUT sends right after
+   * the request is not missed. `timeout_ms` applies to each step. Returns
+   * false if the DUT responded with an error, in which case `spi_frames` hold
+   * the error JSON.
+   */
+  bool DutConsoleTransact(const uint8_t* tx_frame, size_t tx_frame_size,
+                          dut_spi_frame_t* spi_frames, size_t* num_frames,
+                          bool skip_crc_check, bool quiet, uint64_t timeout_ms,
+                          const std::string& tx_ready_pin = "",
+                          const std::string& spi_interface = "",
+                          DutConsoleKind kind = DutConsoleKind::kSpi,
+                          DutCrcKind crc_kind = DutCrcKind::kIsoHdlc,
+                          size_t frame_size = 0,
+                          uint32_t spi_speed_hz = 0,
+                          DutSpiMode spi_mode = DutSpiMode::kDefault,
+                          DutPullMode pull_mode = DutPullMode::kNone,
+                          bool active_low = false);
+  /**
+   * Calls opentitanlib test util to send a message over the console.
+   *
+   * An empty `tx_ready_pin` or `spi_interface` selects the default console
+   * TX-ready GPIO pin or SPI interface. `spi_speed_hz` and `spi_mode`
+   * configure the SPI, and `pull_mode` and `active_low` the TX-ready pin, as
+   * for `DutConsoleOpen`. All are ignored when `kind` selects the UART
+   * console.
+   *
+   * With `compress` the message is deflated before it is sent, for DUT
+   * firmware that inflates its console input. The compressed message is
+   * preceded by a 9 byte header: a format byte of 1 (raw deflate, RFC 1951),
+   * then the uncompressed and the compressed length as little endian 32-bit
+   * integers.
    */
   void DutConsoleTx(const std::string& sync_msg, const uint8_t* spi_frame,
-                    size_t spi_frame_size, uint64_t timeout_ms);
+                    size_t spi_frame_size, uint64_t timeout_ms,
+                    const std::string& tx_ready_pin = "",
+                    const std::string& spi_interface = "",
+                    DutConsoleKind kind = DutConsoleKind::kSpi,
+                    bool compress = false,
+                    uint32_t spi_speed_hz = 0,
+                    DutSpiMode spi_mode = DutSpiMode::kDefault,
+                    DutPullMode pull_mode = DutPullMode::kNone,
+                    bool active_low = false);
+  /**
+   * Calls opentitanlib test util to send `num_frames` frames over the console,
+   * e.g. a payload larger than a single frame.
+   *
+   * Takes the same optional arguments as `DutConsoleTx`.
+   */
+  void DutConsoleTxFrames(const std::string& sync_msg,
+                          const dut_spi_frame_t* frames, size_t num_frames,
+                          uint64_t timeout_ms,
+                          const std::string& tx_ready_pin = "",
+                          const std::string& spi_interface = "",
+                          DutConsoleKind kind = DutConsoleKind::kSpi,
+                          uint32_t spi_speed_hz = 0,
+                          DutSpiMode spi_mode = DutSpiMode::kDefault,
+                          DutPullMode pull_mode = DutPullMode::kNone,
+                          bool active_low = false);
+  /**
+   * Discards the console output received for `drain_ms`, e.g. stale output of
+   * a previous provisioning step that would otherwise be matched by the next
+   * console call.
+   *
+   * Takes the same optional arguments as `DutConsoleWaitForRx`.
+   */
+  void DutConsoleFlush(uint64_t drain_ms, const std::string& tx_ready_pin = "",
+                       const std::string& spi_interface = "",
+                       DutConsoleKind kind = DutConsoleKind::kSpi,
+                       uint32_t spi_speed_hz = 0,
+                       DutSpiMode spi_mode = DutSpiMode::kDefault,
+                       DutPullMode pull_mode = DutPullMode::kNone,
+                       bool active_low = false);
   /**
    * Calls opentitanlib test util to execute a life cycle transition to
    * TestLocked0 (from TestUnlocked0).
+   *
+   * An `adapter_speed_khz` of 0 selects the default JTAG adapter speed.
+   */
+  void DutResetAndLock(const std::string& openocd,
+                       uint32_t adapter_speed_khz = 0,
+                       bool jtag_log_stdio = false);
+  /**
+   * Performs the strap and reset portion of `DutResetAndLock` without locking
+   * the DUT: applies the CPU TAP straps, resets the DUT holding reset for
+   * `reset_delay_ms` milliseconds and checks that the RISC-V TAP responds.
+   * The CPU TAP straps stay applied, e.g. for one more JTAG read before the
+   * lock.
+   *
+   * An `adapter_speed_khz` of 0 selects the default JTAG adapter speed.
    */
-  void DutResetAndLock(const std::string& openocd);
+  void DutResetNoLock(const std::string& openocd, uint64_t reset_delay_ms,
+                      uint32_t adapter_speed_khz = 0,
+                      bool jtag_log_stdio = false);
   /**
    * Calls opentitanlib test util to execute a life cycle transition to
    * TestUnlocked* (from TestLocked*).
+   *
+   * An `adapter_speed_khz` of 0 selects the default JTAG adapter speed. The
+   * raw encoding of the LC state read back after the transition is written to
+   * `out_state` if it is not null.
+   *
+   * `token` is the raw token of `token_size` bytes: 16 for transitions out of
+   * RAW or TestLocked* and into Dev, Prod, ProdEnd or Rma, 0 for the others.
+   * Set `token_is_hashed` to hash a 16 byte token before it is written, for
+   * DUTs that expect the hashed form stored in OTP.
+   *
+   * Set `use_external_clk` for transitions early in the flow (e.g. out of RAW
+   * or into TestUnlocked*) where AST is not calibrated yet; leave it unset once
+   * AST is calibrated.
+   *
+   * The transition is skipped if the DUT is already in `target_lc_state`,
+   * unless `force` is set.
+   *
+   * `reset_tap_straps` selects the TAP strapped after the transition. The LC
+   * state is only read back and checked with `DutJtagTap::kLcTap`; pick
+   * another TAP or none for transitions into states with ROM execution
+   * enabled, which would otherwise reset the chip. `out_state` is then left
+   * untouched.
+   *
+   * Returns false if the LC state read back does not match `target_lc_state`.
+   */
+  bool DutLcTransition(const std::string& openocd, const uint8_t* token,
+                       size_t token_size, uint32_t target_lc_state,
+                       uint32_t adapter_speed_khz = 0,
+                       uint32_t* out_state = nullptr,
+                       bool use_external_clk = false, bool force = false,
+                       DutJtagTap reset_tap_straps = DutJtagTap::kLcTap,
+                       bool jtag_log_stdio = false,
+                       bool token_is_hashed = false);
+  /**
+   * Same as `DutLcTransition`, with the token given as a hex string, as stored
+   * by provisioning recipes: empty, or 32 hex digits encoding the token bytes
+   * in order. Non-hex input is rejected.
+   */
+  bool DutLcTransitionHex(const std::string& openocd,
+                          const std::string& token_hex,
+                          uint32_t target_lc_state,
+                          uint32_t adapter_speed_khz = 0,
+                          uint32_t* out_state = nullptr,
+                          bool use_external_clk = false, bool force = false,
+                          DutJtagTap reset_tap_straps = DutJtagTap::kLcTap,
+                          bool jtag_log_stdio = false,
+                          bool token_is_hashed = false);
+  /**
+   * Unlocks the DUT out of RAW into `target_lc_state` with a volatile raw
+   * unlock, with the 16 byte raw unlock `token`, hashed before it is written.
+   *
+   * Unlike `DutLcTransition`, the unlock is not programmed into OTP and does
+   * not count as an LC transition: the DUT is back in RAW after its next
+   * reset. The chip is reset to strap the LC TAP before the unlock but never
+   * after it, and stays strapped for `post_transition_tap` (not
+   * `DutJtagTap::kNone`). The LC state is only read back into `out_state`,
+   * if it is not null, with `DutJtagTap::kLcTap`.
+   *
+   * An `adapter_speed_khz` of 0 selects the default JTAG adapter speed.
+   * Returns false if the LC state read back does not match `target_lc_state`.
    */
-  void DutLcTransition(const std::string& openocd, const uint8_t* token,
-                       size_t token_size, uint32_t target_lc_state);
+  bool DutVolatileRawUnlock(const std::string& openocd, const uint8_t* token,
+                            size_t token_size, uint32_t target_lc_state,
+                            uint32_t adapter_speed_khz = 0,
+                            uint32_t* out_state = nullptr,
+                            bool use_external_clk = false,
+                            DutJtagTap post_transition_tap =
+                                DutJtagTap::kLcTap,
+                            bool jtag_log_stdio = false);
+  /**
+   * Transitions the DUT through the `num_steps` LC states at `states` in
+   * order, e.g. RAW to TestUnlocked0 to Dev, with the raw token of each step
+   * in the frame of the same index at `tokens` (a `size` of 0 for none). The
+   * LC TAP is strapped once for the whole sequence, and the LC state is read
+   * back after each step. Steps the DUT is already in the state of are
+   * skipped.
+   *
+   * The sequence stops at the first failure. `out_step`, if not null, is set
+   * to the index of the failed step, or to `num_steps` on success, and
+   * `out_state`, if not null, to the LC state read back last. An
+   * `adapter_speed_khz` of 0 selects the default JTAG adapter speed. Returns
+   * false if the LC state read back after a step does not match its target.
+   */
+  bool DutLcTransitionSequence(const std::string& openocd,
+                               const uint32_t* states,
+                               const dut_spi_frame_t* tokens, size_t num_steps,
+                               uint32_t adapter_speed_khz = 0,
+                               bool use_external_clk = false,
+                               size_t* out_step = nullptr,
+                               uint32_t* out_state = nullptr,
+                               bool jtag_log_stdio = false);
+  /**
+   * Transitions the DUT into RMA with the 16 byte RMA unlock `token`, then
+   * reads the LC state back over the LC TAP into `out_state` if it is not
+   * null. The transition wipes the flash, so it can take a while.
+   *
+   * An `adapter_speed_khz` of 0 selects the default JTAG adapter speed.
+   * Returns false if the LC state read back is not RMA.
+   */
+  bool DutRmaUnlock(const std::string& openocd, const uint8_t* token,
+                    size_t token_size, uint32_t adapter_speed_khz = 0,
+                    uint32_t* out_state = nullptr, bool jtag_log_stdio = false);
+  /**
+   * Reads the raw encoding of the current life cycle state over the LC TAP.
+   *
+   * An `adapter_speed_khz` of 0 selects the default JTAG adapter speed.
+   */
+  uint32_t DutReadLcState(const std::string& openocd,
+                          uint32_t adapter_speed_khz = 0,
+                          bool jtag_log_stdio = false);
+  /**
+   * Checks that the DUT is in LC state `expected_state` without transitioning
+   * it, and writes the LC state read back into `out_state` if it is not null.
+   *
+   * An `adapter_speed_khz` of 0 selects the default JTAG adapter speed.
+   * Returns false if the DUT is in another state.
+   */
+  bool DutVerifyLcState(const std::string& openocd, uint32_t expected_state,
+                        uint32_t adapter_speed_khz = 0,
+                        uint32_t* out_state = nullptr,
+                        bool jtag_log_stdio = false);
+  /**
+   * Reads the number of LC transitions the DUT has gone through over the LC
+   * TAP. The LC controller refuses any transition once the count reaches its
+   * limit (24).
+   *
+   * An `adapter_speed_khz` of 0 selects the default JTAG adapter speed.
+   */
+  uint32_t DutReadLcTransitionCount(const std::string& openocd,
+                                    uint32_t adapter_speed_khz = 0,
+                                    bool jtag_log_stdio = false);
+  /**
+   * Reads the hardware device ID of the DUT over the LC TAP, to be
+   * recorded with the provisioning results.
+   *
+   * Returns the `kDeviceIdSizeInBytes` bytes of the device ID, little endian
+   * with its least significant word first.
+   */
+  std::vector<uint8_t> DutReadDeviceId(const std::string& openocd,
+                                       uint32_t adapter_speed_khz = 0,
+                                       bool jtag_log_stdio = false);
+  /**
+   * Reads the JTAG IDCODE of the DUT over the RISC-V TAP.
+   *
+   * An `adapter_speed_khz` of 0 selects the default JTAG adapter speed.
+   */
+  uint32_t DutReadJtagIdcode(const std::string& openocd,
+                             uint32_t adapter_speed_khz = 0,
+                             bool jtag_log_stdio = false);
+  /**
+   * Reads the RISC-V debug module interface register at `addr` over the
+   * RISC-V TAP, without resetting the DUT.
+   *
+   * The debug module state is kept across calls, e.g. for a read-modify-write,
+   * but the RISC-V TAP strapping must already have been latched by an earlier
+   * reset, e.g. `DutApplyStrapping("PINMUX_TAP_RISCV")` followed by
+   * `DutResetTarget`.
+   *
+   * An `adapter_speed_khz` of 0 selects the default JTAG adapter speed.
+   */
+  uint32_t DutJtagReadReg(const std::string& openocd, uint32_t addr,
+                          uint32_t adapter_speed_khz = 0,
+                          bool jtag_log_stdio = false);
+  /**
+   * Writes `val` to the RISC-V debug module interface register at `addr` over
+   * the RISC-V TAP, without resetting the DUT. Like for `DutJtagReadReg`, the
+   * RISC-V TAP strapping must already have been latched.
+   *
+   * An `adapter_speed_khz` of 0 selects the default JTAG adapter speed.
+   */
+  void DutJtagWriteReg(const std::string& openocd, uint32_t addr, uint32_t val,
+                       uint32_t adapter_speed_khz = 0,
+                       bool jtag_log_stdio = false);
+  /**
+   * Halts the CPU over the RISC-V TAP without resetting the DUT and reads
+   * `len` bytes of memory at `addr` into `buf`, e.g. to validate what an SRAM
+   * program wrote. The CPU stays halted afterwards, and like for
+   * `DutJtagReadReg`, the RISC-V TAP strapping must already have been latched.
+   *
+   * `addr` and `len` must be multiples of 4.
+   */
+  void DutJtagMemRead(const std::string& openocd, uint32_t addr, uint8_t* buf,
+                      size_t len, uint32_t adapter_speed_khz = 0,
+                      bool jtag_log_stdio = false);
+  /**
+   * Halts the CPU over the RISC-V TAP without resetting the DUT and writes the
+   * `len` bytes of `buf` to memory at `addr`. As for `DutJtagMemRead`, the CPU
+   * stays halted and the strapping must already have been latched.
+   *
+   * `addr` and `len` must be multiples of 4.
+   */
+  void DutJtagMemWrite(const std::string& openocd, uint32_t addr,
+                       const uint8_t* buf, size_t len,
+                       uint32_t adapter_speed_khz = 0,
+                       bool jtag_log_stdio = false);
+  /**
+   * Reads the OTP partition `partition`, named as in the OTP memory map (e.g.
+   * "HW_CFG0"), over the RISC-V TAP into `buf` of `len` bytes, e.g. to verify
+   * the OTP contents after programming.
+   *
+   * Fails if the partition is unknown or does not fit into `buf`.
+   */
+  void DutReadOtp(const std::string& openocd, const std::string& partition,
+                  uint8_t* buf, size_t len, uint32_t adapter_speed_khz = 0,
+                  bool jtag_log_stdio = false);
+  /**
+   * Halts the CPU over the RISC-V TAP without resetting the DUT.
+   *
+   * The JTAG session is re-established on each call and closed before
+   * returning. The CPU stays halted until `DutCpuResume()` or the next reset.
+   */
+  void DutCpuHalt(const std::string& openocd, uint32_t adapter_speed_khz = 0,
+                  bool jtag_log_stdio = false);
+  /**
+   * Resumes the CPU over the RISC-V TAP without resetting the DUT.
+   */
+  void DutCpuResume(const std::string& openocd,
+                    uint32_t adapter_speed_khz = 0,
+                    bool jtag_log_stdio = false);
   /**
    * Calls opentitanlib test utils to reset the DUT and wait for a ROM_EXT and
    * owner firmware boot message to appear over the console to indicate the DUT
    * has been provisioned successfully.
+   *
+   * A non-empty `rom_ext_regex` replaces the default ROM_EXT boot message, and
+   * a non-empty `boot_success_regex` replaces `owner_fw_boot_msg` as the owner
+   * firmware boot message.
+   *
+   * `boot_failure_regexes` are fatal markers in addition to the invalid UDS
+   * certificate and BFV messages. A match is reported as
+   * `DutBootResult::kBootFault`, and the matched regex is logged.
+   *
+   * Returns the outcome of the boot, so that the caller can decide how to bin
+   * a failed boot.
+   *
+   * The DUT is held in reset for 50 ms. If not null, `out_rom_ext_boot_us` and
+   * `out_owner_fw_boot_us` receive the time in microseconds from the release
+   * of the reset until the ROM_EXT and the owner firmware boot messages
+   * appeared, or 0 if they did not.
+   */
+  DutBootResult DutCheckTransportImgBoot(
+      const char* owner_fw_boot_msg, uint64_t timeout_ms,
+      const std::string& rom_ext_regex = "",
+      const std::string& boot_success_regex = "",
+      const std::vector<std::string>& boot_failure_regexes = {},
+      uint64_t* out_rom_ext_boot_us = nullptr,
+      uint64_t* out_owner_fw_boot_us = nullptr);
+  /**
+   * Same as `DutCheckTransportImgBoot`, waiting up to `rom_ext_timeout_ms` for
+   * the ROM_EXT boot message and then up to `owner_fw_timeout_ms` for the
+   * owner firmware boot message.
    */
-  void DutCheckTransportImgBoot(const char* owner_fw_boot_msg,
-                                uint64_t timeout_ms);
+  DutBootResult DutCheckTransportImgBootWithTimeouts(
+      const char* owner_fw_boot_msg, uint64_t rom_ext_timeout_ms,
+      uint64_t owner_fw_timeout_ms, const std::string& rom_ext_regex = "",
+      const std::string& boot_success_regex = "",
+      const std::vector<std::string>& boot_failure_regexes = {},
+      uint64_t* out_rom_ext_boot_us = nullptr,
+      uint64_t* out_owner_fw_boot_us = nullptr);
 
  private:
   // Must be 2x the opentitanlib UartConsole buffer size defined here:
@@ -78,9 +966,11 @@ class DutLib {
   static constexpr size_t kMaxRxMsgSizeInBytes = 65536;
 
   // Force users to call `Create` factory method.
-  DutLib(void* transport) : transport_(transport){};
+  DutLib(OtLibTransport* transport) : transport_(transport){};
 
-  void* transport_;
+  OtLibTransport* transport_;
+  // SPI console opened by `DutConsoleOpen`, or nullptr.
+  void* console_ = nullptr;
 };
 
 }  // namespace test_programs
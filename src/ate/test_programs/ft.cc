@@ -68,6 +68,7 @@ ABSL_FLAG(std::string, ca_root_certs, "",
 
 namespace {
 using provisioning::VersionFormatted;
+using provisioning::test_programs::DutBootResult;
 using provisioning::test_programs::DutLib;
 
 absl::StatusOr<ate_client_ptr> AteClientNew(void) {
@@ -189,6 +190,10 @@ int main(int argc, char** argv) {
   // Note: we do not reload the bitstream as the CP test program should be run
   // before running this test program.
   auto dut = DutLib::Create(absl::GetFlag(FLAGS_fpga));
+  if (dut == nullptr) {
+    LOG(ERROR) << "DutLib::Create failed.";
+    return -1;
+  }
 
   // Regenerate the test tokens.
   derive_token_params_t test_tokens_params[] = {
@@ -271,15 +276,21 @@ int main(int argc, char** argv) {
   }
 
   // Unlock the chip and run the individualization firmware.
-  dut->DutLcTransition(openocd_path, tokens[0].data, kTokenSize128,
-                       kDifLcCtrlStateTestUnlocked1);
+  if (!dut->DutLcTransition(openocd_path, tokens[0].data, kTokenSize128,
+                            kDifLcCtrlStateTestUnlocked1)) {
+    LOG(ERROR) << "Failed to transition to TestUnlocked1.";
+    return -1;
+  }
   dut->DutLoadSramElf(openocd_path, ft_individ_elf_path,
                       /*wait_for_done=*/true,
                       /*timeout_ms=*/1000);
 
   // Transition to mission mode and start running the personalization firmware.
-  dut->DutLcTransition(openocd_path, tokens[1].data, kTokenSize128,
-                       kDifLcCtrlStateProd);
+  if (!dut->DutLcTransition(openocd_path, tokens[1].data, kTokenSize128,
+                            kDifLcCtrlStateProd)) {
+    LOG(ERROR) << "Failed to transition to Prod.";
+    return -1;
+  }
   dut->DutBootstrap(ft_perso_bin_path);
   dut->DutConsoleWaitForRx("Bootstrap requested.", /*timeout_ms=*/1000);
   dut->DutBootstrap(ft_fw_bundle_path);
@@ -466,7 +477,11 @@ int main(int argc, char** argv) {
     LOG(ERROR) << "GetOwnerFwBootMessage failed.";
     return -1;
   }
-  dut->DutCheckTransportImgBoot(boot_msg_cstr, /*timeout_ms=*/5000);
+  if (dut->DutCheckTransportImgBoot(boot_msg_cstr, /*timeout_ms=*/5000) !=
+      DutBootResult::kOwnerBooted) {
+    LOG(ERROR) << "DUT failed to boot the transport image.";
+    return -1;
+  }
 
   // Close session with PA.
   if (CloseSession(ate_client) != 0) {
@@ -0,0 +1,365 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use opentitanlib::app::TransportWrapper;
+use opentitanlib::test_utils::load_bitstream::LoadBitstream;
+use opentitanlib::transport::TransportError;
+
+use crate::console::wait_for;
+use crate::dry_run::dry_run;
+use crate::ffi::{check_not_null, cstr_to_str};
+use crate::log::log_info;
+use crate::metrics::{OpTimer, OtLibOp};
+use crate::status::{bail_status, ffi_call, OtLibStatus, StatusContext, StatusError};
+use crate::transport::{acquire_transport, OtLibTransport};
+
+/// ROM reset pulse used when the caller passes a `rom_reset_pulse_ms` of 0.
+const DEFAULT_ROM_RESET_PULSE: Duration = Duration::from_millis(50);
+
+/// ROM boot timeout used when the caller passes a `rom_timeout_ms` of 0.
+const DEFAULT_ROM_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Banner printed by the ROM, and by the test ROM as `TestROM:`, when it starts.
+const ROM_BOOT_MSG: &str = r"ROM:";
+
+/// Describes how to load the bitstream at `bitstream_path`. A `rom_reset_pulse_ms` or
+/// `rom_timeout_ms` of 0 selects the default.
+fn load_bitstream_params(
+    bitstream_path: PathBuf,
+    rom_reset_pulse_ms: u64,
+    rom_timeout_ms: u64,
+    clear_bitstream: bool,
+) -> LoadBitstream {
+    let duration_or = |ms, default| match ms {
+        0 => default,
+        ms => Duration::from_millis(ms),
+    };
+    LoadBitstream {
+        clear_bitstream,
+        bitstream: Some(bitstream_path),
+        rom_reset_pulse: duration_or(rom_reset_pulse_ms, DEFAULT_ROM_RESET_PULSE),
+        rom_timeout: duration_or(rom_timeout_ms, DEFAULT_ROM_TIMEOUT),
+    }
+}
+
+/// Reports the progress of a bitstream load in percent, see `OtLibFpgaLoadBitstream(...)`.
+pub type OtLibProgressCallback = extern "C" fn(percent: u32);
+
+/// Loads the FPGA bitstream at `fpga_bitstream`.
+///
+/// If `progress_cb` is not null, it is called with 0 when loading starts, with 50 once the FPGA is
+/// cleared (if `clear_bitstream` is set) and with 100 once the ROM has come up.
+///
+/// Once the bitstream is loaded, the DUT is held in reset for `rom_reset_pulse_ms` and the ROM
+/// banner awaited over the console for up to `rom_timeout_ms`; a ROM that does not come up in
+/// time is reported as `OtLibStatus::RomTimeout`.
+///
+/// Without `clear_bitstream` the FPGA is not cleared first, and programming is skipped if the
+/// same bitstream is already loaded. The fabric then keeps whatever state the previous DUT left
+/// in it (e.g. OTP and flash contents of the emulated chip), so this should only be used when the
+/// next part tolerates that state.
+#[no_mangle]
+pub extern "C" fn OtLibFpgaLoadBitstream(
+    transport: *const OtLibTransport,
+    fpga_bitstream: *mut c_char,
+    rom_reset_pulse_ms: u64,
+    rom_timeout_ms: u64,
+    clear_bitstream: bool,
+    progress_cb: Option<OtLibProgressCallback>,
+) -> OtLibStatus {
+    ffi_call("OtLibFpgaLoadBitstream", || {
+        let _timer = OpTimer::start(OtLibOp::BitstreamLoad);
+        check_not_null(transport, "transport")?;
+        check_not_null(fpga_bitstream, "fpga_bitstream")?;
+
+        // Unpack FPGA bitstream path string.
+        let fpga_bitstream_in = cstr_to_str(fpga_bitstream)?;
+
+        let bitstream_path = PathBuf::from(fpga_bitstream_in);
+        if !bitstream_path.is_file() {
+            bail_status!(
+                OtLibStatus::BitstreamNotFound,
+                "FPGA bitstream not found: {:?}.",
+                bitstream_path
+            );
+        }
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        if dry_run() {
+            return Ok(());
+        }
+
+        load_bitstream(
+            transport,
+            bitstream_path,
+            rom_reset_pulse_ms,
+            rom_timeout_ms,
+            clear_bitstream,
+            progress_cb,
+        )
+    })
+}
+
+/// Loads the bitstream buffer `data` of `len` bytes into the FPGA.
+///
+/// Same as `OtLibFpgaLoadBitstream`, except that the bitstream is staged in a temporary file,
+/// which is removed once loading is done.
+#[no_mangle]
+pub extern "C" fn OtLibFpgaLoadBitstreamBuf(
+    transport: *const OtLibTransport,
+    data: *const u8,
+    len: usize,
+    rom_reset_pulse_ms: u64,
+    rom_timeout_ms: u64,
+    clear_bitstream: bool,
+    progress_cb: Option<OtLibProgressCallback>,
+) -> OtLibStatus {
+    ffi_call("OtLibFpgaLoadBitstreamBuf", || {
+        let _timer = OpTimer::start(OtLibOp::BitstreamLoad);
+        check_not_null(transport, "transport")?;
+        check_not_null(data, "data")?;
+        if len == 0 {
+            bail_status!(
+                OtLibStatus::InvalidArgument,
+                "FPGA bitstream buffer is empty."
+            );
+        }
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        if dry_run() {
+            return Ok(());
+        }
+
+        // SAFETY: `data` was checked to be non-null and must point to `len` readable bytes.
+        let data = unsafe { slice::from_raw_parts(data, len) };
+        let bitstream = TempBitstream::new(data)?;
+        load_bitstream(
+            transport,
+            bitstream.path.clone(),
+            rom_reset_pulse_ms,
+            rom_timeout_ms,
+            clear_bitstream,
+            progress_cb,
+        )
+    })
+}
+
+/// Loads the bitstream at `bitstream_path` into the FPGA.
+fn load_bitstream(
+    transport: &TransportWrapper,
+    bitstream_path: PathBuf,
+    rom_reset_pulse_ms: u64,
+    rom_timeout_ms: u64,
+    clear_bitstream: bool,
+    progress_cb: Option<OtLibProgressCallback>,
+) -> Result<()> {
+    let progress = |percent| {
+        if let Some(cb) = progress_cb {
+            cb(percent);
+        }
+    };
+    let init = |load_bitstream: LoadBitstream| {
+        load_bitstream.init(transport).map_err(|e| {
+            let status = load_bitstream_status(&e);
+            anyhow::Error::from(StatusError::new(status, e))
+        })
+    };
+
+    let mut load_bitstream = load_bitstream_params(
+        bitstream_path,
+        rom_reset_pulse_ms,
+        rom_timeout_ms,
+        clear_bitstream,
+    );
+    progress(0);
+    if progress_cb.is_some() && load_bitstream.clear_bitstream {
+        // Clear the FPGA on its own to report progress before programming it.
+        init(LoadBitstream {
+            clear_bitstream: true,
+            bitstream: None,
+            rom_reset_pulse: load_bitstream.rom_reset_pulse,
+            rom_timeout: load_bitstream.rom_timeout,
+        })?;
+        progress(50);
+        load_bitstream.clear_bitstream = false;
+    }
+    let (rom_reset_pulse, rom_timeout) =
+        (load_bitstream.rom_reset_pulse, load_bitstream.rom_timeout);
+    init(load_bitstream)?;
+    wait_for_rom(transport, rom_reset_pulse, rom_timeout)?;
+    progress(100);
+    log_info!("load_bitstream: Ok");
+    Ok(())
+}
+
+/// A bitstream staged in a temporary file, which is removed when this is dropped.
+struct TempBitstream {
+    path: PathBuf,
+}
+
+impl TempBitstream {
+    fn new(data: &[u8]) -> Result<Self> {
+        // Distinguishes the bitstreams of concurrent loads within this process.
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "otlib_bitstream_{}_{}.bit",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .with_context(|| format!("Could not create temporary bitstream {:?}.", path))
+            .status(OtLibStatus::InternalError)?;
+        // From here on, the file is removed when `bitstream` is dropped, including on error.
+        let bitstream = TempBitstream { path };
+        file.write_all(data)
+            .and_then(|_| file.flush())
+            .with_context(|| format!("Could not write temporary bitstream {:?}.", bitstream.path))
+            .status(OtLibStatus::InternalError)?;
+        Ok(bitstream)
+    }
+}
+
+impl Drop for TempBitstream {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Resets the DUT, holding reset for `rom_reset_pulse`, and waits up to `rom_timeout` for the ROM
+/// banner over the console.
+///
+/// The ROM not coming up in time is reported as `OtLibStatus::RomTimeout`.
+fn wait_for_rom(
+    transport: &TransportWrapper,
+    rom_reset_pulse: Duration,
+    rom_timeout: Duration,
+) -> Result<()> {
+    let uart_console = transport
+        .uart("console")
+        .context("Unable to instantiate the UART console.")
+        .status(OtLibStatus::TransportError)?;
+    transport
+        .reset_target(rom_reset_pulse, true)
+        .context("Could not reset the DUT.")
+        .status(OtLibStatus::TransportError)?;
+    rom_wait_result(wait_for(&*uart_console, ROM_BOOT_MSG, rom_timeout).map(|_| ()))
+}
+
+/// Tags a console timeout of `result`, the wait for the ROM banner, as `OtLibStatus::RomTimeout`.
+fn rom_wait_result(result: Result<()>) -> Result<()> {
+    result.map_err(|e| match OtLibStatus::from_error(&e) {
+        OtLibStatus::ConsoleTimeout => {
+            StatusError::new(OtLibStatus::RomTimeout, e.context("ROM did not come up.")).into()
+        }
+        _ => e,
+    })
+}
+
+/// Classifies a failure reported by `LoadBitstream::init(...)`.
+fn load_bitstream_status(err: &anyhow::Error) -> OtLibStatus {
+    let unsupported = err.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<TransportError>(),
+            Some(TransportError::UnsupportedOperation)
+        )
+    });
+    let not_found = err.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == ErrorKind::NotFound)
+    });
+    if unsupported {
+        // Backends without an FPGA, such as the Ti50 emulator, cannot load a bitstream.
+        OtLibStatus::Unsupported
+    } else if not_found {
+        OtLibStatus::BitstreamNotFound
+    } else {
+        OtLibStatus::FpgaProgramError
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::ffi::CString;
+
+    use crate::ffi::tests::dangling;
+
+    #[test]
+    fn missing_bitstream_is_reported() {
+        let path = CString::new("/nonexistent/bitstream.bit").unwrap();
+        let status =
+            OtLibFpgaLoadBitstream(dangling(), path.as_ptr() as *mut c_char, 0, 0, true, None);
+        assert_eq!(status, OtLibStatus::BitstreamNotFound);
+    }
+
+    #[test]
+    fn temp_bitstream_is_removed_on_drop() {
+        let bitstream = TempBitstream::new(b"bitstream").unwrap();
+        let path = bitstream.path.clone();
+        assert_eq!(fs::read(&path).unwrap(), b"bitstream");
+        drop(bitstream);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn zero_rom_timings_use_defaults() {
+        let params = load_bitstream_params(PathBuf::from("fpga.bit"), 0, 0, true);
+        assert_eq!(params.rom_reset_pulse, DEFAULT_ROM_RESET_PULSE);
+        assert_eq!(params.rom_timeout, DEFAULT_ROM_TIMEOUT);
+        let params = load_bitstream_params(PathBuf::from("fpga.bit"), 100, 10_000, false);
+        assert_eq!(params.rom_reset_pulse, Duration::from_millis(100));
+        assert_eq!(params.rom_timeout, Duration::from_secs(10));
+        assert!(!params.clear_bitstream);
+    }
+
+    #[test]
+    fn load_failures_are_classified() {
+        let not_found = anyhow::Error::from(std::io::Error::from(ErrorKind::NotFound));
+        assert_eq!(
+            load_bitstream_status(&not_found.context("open bitstream")),
+            OtLibStatus::BitstreamNotFound
+        );
+        assert_eq!(
+            load_bitstream_status(&TransportError::UnsupportedOperation.into()),
+            OtLibStatus::Unsupported
+        );
+        assert_eq!(
+            load_bitstream_status(&anyhow!("USB transfer failed")),
+            OtLibStatus::FpgaProgramError
+        );
+    }
+
+    #[test]
+    fn rom_timeouts_are_tagged() {
+        let timeout = StatusError::new(OtLibStatus::ConsoleTimeout, anyhow!("Timed Out")).into();
+        let err = rom_wait_result(Err(timeout)).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::RomTimeout);
+
+        let closed = StatusError::new(OtLibStatus::ConsoleError, anyhow!("UART closed")).into();
+        let err = rom_wait_result(Err(closed)).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::ConsoleError);
+        assert!(rom_wait_result(Ok(())).is_ok());
+    }
+}
@@ -0,0 +1,509 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::os::raw::c_char;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+use opentitanlib::app::TransportWrapper;
+use opentitanlib::io::console::ConsoleDevice;
+use regex::Regex;
+
+use crate::console::wait_for;
+use crate::dry_run::dry_run;
+use crate::ffi::{check_not_null, cstr_array_to_vec, cstr_to_str};
+use crate::log::{log_error, log_info};
+use crate::metrics::elapsed_us;
+use crate::status::{bail_status, ffi_call, OtLibStatus, StatusContext};
+use crate::transport::{acquire_transport, OtLibTransport};
+
+/// Outcome of the boot checked by `OtLibCheckTransportImgBoot(...)`.
+///
+/// NOTE: must match the definition of `DutBootResult` in src/ate/test_programs/dut_lib/dut_lib.h
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtLibBootResult {
+    /// The owner firmware booted.
+    OwnerBooted = 0,
+    /// The ROM_EXT detected an invalid UDS certificate.
+    UdsCertInvalid = 1,
+    /// A boot fault (BFV) was reported.
+    BootFault = 2,
+    /// The owner firmware boot message did not appear in time.
+    Timeout = 3,
+    /// The ROM_EXT boot message did not appear.
+    RomExtFailed = 4,
+}
+
+/// Time the DUT is held in reset before its boot is timed, as for `OtLibBootstrap(...)`.
+const BOOT_RESET_DELAY: Duration = Duration::from_millis(50);
+
+/// ROM_EXT boot message used when the caller passes an empty `rom_ext_regex`.
+const DEFAULT_ROM_EXT_REGEX: &str = r"(?:\n| )ROM_EXT[: ](.*)\r\n";
+
+// CAUTION: This error message should match the one in
+//   @lowrisc_opentitan//sw/device/silicon_creator/lib/cert/dice_chain.c.
+const ROM_EXT_CERT_FAILURE_MSG: &str = r"UDS certificate not valid";
+
+/// Classifies the outcome `result` of waiting for the owner firmware boot message
+/// `owner_fw_boot_msg`, also returning which of `failure_regexes` matched, if any.
+///
+/// Without `owner_fw_boot_msg` only boot errors are waited for, so a timeout means the owner
+/// firmware booted.
+fn owner_fw_boot_result<'a>(
+    result: Result<Vec<String>>,
+    owner_fw_boot_msg: &str,
+    failure_regexes: &'a [Regex],
+) -> Result<(OtLibBootResult, Option<&'a Regex>)> {
+    match result {
+        Ok(captures) if captures[0] == ROM_EXT_CERT_FAILURE_MSG => {
+            Ok((OtLibBootResult::UdsCertInvalid, None))
+        }
+        Ok(captures) if captures[0].starts_with("BFV:") => Ok((OtLibBootResult::BootFault, None)),
+        Ok(captures) => match failure_regexes.iter().find(|rx| rx.is_match(&captures[0])) {
+            Some(rx) => Ok((OtLibBootResult::BootFault, Some(rx))),
+            None => Ok((OtLibBootResult::OwnerBooted, None)),
+        },
+        Err(e) if OtLibStatus::from_error(&e) == OtLibStatus::ConsoleTimeout => {
+            if owner_fw_boot_msg.is_empty() {
+                // Error message not found after timeout. This is the expected behavior.
+                Ok((OtLibBootResult::OwnerBooted, None))
+            } else {
+                Ok((OtLibBootResult::Timeout, None))
+            }
+        }
+        // An unexpected error occurred while waiting for the console output.
+        Err(e) => Err(e),
+    }
+}
+
+/// Compiles the extra boot failure regexes passed in from C.
+///
+/// A regex that does not compile is reported as `OtLibStatus::InvalidArgument`.
+fn compile_boot_failure_regexes(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern)
+                .with_context(|| format!("Invalid boot failure regex `{}`.", pattern))
+                .status(OtLibStatus::InvalidArgument)
+        })
+        .collect()
+}
+
+/// Returns `regex`, or `default` if it is empty.
+///
+/// A `regex` that does not compile is reported as `OtLibStatus::InvalidArgument`.
+fn regex_or<'a>(regex: &'a str, default: &'a str, name: &str) -> Result<&'a str> {
+    if regex.is_empty() {
+        return Ok(default);
+    }
+    Regex::new(regex)
+        .with_context(|| format!("Invalid `{}`.", name))
+        .status(OtLibStatus::InvalidArgument)?;
+    Ok(regex)
+}
+
+/// What `timed_boot(...)` saw on the console after the reset.
+enum TimedBoot {
+    /// The ROM_EXT boot message did not appear; the error of waiting for it.
+    RomExtFailed(anyhow::Error),
+    /// The ROM_EXT boot message appeared after `rom_ext_us`, and waiting for the owner firmware
+    /// boot text returned `owner_fw` after `owner_fw_us`, both in microseconds since the reset.
+    RomExtBooted {
+        rom_ext_us: u64,
+        owner_fw: Result<Vec<String>>,
+        owner_fw_us: u64,
+    },
+}
+
+/// Resets the DUT with `reset`, then waits on `console` for the ROM_EXT boot message
+/// `rom_ext_regex` and, once it appeared, for the owner firmware `boot_text`.
+///
+/// Both messages are timed from the moment `reset` returned, which has released the reset, so
+/// that the times do not include the reset pulse.
+fn timed_boot<T: ConsoleDevice + ?Sized>(
+    console: &T,
+    reset: impl FnOnce() -> Result<()>,
+    rom_ext_regex: &str,
+    rom_ext_timeout: Duration,
+    boot_text: &str,
+    owner_fw_timeout: Duration,
+) -> Result<TimedBoot> {
+    reset()?;
+    let reset_released = Instant::now();
+
+    // Wait for a successful ROM_EXT boot message.
+    log_info!("Waiting for ROM_EXT to boot ...");
+    if let Err(e) = wait_for(console, rom_ext_regex, rom_ext_timeout) {
+        return Ok(TimedBoot::RomExtFailed(e));
+    }
+    let rom_ext_us = elapsed_us(reset_released);
+    log_info!("ROM_EXT has booted after {} us.", rom_ext_us);
+
+    log_info!("Waiting for Owner Firmware to boot ...");
+    let owner_fw = wait_for(console, boot_text, owner_fw_timeout);
+    Ok(TimedBoot::RomExtBooted {
+        rom_ext_us,
+        owner_fw,
+        owner_fw_us: elapsed_us(reset_released),
+    })
+}
+
+/// Writes `result` to `out_result` and fails with the status matching a failed boot.
+///
+/// `failure_regex` is the extra boot failure regex that matched, if any.
+fn report_boot_result(
+    result: OtLibBootResult,
+    failure_regex: Option<&Regex>,
+    out_result: *mut OtLibBootResult,
+) -> Result<()> {
+    // SAFETY: `out_result` was checked to be non-null and must point to writable memory.
+    unsafe { *out_result = result };
+    match result {
+        OtLibBootResult::OwnerBooted => {
+            log_info!("Owner Firmware has booted.");
+            Ok(())
+        }
+        OtLibBootResult::UdsCertInvalid => {
+            log_error!("ROM_EXT detected invalid UDS certificate!");
+            bail_status!(
+                OtLibStatus::BootError,
+                "ROM_EXT detected invalid UDS certificate!"
+            );
+        }
+        OtLibBootResult::BootFault if failure_regex.is_some() => {
+            let pattern = failure_regex.map_or("", Regex::as_str);
+            log_error!("Boot failure pattern `{}` matched!", pattern);
+            bail_status!(
+                OtLibStatus::BootError,
+                "Boot failure pattern `{}` matched!",
+                pattern
+            );
+        }
+        OtLibBootResult::BootFault => {
+            log_error!("Boot fault detected!");
+            bail_status!(OtLibStatus::BootError, "Boot fault detected!");
+        }
+        OtLibBootResult::Timeout => bail_status!(
+            OtLibStatus::ConsoleTimeout,
+            "Timed out waiting for the Owner Firmware to boot."
+        ),
+        OtLibBootResult::RomExtFailed => {
+            bail_status!(OtLibStatus::BootError, "Failed to boot the ROM_EXT.")
+        }
+    }
+}
+
+/// Resets the DUT and checks that the ROM_EXT and then the owner firmware boot, writing the
+/// outcome to `out_result`.
+///
+/// Boots that fail because of the DUT are reported as `OtLibStatus::BootError`, or as
+/// `OtLibStatus::ConsoleTimeout` if the owner firmware boot message did not appear; `out_result`
+/// then tells the failures apart. Any other status means the outcome could not be determined.
+///
+/// A non-empty `rom_ext_regex` replaces the default ROM_EXT boot message, and a non-empty
+/// `boot_success_regex` replaces `owner_fw_boot_msg` as the owner firmware boot message.
+///
+/// The `boot_failure_regexes_count` regexes in `boot_failure_regexes` are fatal markers in
+/// addition to the invalid UDS certificate and BFV messages. A match is reported as
+/// `OtLibBootResult::BootFault`, naming the matched regex in the last error.
+///
+/// Both boot messages are awaited for up to `timeout_ms`, see
+/// `OtLibCheckTransportImgBootWithTimeouts(...)` to set their timeouts separately.
+///
+/// The DUT is held in reset for 50 ms. The time in microseconds from the release of the reset
+/// until the ROM_EXT and the owner firmware boot messages appeared is written to
+/// `out_rom_ext_boot_us` and `out_owner_fw_boot_us`, or 0 if the message did not appear.
+#[no_mangle]
+pub extern "C" fn OtLibCheckTransportImgBoot(
+    transport: *const OtLibTransport,
+    owner_fw_boot_msg: *mut c_char,
+    timeout_ms: u64,
+    rom_ext_regex: *const c_char,
+    boot_success_regex: *const c_char,
+    boot_failure_regexes: *const *const c_char,
+    boot_failure_regexes_count: usize,
+    out_result: *mut OtLibBootResult,
+    out_rom_ext_boot_us: *mut u64,
+    out_owner_fw_boot_us: *mut u64,
+) -> OtLibStatus {
+    OtLibCheckTransportImgBootWithTimeouts(
+        transport,
+        owner_fw_boot_msg,
+        timeout_ms,
+        timeout_ms,
+        rom_ext_regex,
+        boot_success_regex,
+        boot_failure_regexes,
+        boot_failure_regexes_count,
+        out_result,
+        out_rom_ext_boot_us,
+        out_owner_fw_boot_us,
+    )
+}
+
+/// Same as `OtLibCheckTransportImgBoot(...)`, waiting up to `rom_ext_timeout_ms` for the ROM_EXT
+/// boot message and then up to `owner_fw_timeout_ms` for the owner firmware boot message.
+#[no_mangle]
+pub extern "C" fn OtLibCheckTransportImgBootWithTimeouts(
+    transport: *const OtLibTransport,
+    owner_fw_boot_msg: *mut c_char,
+    rom_ext_timeout_ms: u64,
+    owner_fw_timeout_ms: u64,
+    rom_ext_regex: *const c_char,
+    boot_success_regex: *const c_char,
+    boot_failure_regexes: *const *const c_char,
+    boot_failure_regexes_count: usize,
+    out_result: *mut OtLibBootResult,
+    out_rom_ext_boot_us: *mut u64,
+    out_owner_fw_boot_us: *mut u64,
+) -> OtLibStatus {
+    ffi_call("OtLibCheckTransportImgBootWithTimeouts", || {
+        check_not_null(transport, "transport")?;
+        check_not_null(owner_fw_boot_msg, "owner_fw_boot_msg")?;
+        check_not_null(rom_ext_regex, "rom_ext_regex")?;
+        check_not_null(boot_success_regex, "boot_success_regex")?;
+        check_not_null(out_result, "out_result")?;
+        check_not_null(out_rom_ext_boot_us, "out_rom_ext_boot_us")?;
+        check_not_null(out_owner_fw_boot_us, "out_owner_fw_boot_us")?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+
+        // Unpack boot message string.
+        let owner_fw_boot_msg_in = regex_or(
+            cstr_to_str(boot_success_regex)?,
+            cstr_to_str(owner_fw_boot_msg)?,
+            "boot_success_regex",
+        )?;
+        let rom_ext_regex = regex_or(
+            cstr_to_str(rom_ext_regex)?,
+            DEFAULT_ROM_EXT_REGEX,
+            "rom_ext_regex",
+        )?;
+        let failure_regexes = compile_boot_failure_regexes(&cstr_array_to_vec(
+            boot_failure_regexes,
+            boot_failure_regexes_count,
+        )?)?;
+
+        let rom_ext_timeout = Duration::from_millis(rom_ext_timeout_ms);
+        let owner_fw_timeout = Duration::from_millis(owner_fw_timeout_ms);
+
+        if dry_run() {
+            return Ok(());
+        }
+
+        let boot_failure_msg = r"BFV:.*\r\n";
+        let boot_errors_text = std::iter::once(ROM_EXT_CERT_FAILURE_MSG)
+            .chain(std::iter::once(boot_failure_msg))
+            .chain(failure_regexes.iter().map(Regex::as_str))
+            .collect::<Vec<_>>()
+            .join("|");
+        let boot_text = match owner_fw_boot_msg_in {
+            "" => format!(r"(?s)({boot_errors_text})"),
+            x => format!(r"(?s)({boot_errors_text}|{x})"),
+        };
+        log_info!("Boot Text: {}", boot_text);
+
+        // SAFETY: The boot time out-parameters were checked to be non-null and must point to
+        // writable `u64`s.
+        unsafe {
+            *out_rom_ext_boot_us = 0;
+            *out_owner_fw_boot_us = 0;
+        }
+
+        // Get the UART console handle, reset the DUT and time its boot.
+        let uart_console = transport
+            .uart("console")
+            .context("Unable to instantiate the UART console.")
+            .status(OtLibStatus::TransportError)?;
+        let reset = || {
+            transport
+                .reset_target(BOOT_RESET_DELAY, true)
+                .context("Failed to reset the DUT.")
+                .status(OtLibStatus::TransportError)
+        };
+        let boot = timed_boot(
+            &*uart_console,
+            reset,
+            rom_ext_regex,
+            rom_ext_timeout,
+            &boot_text,
+            owner_fw_timeout,
+        )?;
+        let (owner_fw, owner_fw_boot_us) = match boot {
+            TimedBoot::RomExtFailed(e) => {
+                log_error!("Failed to boot the ROM_EXT: {:#}", e);
+                return report_boot_result(OtLibBootResult::RomExtFailed, None, out_result);
+            }
+            TimedBoot::RomExtBooted {
+                rom_ext_us,
+                owner_fw,
+                owner_fw_us,
+            } => {
+                // SAFETY: See above.
+                unsafe { *out_rom_ext_boot_us = rom_ext_us };
+                (owner_fw, owner_fw_us)
+            }
+        };
+        let matched = owner_fw.is_ok();
+        let (boot_result, failure_regex) =
+            owner_fw_boot_result(owner_fw, owner_fw_boot_msg_in, &failure_regexes)?;
+        // Without an owner firmware boot message, there is no message to time.
+        if matched && boot_result == OtLibBootResult::OwnerBooted {
+            // SAFETY: See above.
+            unsafe { *out_owner_fw_boot_us = owner_fw_boot_us };
+            log_info!("Owner Firmware has booted after {} us.", owner_fw_boot_us);
+        }
+        report_boot_result(boot_result, failure_regex, out_result)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::cell::Cell;
+
+    use crate::mock::MockConsole;
+    use crate::status::StatusError;
+
+    fn captures(text: &str) -> Result<Vec<String>> {
+        Ok(vec![text.to_string()])
+    }
+
+    fn timeout() -> Result<Vec<String>> {
+        Err(StatusError::new(OtLibStatus::ConsoleTimeout, anyhow!("Timed Out")).into())
+    }
+
+    #[test]
+    fn owner_fw_boot_outcomes_are_classified() {
+        assert_eq!(
+            owner_fw_boot_result(captures("UDS certificate not valid"), "", &[])
+                .unwrap()
+                .0,
+            OtLibBootResult::UdsCertInvalid
+        );
+        assert_eq!(
+            owner_fw_boot_result(captures("BFV:0142500d\r\n"), "", &[])
+                .unwrap()
+                .0,
+            OtLibBootResult::BootFault
+        );
+        assert_eq!(
+            owner_fw_boot_result(captures("Owner FW booted"), "Owner FW booted", &[])
+                .unwrap()
+                .0,
+            OtLibBootResult::OwnerBooted
+        );
+        assert_eq!(
+            owner_fw_boot_result(timeout(), "Owner FW booted", &[])
+                .unwrap()
+                .0,
+            OtLibBootResult::Timeout
+        );
+        assert!(owner_fw_boot_result(Err(anyhow!("UART closed")), "", &[]).is_err());
+    }
+
+    #[test]
+    fn extra_failure_regexes_are_reported() {
+        let regexes =
+            compile_boot_failure_regexes(&["FAULT:".to_string(), "PANIC:".to_string()]).unwrap();
+        let (result, regex) =
+            owner_fw_boot_result(captures("PANIC: stack overflow"), "", &regexes).unwrap();
+        assert_eq!(result, OtLibBootResult::BootFault);
+        assert_eq!(regex.map(Regex::as_str), Some("PANIC:"));
+
+        let err = compile_boot_failure_regexes(&["(FAULT".to_string()]).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn timeout_without_boot_message_is_a_successful_boot() {
+        assert_eq!(
+            owner_fw_boot_result(timeout(), "", &[]).unwrap().0,
+            OtLibBootResult::OwnerBooted
+        );
+    }
+
+    /// Console that fails the test if it is read before the DUT reset was released.
+    struct AfterResetConsole {
+        console: MockConsole,
+        reset_released: Cell<bool>,
+    }
+
+    impl ConsoleDevice for AfterResetConsole {
+        fn console_read(&self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+            assert!(self.reset_released.get(), "console read before the reset");
+            self.console.console_read(buf, timeout)
+        }
+
+        fn console_write(&self, buf: &[u8]) -> Result<()> {
+            self.console.console_write(buf)
+        }
+    }
+
+    const RESET_PULSE: Duration = Duration::from_millis(20);
+
+    fn boot(script: &[u8]) -> TimedBoot {
+        let console = AfterResetConsole {
+            // One byte per read leaves the owner firmware message to the second wait.
+            console: MockConsole::with_chunk_size(script, 1),
+            reset_released: Cell::new(false),
+        };
+        let reset = || {
+            std::thread::sleep(RESET_PULSE);
+            console.reset_released.set(true);
+            Ok(())
+        };
+        let timeout = Duration::from_millis(10);
+        timed_boot(
+            &console,
+            reset,
+            "ROM_EXT:",
+            timeout,
+            "Owner FW booted",
+            timeout,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn boot_is_timed_from_the_reset_release() {
+        let TimedBoot::RomExtBooted {
+            rom_ext_us,
+            owner_fw,
+            owner_fw_us,
+        } = boot(b"ROM_EXT: 0.1\r\nOwner FW booted\r\n")
+        else {
+            panic!("ROM_EXT boot not detected");
+        };
+        assert!(rom_ext_us < RESET_PULSE.as_micros() as u64);
+        assert_eq!(owner_fw.unwrap()[0], "Owner FW booted");
+        assert!(owner_fw_us >= rom_ext_us);
+    }
+
+    #[test]
+    fn boot_without_rom_ext_message_fails() {
+        assert!(matches!(boot(b"garbage\r\n"), TimedBoot::RomExtFailed(_)));
+    }
+
+    #[test]
+    fn boot_regexes_default_when_empty_and_must_compile() {
+        assert_eq!(
+            regex_or("", DEFAULT_ROM_EXT_REGEX, "rom_ext_regex").unwrap(),
+            DEFAULT_ROM_EXT_REGEX
+        );
+        assert_eq!(
+            regex_or("MY_ROM_EXT:(.*)", DEFAULT_ROM_EXT_REGEX, "rom_ext_regex").unwrap(),
+            "MY_ROM_EXT:(.*)"
+        );
+        let err = regex_or("(unbalanced", DEFAULT_ROM_EXT_REGEX, "rom_ext_regex").unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+    }
+}
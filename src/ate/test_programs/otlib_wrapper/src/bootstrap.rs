@@ -0,0 +1,395 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use opentitanlib::app::TransportWrapper;
+use opentitanlib::bootstrap::{BootstrapOptions, BootstrapProtocol};
+use opentitanlib::io::spi::SpiParams;
+use opentitanlib::io::uart::UartParams;
+use opentitanlib::test_utils::bootstrap::Bootstrap;
+
+use crate::cancel::CancelToken;
+use crate::dry_run::dry_run;
+use crate::ffi::{check_file_exists, check_not_null, cstr_to_str};
+use crate::log::log_warn;
+use crate::metrics::{OpTimer, OtLibOp};
+use crate::spi::{is_default, spi_params, OtLibSpiMode, SpiSettings};
+use crate::status::{bail_status, ffi_call, OtLibStatus, StatusContext};
+use crate::transport::{acquire_transport, OtLibTransport};
+
+/// Protocol used to bootstrap a binary into the DUT's flash.
+///
+/// NOTE: must match the definition of `DutBootstrapProtocol` in
+/// src/ate/test_programs/dut_lib/dut_lib.h
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtLibBootstrapProtocol {
+    /// The primitive SPI protocol of early silicon.
+    Primitive = 0,
+    /// The legacy SPI protocol.
+    Legacy = 1,
+    /// The legacy rescue protocol, over the console UART.
+    LegacyRescue = 2,
+    /// The SPI EEPROM protocol.
+    Eeprom = 3,
+    /// The protocol of the ti50 emulator.
+    Emulator = 4,
+}
+
+impl OtLibBootstrapProtocol {
+    /// Whether the protocol talks to the DUT over the console UART.
+    fn uses_uart(self) -> bool {
+        self == OtLibBootstrapProtocol::LegacyRescue
+    }
+}
+
+impl From<OtLibBootstrapProtocol> for BootstrapProtocol {
+    fn from(protocol: OtLibBootstrapProtocol) -> Self {
+        match protocol {
+            OtLibBootstrapProtocol::Primitive => BootstrapProtocol::Primitive,
+            OtLibBootstrapProtocol::Legacy => BootstrapProtocol::Legacy,
+            OtLibBootstrapProtocol::LegacyRescue => BootstrapProtocol::LegacyRescue,
+            OtLibBootstrapProtocol::Eeprom => BootstrapProtocol::Eeprom,
+            OtLibBootstrapProtocol::Emulator => BootstrapProtocol::Emulator,
+        }
+    }
+}
+
+/// SPI interface the SPI protocols bootstrap over, the default of `SpiParams::create(...)` in
+/// opentitanlib's `Bootstrap`.
+const BOOTSTRAP_SPI: &str = "BOOTSTRAP";
+
+/// Rejects UART parameters for protocols that do not bootstrap over the console UART, and SPI
+/// parameters for the one that does, as they would be silently ignored.
+fn check_protocol(
+    protocol: OtLibBootstrapProtocol,
+    baudrate: u32,
+    flow_control: bool,
+    spi_params: &SpiParams,
+) -> Result<BootstrapProtocol> {
+    if !protocol.uses_uart() && (baudrate != 0 || flow_control) {
+        bail_status!(
+            OtLibStatus::InvalidArgument,
+            "The {:?} bootstrap protocol does not use the UART baudrate or flow control.",
+            protocol
+        );
+    }
+    if protocol.uses_uart() && !is_default(spi_params) {
+        bail_status!(
+            OtLibStatus::InvalidArgument,
+            "The {:?} bootstrap protocol does not use the SPI speed or mode.",
+            protocol
+        );
+    }
+    Ok(protocol.into())
+}
+
+/// Builds the bootstrap console UART parameters. A `baudrate` of 0 selects the default baudrate.
+fn uart_params(baudrate: u32, flow_control: bool) -> UartParams {
+    UartParams {
+        uart: "CONSOLE".to_string(),
+        baudrate: (baudrate != 0).then_some(baudrate),
+        flow_control,
+    }
+}
+
+/// Builds the bootstrap options. An `inter_frame_delay_us` or `flash_erase_delay_ms` of 0 selects
+/// the default delay of `protocol`.
+fn bootstrap_options(
+    protocol: BootstrapProtocol,
+    baudrate: u32,
+    flow_control: bool,
+    spi_params: SpiParams,
+    inter_frame_delay_us: u64,
+    flash_erase_delay_ms: u64,
+) -> BootstrapOptions {
+    BootstrapOptions {
+        uart_params: uart_params(baudrate, flow_control),
+        spi_params,
+        protocol,
+        clear_uart: None,
+        reset_delay: Duration::from_millis(100),
+        leave_in_bootstrap: false,
+        leave_in_reset: false,
+        inter_frame_delay: (inter_frame_delay_us != 0)
+            .then(|| Duration::from_micros(inter_frame_delay_us)),
+        flash_erase_delay: (flash_erase_delay_ms != 0)
+            .then(|| Duration::from_millis(flash_erase_delay_ms)),
+    }
+}
+
+/// Runs `bootstrap` up to `attempts` times until it succeeds, waiting `retry_delay` and calling
+/// `reset` between attempts.
+///
+/// The error of the last attempt is returned with the number of attempts made. No further attempt
+/// is made once a cancellation is requested on `cancel`.
+fn bootstrap_with_retries(
+    attempts: u32,
+    retry_delay: Duration,
+    cancel: &CancelToken,
+    mut reset: impl FnMut() -> Result<()>,
+    mut bootstrap: impl FnMut() -> Result<()>,
+) -> Result<()> {
+    let mut attempt = 1;
+    loop {
+        cancel.check()?;
+        match bootstrap() {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < attempts => {
+                log_warn!(
+                    "Bootstrap attempt {} of {} failed: {:#}",
+                    attempt,
+                    attempts,
+                    e
+                );
+                std::thread::sleep(retry_delay);
+                reset()?;
+                attempt += 1;
+            }
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Bootstrap failed after {} attempt(s).", attempt))
+            }
+        }
+    }
+}
+
+/// Bootstraps `bin` into the DUT's flash, re-attempting it up to `retries` times after a failure.
+///
+/// The DUT is reset `retry_delay_ms` after a failed attempt, before the next one. Slower flash
+/// parts may need a longer `flash_erase_delay_ms` for the bootstrap to verify.
+///
+/// The SPI protocols run at `spi_speed_hz` in `spi_mode`; a speed of 0 and `OtLibSpiMode::Default`
+/// keep the defaults of the SPI interface. The SPI interface gets its previous speed and mode back
+/// once the bootstrap is done.
+#[no_mangle]
+pub extern "C" fn OtLibBootstrap(
+    transport: *const OtLibTransport,
+    bin: *mut c_char,
+    baudrate: u32,
+    flow_control: bool,
+    protocol: OtLibBootstrapProtocol,
+    retries: u32,
+    retry_delay_ms: u64,
+    inter_frame_delay_us: u64,
+    flash_erase_delay_ms: u64,
+    spi_speed_hz: u32,
+    spi_mode: OtLibSpiMode,
+) -> OtLibStatus {
+    ffi_call("OtLibBootstrap", || {
+        let _timer = OpTimer::start(OtLibOp::Bootstrap);
+        check_not_null(transport, "transport")?;
+        check_not_null(bin, "bin")?;
+        let spi_params = spi_params(spi_speed_hz, spi_mode);
+        let protocol = check_protocol(protocol, baudrate, flow_control, &spi_params)?;
+
+        // Unpack path strings.
+        let bin_in = cstr_to_str(bin)?;
+        let bin_path = PathBuf::from(bin_in);
+        check_file_exists(&bin_path, "bin")?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        if dry_run() {
+            return Ok(());
+        }
+
+        // opentitanlib applies the SPI speed and mode to the bootstrap SPI, and leaves them set.
+        let _spi_settings = if is_default(&spi_params) {
+            None
+        } else {
+            let spi = transport
+                .spi(BOOTSTRAP_SPI)
+                .context("Unable to instantiate the bootstrap SPI.")
+                .status(OtLibStatus::TransportError)?;
+            Some(SpiSettings::save(&spi, &spi_params)?)
+        };
+
+        // Bootstrap flash binary into the DUT.
+        let bs = Bootstrap {
+            options: bootstrap_options(
+                protocol,
+                baudrate,
+                flow_control,
+                spi_params,
+                inter_frame_delay_us,
+                flash_erase_delay_ms,
+            ),
+            bootstrap: Some(bin_path.clone()),
+        };
+        bootstrap_with_retries(
+            retries.saturating_add(1),
+            Duration::from_millis(retry_delay_ms),
+            guard.cancel_token(),
+            || {
+                transport
+                    .reset_target(Duration::from_millis(50), true)
+                    .context("Could not reset chip.")
+                    .status(OtLibStatus::TransportError)
+            },
+            || {
+                bs.load(transport, &bin_path)
+                    .with_context(|| format!("Failed to bootstrap binary: {:?}.", bin_path))
+                    .status(OtLibStatus::BootstrapError)
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_baudrate_uses_default() {
+        let params = uart_params(0, false);
+        assert_eq!(params.baudrate, None);
+        assert!(!params.flow_control);
+        let params = uart_params(921600, true);
+        assert_eq!(params.baudrate, Some(921600));
+        assert!(params.flow_control);
+    }
+
+    #[test]
+    fn uart_params_are_rejected_for_spi_protocols() {
+        let spi = SpiParams::default();
+        let err = check_protocol(OtLibBootstrapProtocol::Eeprom, 115200, false, &spi).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+        let err = check_protocol(OtLibBootstrapProtocol::Legacy, 0, true, &spi).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+        assert_eq!(
+            check_protocol(OtLibBootstrapProtocol::Eeprom, 0, false, &spi).unwrap(),
+            BootstrapProtocol::Eeprom
+        );
+        assert_eq!(
+            check_protocol(OtLibBootstrapProtocol::LegacyRescue, 115200, true, &spi).unwrap(),
+            BootstrapProtocol::LegacyRescue
+        );
+    }
+
+    #[test]
+    fn spi_params_are_rejected_for_uart_protocol() {
+        let spi = spi_params(4_000_000, OtLibSpiMode::Mode0);
+        let err = check_protocol(OtLibBootstrapProtocol::LegacyRescue, 0, false, &spi).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+        assert_eq!(
+            check_protocol(OtLibBootstrapProtocol::Eeprom, 0, false, &spi).unwrap(),
+            BootstrapProtocol::Eeprom
+        );
+        let options = bootstrap_options(BootstrapProtocol::Eeprom, 0, false, spi, 0, 0);
+        assert_eq!(options.spi_params.speed, Some(4_000_000));
+    }
+
+    #[test]
+    fn zero_delays_use_protocol_defaults() {
+        let options = bootstrap_options(
+            BootstrapProtocol::Eeprom,
+            0,
+            false,
+            SpiParams::default(),
+            0,
+            0,
+        );
+        assert_eq!(options.inter_frame_delay, None);
+        assert_eq!(options.flash_erase_delay, None);
+        let options = bootstrap_options(
+            BootstrapProtocol::Eeprom,
+            0,
+            false,
+            SpiParams::default(),
+            500,
+            250,
+        );
+        assert_eq!(options.inter_frame_delay, Some(Duration::from_micros(500)));
+        assert_eq!(options.flash_erase_delay, Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn bootstrap_rejects_missing_bin() {
+        let bin = std::ffi::CString::new("/nonexistent/perso.bin").unwrap();
+        let status = OtLibBootstrap(
+            crate::ffi::tests::dangling(),
+            bin.as_ptr() as *mut c_char,
+            0,
+            false,
+            OtLibBootstrapProtocol::Eeprom,
+            0,
+            0,
+            0,
+            0,
+            0,
+            OtLibSpiMode::Default,
+        );
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn bootstrap_is_retried_until_it_succeeds() {
+        let (mut resets, mut tries) = (0, 0);
+        let result = bootstrap_with_retries(
+            3,
+            Duration::ZERO,
+            &CancelToken::default(),
+            || {
+                resets += 1;
+                Ok(())
+            },
+            || {
+                tries += 1;
+                if tries < 3 {
+                    bail_status!(OtLibStatus::BootstrapError, "EEPROM handshake failed.");
+                }
+                Ok(())
+            },
+        );
+        assert!(result.is_ok());
+        assert_eq!((resets, tries), (2, 3));
+    }
+
+    #[test]
+    fn bootstrap_error_reports_attempts() {
+        let mut tries = 0;
+        let err = bootstrap_with_retries(
+            2,
+            Duration::ZERO,
+            &CancelToken::default(),
+            || Ok(()),
+            || {
+                tries += 1;
+                bail_status!(OtLibStatus::BootstrapError, "EEPROM handshake failed.");
+            },
+        )
+        .unwrap_err();
+        assert_eq!(tries, 2);
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::BootstrapError);
+        assert!(format!("{:#}", err).starts_with("Bootstrap failed after 2 attempt(s)."));
+    }
+
+    #[test]
+    fn cancelled_bootstrap_is_not_retried() {
+        let cancel = CancelToken::default();
+        let mut tries = 0;
+        let err = bootstrap_with_retries(
+            3,
+            Duration::ZERO,
+            &cancel,
+            || Ok(()),
+            || {
+                tries += 1;
+                cancel.cancel();
+                bail_status!(OtLibStatus::BootstrapError, "EEPROM handshake failed.");
+            },
+        )
+        .unwrap_err();
+        assert_eq!(tries, 1);
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::Cancelled);
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use opentitanlib::io::console::ConsoleDevice;
+
+use crate::status::{bail_status, OtLibStatus};
+
+/// Longest a `CancellableConsole` read blocks before the cancellation is checked again.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Cancellation requested by `OtLibRequestCancel(...)` for the long-running operations of a
+/// transport, shared with the consoles opened on it.
+#[derive(Clone, Default)]
+pub(crate) struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Requests the cancellation. May be called from any thread.
+    pub(crate) fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Fails with `OtLibStatus::Cancelled` if a cancellation was requested.
+    ///
+    /// The request is consumed by the operation it cancels, so that the following calls run
+    /// normally.
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.0.swap(false, Ordering::AcqRel) {
+            bail_status!(OtLibStatus::Cancelled, "The operation was cancelled.");
+        }
+        Ok(())
+    }
+}
+
+/// Console device that fails its reads with `OtLibStatus::Cancelled` once a cancellation is
+/// requested on `cancel`.
+///
+/// Reads block for at most `CANCEL_POLL_INTERVAL` at a time; those that time out return no bytes,
+/// which the console helpers already treat as "nothing received yet".
+pub(crate) struct CancellableConsole<'d, T: ?Sized> {
+    device: &'d T,
+    cancel: &'d CancelToken,
+}
+
+impl<'d, T: ConsoleDevice + ?Sized> CancellableConsole<'d, T> {
+    pub(crate) fn new(device: &'d T, cancel: &'d CancelToken) -> Self {
+        CancellableConsole { device, cancel }
+    }
+}
+
+impl<T: ConsoleDevice + ?Sized> ConsoleDevice for CancellableConsole<'_, T> {
+    fn console_read(&self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        self.cancel.check()?;
+        self.device
+            .console_read(buf, timeout.min(CANCEL_POLL_INTERVAL))
+    }
+
+    fn console_write(&self, buf: &[u8]) -> Result<()> {
+        self.device.console_write(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    use crate::mock::MockConsole;
+
+    #[test]
+    fn cancellation_is_consumed_by_the_cancelled_operation() {
+        let cancel = CancelToken::default();
+        assert!(cancel.check().is_ok());
+        cancel.clone().cancel();
+        let err = cancel.check().unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::Cancelled);
+        assert!(cancel.check().is_ok());
+    }
+
+    #[test]
+    fn cancellable_console_reads_are_sliced_and_cancelled() {
+        let console = MockConsole::new(b"");
+        let cancel = CancelToken::default();
+        let device = CancellableConsole::new(&console, &cancel);
+        let mut buf = [0u8; 8];
+        let start = Instant::now();
+        assert_eq!(
+            device
+                .console_read(&mut buf, Duration::from_secs(10))
+                .unwrap(),
+            0
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        cancel.cancel();
+        let err = device.console_read(&mut buf, Duration::ZERO).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::Cancelled);
+    }
+}
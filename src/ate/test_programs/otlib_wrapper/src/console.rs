@@ -0,0 +1,3481 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::fs::File;
+use std::io::Write;
+use std::mem::ManuallyDrop;
+use std::os::fd::FromRawFd;
+use std::os::raw::{c_char, c_int};
+use std::rc::Rc;
+use std::slice;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use crc::{Algorithm, Crc, CRC_32_BZIP2, CRC_32_ISO_HDLC, CRC_32_MPEG_2};
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use regex::bytes::Regex as BytesRegex;
+use regex::Regex;
+
+use opentitanlib::app::TransportWrapper;
+use opentitanlib::console::spi::SpiConsoleDevice;
+use opentitanlib::io::console::ConsoleDevice;
+use opentitanlib::io::gpio::{GpioPin, PinMode};
+use opentitanlib::io::spi::{SpiParams, Target};
+use opentitanlib::uart::console::{ExitStatus, UartConsole};
+
+use crate::cancel::{CancelToken, CancellableConsole};
+use crate::dry_run::dry_run;
+use crate::ffi::{
+    check_not_empty, check_not_null, copy_to_cstr_buf, cstr_array_to_vec, cstr_to_str,
+};
+use crate::gpio::{ActiveLowPin, OtLibPullMode};
+use crate::log::{log_error, log_info};
+use crate::metrics::{OpTimer, OtLibOp};
+use crate::spi::{configure_spi, spi_params, OtLibSpiMode, SpiSettings};
+use crate::status::{
+    bail_status, catch_panic, ffi_call, set_last_error, OtLibStatus, StatusContext, StatusError,
+};
+use crate::transport::{acquire_transport, OtLibTransport};
+use crate::{DutSpiFrame, CONSOLE_BUFFER_MAX_SIZE};
+
+/// Waits for a message matching `rx` to be received over the console.
+///
+/// Timeouts are reported as `OtLibStatus::ConsoleTimeout`, any other failure as
+/// `OtLibStatus::ConsoleError`.
+pub(crate) fn wait_for<T>(console: &T, rx: &str, timeout: Duration) -> Result<Vec<String>>
+where
+    T: ConsoleDevice + ?Sized,
+{
+    UartConsole::wait_for(console, rx, timeout).map_err(|e| {
+        let status = if e.to_string().contains("Timed Out") {
+            OtLibStatus::ConsoleTimeout
+        } else {
+            OtLibStatus::ConsoleError
+        };
+        StatusError::new(status, e).into()
+    })
+}
+
+/// SPI interface used when the caller passes an empty `spi_interface`.
+const DEFAULT_SPI_INTERFACE: &str = "BOOTSTRAP";
+
+/// Device console TX-ready pin used when the caller passes an empty `tx_ready_pin`.
+const DEFAULT_TX_READY_PIN: &str = "IOA5";
+
+/// Looks up the SPI interface and the device console TX-ready pin used to build a
+/// `SpiConsoleDevice`.
+///
+/// An unknown `spi_interface` or `tx_ready_pin` is reported as `OtLibStatus::InvalidArgument`.
+fn lookup_spi_console(
+    transport: &TransportWrapper,
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+) -> Result<(Rc<dyn Target>, Rc<dyn GpioPin>)> {
+    let spi_interface = match cstr_to_str(spi_interface)? {
+        "" => DEFAULT_SPI_INTERFACE,
+        x => x,
+    };
+    let tx_ready_pin = match cstr_to_str(tx_ready_pin)? {
+        "" => DEFAULT_TX_READY_PIN,
+        x => x,
+    };
+    let spi = transport
+        .spi(spi_interface)
+        .with_context(|| format!("Unknown console SPI interface: {}.", spi_interface))
+        .status(OtLibStatus::InvalidArgument)?;
+    let device_console_tx_ready_pin = transport
+        .gpio_pin(tx_ready_pin)
+        .with_context(|| format!("Unknown console TX-ready pin: {}.", tx_ready_pin))
+        .status(OtLibStatus::InvalidArgument)?;
+    Ok((spi, device_console_tx_ready_pin))
+}
+
+/// The SPI console selected by the FFI arguments `spi_interface` and `tx_ready_pin`, whose SPI
+/// runs with `spi_params`, and whose TX-ready pin is an input with `pull_mode`, and with
+/// `active_low` signals ready at a low level.
+///
+/// An empty `spi_interface` or `tx_ready_pin` selects the default, as do the defaults of
+/// `spi_params(...)` for the SPI clock speed and mode.
+struct SpiConsoleArgs {
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+    spi_params: SpiParams,
+    pull_mode: OtLibPullMode,
+    active_low: bool,
+}
+
+/// SPI console resources: the SPI, the TX-ready pin, and the SPI settings to restore once done.
+type SpiConsoleHandles = (Rc<dyn Target>, Rc<dyn GpioPin>, SpiSettings);
+
+/// Same as `lookup_spi_console(...)`, and configures the SPI and the TX-ready pin as described at
+/// `SpiConsoleArgs`. The SPI keeps its clock speed and mode until the returned `SpiSettings` is
+/// dropped.
+///
+/// With `active_low` the returned pin reads as ready at a low level, so that the handshake of the
+/// `SpiConsoleDevice` works on boards that invert the signal.
+fn spi_console_handles(
+    transport: &TransportWrapper,
+    spi_args: &SpiConsoleArgs,
+) -> Result<SpiConsoleHandles> {
+    let (spi, device_console_tx_ready_pin) =
+        lookup_spi_console(transport, spi_args.spi_interface, spi_args.tx_ready_pin)?;
+    let spi_settings = configure_spi(&spi, &spi_args.spi_params)?;
+    device_console_tx_ready_pin
+        .set_mode(PinMode::Input)
+        .context("Unable to set GPIO pin mode.")
+        .status(OtLibStatus::TransportError)?;
+    device_console_tx_ready_pin
+        .set_pull_mode(spi_args.pull_mode.into())
+        .context("Unable to set GPIO pull mode.")
+        .status(OtLibStatus::TransportError)?;
+    if spi_args.active_low {
+        return Ok((
+            spi,
+            Rc::new(ActiveLowPin(device_console_tx_ready_pin)),
+            spi_settings,
+        ));
+    }
+    Ok((spi, device_console_tx_ready_pin, spi_settings))
+}
+
+/// Console used to exchange messages with the DUT.
+///
+/// NOTE: must match the definition of `DutConsoleKind` in src/ate/test_programs/dut_lib/dut_lib.h
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtLibConsoleKind {
+    /// The SPI console, using the `spi_interface`, `tx_ready_pin`, `spi_speed_hz`, `spi_mode`,
+    /// `pull_mode` and `active_low` arguments as described at `OtLibConsoleOpen(...)`.
+    Spi = 0,
+    /// The UART console. The `spi_interface`, `tx_ready_pin`, `spi_speed_hz`, `spi_mode`,
+    /// `pull_mode` and `active_low` arguments are ignored.
+    Uart = 1,
+}
+
+/// Name of the UART used when `OtLibConsoleKind::Uart` is selected.
+const UART_CONSOLE: &str = "console";
+
+/// Looks up the console selected by `kind`, as `with_console!` would, without using it.
+///
+/// Used in dry-run mode, see `OtLibSetDryRun(...)`.
+fn check_console(
+    transport: &TransportWrapper,
+    kind: OtLibConsoleKind,
+    spi_args: &SpiConsoleArgs,
+) -> Result<()> {
+    match kind {
+        OtLibConsoleKind::Spi => {
+            lookup_spi_console(transport, spi_args.spi_interface, spi_args.tx_ready_pin)?;
+        }
+        OtLibConsoleKind::Uart => {
+            transport
+                .uart(UART_CONSOLE)
+                .context("Unable to instantiate the UART console.")
+                .status(OtLibStatus::TransportError)?;
+        }
+    }
+    Ok(())
+}
+
+/// Evaluates `$body` with `$device` bound to the console device selected by `$kind` on the
+/// transport held by `$guard`, the SPI console being the one described by `$spi_args`. Its reads
+/// fail with `OtLibStatus::Cancelled` once a cancellation is requested with
+/// `OtLibRequestCancel(...)`.
+///
+/// This is a macro rather than a function because the SPI and UART console devices are different
+/// types, and the console helpers are generic over the device.
+macro_rules! with_console {
+    (
+        $guard:expr, $kind:expr, $spi_args:expr,
+        |$device:ident| $body:expr
+    ) => {
+        match $kind {
+            OtLibConsoleKind::Spi => {
+                let (spi, device_console_tx_ready_pin, _spi_settings) =
+                    spi_console_handles(&$guard, $spi_args)?;
+                let spi_console = SpiConsoleDevice::new(
+                    &*spi,
+                    Some(&device_console_tx_ready_pin),
+                    /*ignore_frame_num=*/ true,
+                )
+                .status(OtLibStatus::ConsoleError)?;
+                let $device = &CancellableConsole::new(&spi_console, $guard.cancel_token());
+                $body
+            }
+            OtLibConsoleKind::Uart => {
+                let uart = $guard
+                    .uart(UART_CONSOLE)
+                    .context("Unable to instantiate the UART console.")
+                    .status(OtLibStatus::TransportError)?;
+                let $device = &CancellableConsole::new(&*uart, $guard.cancel_token());
+                $body
+            }
+        }
+    };
+}
+
+/// CRC-32 variant the device uses to protect its console responses.
+///
+/// NOTE: must match the definition of `DutCrcKind` in src/ate/test_programs/dut_lib/dut_lib.h
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtLibCrcKind {
+    /// CRC-32/ISO-HDLC, used by the current personalization firmware.
+    IsoHdlc = 0,
+    /// CRC-32/BZIP2.
+    Bzip2 = 1,
+    /// CRC-32/MPEG-2.
+    Mpeg2 = 2,
+}
+
+impl OtLibCrcKind {
+    fn algorithm(self) -> &'static Algorithm<u32> {
+        match self {
+            OtLibCrcKind::IsoHdlc => &CRC_32_ISO_HDLC,
+            OtLibCrcKind::Bzip2 => &CRC_32_BZIP2,
+            OtLibCrcKind::Mpeg2 => &CRC_32_MPEG_2,
+        }
+    }
+}
+
+/// Checks that `crc_str`, the decimal CRC sent by the device, matches the `crc_kind` CRC of the
+/// raw `payload` bytes. Text payloads are checked over their UTF-8 bytes, so both response kinds
+/// are checked over exactly what the device sent.
+///
+/// A mismatch or an unparsable `crc_str` is reported as `OtLibStatus::CrcMismatch`.
+fn check_console_crc(payload: &[u8], crc_str: &str, crc_kind: OtLibCrcKind) -> Result<()> {
+    let crc = crc_str.parse::<u32>().status(OtLibStatus::CrcMismatch)?;
+    let actual_crc = Crc::<u32>::new(crc_kind.algorithm()).checksum(payload);
+    if crc != actual_crc {
+        bail_status!(
+            OtLibStatus::CrcMismatch,
+            "CRC didn't match received json body."
+        );
+    }
+    Ok(())
+}
+
+/// Returns the number of `DutSpiFrame`s of `frame_size` bytes needed to hold a JSON string of
+/// `json_len` bytes.
+fn frames_required(json_len: usize, frame_size: usize) -> usize {
+    json_len.div_ceil(frame_size)
+}
+
+/// Returns the per-frame payload size for the `frame_size` argument, which must match the
+/// `kDutTxMaxSpiFrameSizeInBytes` of the device build. A `frame_size` of 0 selects
+/// `CONSOLE_BUFFER_MAX_SIZE`.
+///
+/// A `frame_size` larger than the payload capacity of a `DutSpiFrame` is reported as
+/// `OtLibStatus::InvalidArgument`.
+fn console_frame_size(frame_size: usize) -> Result<usize> {
+    match frame_size {
+        0 => Ok(CONSOLE_BUFFER_MAX_SIZE),
+        x if x > CONSOLE_BUFFER_MAX_SIZE => bail_status!(
+            OtLibStatus::InvalidArgument,
+            "Frame size {} exceeds the {} byte payload of a frame.",
+            x,
+            CONSOLE_BUFFER_MAX_SIZE
+        ),
+        x => Ok(x),
+    }
+}
+
+/// Waits for `msg` to be received over the console `device`.
+fn console_wait_for_rx<T>(device: &T, msg: &str, timeout_ms: u64) -> Result<()>
+where
+    T: ConsoleDevice + ?Sized,
+{
+    wait_for(device, msg, Duration::from_millis(timeout_ms))?;
+    Ok(())
+}
+
+/// Checks that `msg` compiles to a regex with a capture group `group`.
+fn check_capture_group(msg: &str, group: usize) -> Result<()> {
+    let regex = Regex::new(msg)
+        .with_context(|| format!("Invalid message regex {:?}.", msg))
+        .status(OtLibStatus::InvalidArgument)?;
+    if group >= regex.captures_len() {
+        bail_status!(
+            OtLibStatus::InvalidArgument,
+            "Message regex {:?} has no capture group {}.",
+            msg,
+            group
+        );
+    }
+    Ok(())
+}
+
+/// Waits for `msg` to be received over the console `device`, and returns the text captured by
+/// its capture group `group`.
+fn console_wait_for_capture<T>(
+    device: &T,
+    msg: &str,
+    group: usize,
+    timeout_ms: u64,
+) -> Result<String>
+where
+    T: ConsoleDevice + ?Sized,
+{
+    let mut captures = wait_for(device, msg, Duration::from_millis(timeout_ms))?;
+    if group >= captures.len() {
+        bail_status!(
+            OtLibStatus::ConsoleError,
+            "Console message has no capture group {}.",
+            group
+        );
+    }
+    Ok(captures.swap_remove(group))
+}
+
+/// Copies `capture` into the caller's buffer `buf` of `buf_len` bytes, NUL terminated, and sets
+/// `out_len` to its length.
+///
+/// A capture that does not fit is truncated and reported as `OtLibStatus::BufferTooSmall`, with
+/// `out_len` set to the full length.
+fn copy_capture(
+    capture: &str,
+    buf: *mut c_char,
+    buf_len: usize,
+    out_len: &mut usize,
+) -> Result<()> {
+    *out_len = copy_to_cstr_buf(capture, buf, buf_len);
+    if capture.len() >= buf_len {
+        bail_status!(
+            OtLibStatus::BufferTooSmall,
+            "Capture of {} bytes does not fit into a buffer of {} bytes.",
+            capture.len(),
+            buf_len
+        );
+    }
+    Ok(())
+}
+
+/// Capture group of the `uds_cert_regex` of `OtLibExtractUdsCert(...)` holding the certificate.
+const UDS_CERT_GROUP: usize = 1;
+
+/// Decodes the hex or base64 `encoded` certificate dumped by the device into DER.
+///
+/// A DER certificate starts with a SEQUENCE tag, `30` in hex but `M` in base64, so an even number
+/// of hex digits is decoded as hex and anything else as base64. A certificate that does not decode
+/// to a DER SEQUENCE is reported as `OtLibStatus::ConsoleError`.
+fn decode_cert(encoded: &str) -> Result<Vec<u8>> {
+    let der = if encoded.len().is_multiple_of(2) && encoded.bytes().all(|b| b.is_ascii_hexdigit()) {
+        (0..encoded.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&encoded[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .context("Invalid hex certificate.")
+            .status(OtLibStatus::ConsoleError)?
+    } else {
+        BASE64
+            .decode(encoded)
+            .context("Certificate is neither hex nor base64.")
+            .status(OtLibStatus::ConsoleError)?
+    };
+    if der.first() != Some(&0x30) {
+        bail_status!(
+            OtLibStatus::ConsoleError,
+            "Decoded certificate is not a DER SEQUENCE."
+        );
+    }
+    Ok(der)
+}
+
+/// Copies `bytes` into the caller's buffer `buf` of `buf_len` bytes and sets `out_len` to their
+/// length.
+///
+/// Bytes that do not fit are not copied at all and reported as `OtLibStatus::BufferTooSmall`, with
+/// `out_len` set to the length that would have been needed.
+fn copy_bytes(bytes: &[u8], buf: *mut u8, buf_len: usize, out_len: &mut usize) -> Result<()> {
+    *out_len = bytes.len();
+    if bytes.len() > buf_len {
+        bail_status!(
+            OtLibStatus::BufferTooSmall,
+            "{} bytes do not fit into a buffer of {} bytes.",
+            bytes.len(),
+            buf_len
+        );
+    }
+    // SAFETY: `buf` was checked to be non-null and must point to `buf_len` writable bytes.
+    let buf = unsafe { slice::from_raw_parts_mut(buf, buf_len) };
+    buf[..bytes.len()].copy_from_slice(bytes);
+    Ok(())
+}
+
+/// Regex matching any of several patterns, see `any_of_regex(...)`.
+struct AnyOfRegex {
+    /// Alternation of all patterns, each wrapped in a capture group.
+    regex: String,
+    /// Index of the capture group wrapping each pattern.
+    groups: Vec<usize>,
+}
+
+/// Combines `patterns` into a single regex that matches any of them.
+///
+/// A pattern that does not compile is reported as `OtLibStatus::InvalidArgument`, and so is one
+/// that matches empty text, since `matched_pattern(...)` could not tell its empty match apart
+/// from a group that took no part in the match.
+fn any_of_regex(patterns: &[String]) -> Result<AnyOfRegex> {
+    check_not_empty(patterns.len(), "patterns")?;
+    let mut alternatives = Vec::with_capacity(patterns.len());
+    let mut groups = Vec::with_capacity(patterns.len());
+    let mut next_group = 1;
+    for (i, pattern) in patterns.iter().enumerate() {
+        let regex = Regex::new(pattern)
+            .with_context(|| format!("Invalid pattern {}: {:?}.", i, pattern))
+            .status(OtLibStatus::InvalidArgument)?;
+        if regex.is_match("") {
+            bail_status!(
+                OtLibStatus::InvalidArgument,
+                "Pattern {} matches empty text: {:?}.",
+                i,
+                pattern
+            );
+        }
+        alternatives.push(format!("({})", pattern));
+        groups.push(next_group);
+        // The wrapping group, followed by the groups of the pattern itself.
+        next_group += regex.captures_len();
+    }
+    Ok(AnyOfRegex {
+        regex: alternatives.join("|"),
+        groups,
+    })
+}
+
+/// Returns the index of the pattern whose group in `captures` took part in the match.
+fn matched_pattern(any_of: &AnyOfRegex, captures: &[String]) -> Option<usize> {
+    any_of
+        .groups
+        .iter()
+        .position(|&group| captures.get(group).is_some_and(|c| !c.is_empty()))
+}
+
+/// Waits for a message matching any of the patterns of `any_of` to be received over the console
+/// `device`, and returns the index of the pattern that matched.
+fn console_wait_for_any_of<T>(device: &T, any_of: &AnyOfRegex, timeout_ms: u64) -> Result<usize>
+where
+    T: ConsoleDevice + ?Sized,
+{
+    let captures = wait_for(device, &any_of.regex, Duration::from_millis(timeout_ms))?;
+    matched_pattern(any_of, &captures)
+        .context("Console message matched none of the patterns.")
+        .status(OtLibStatus::ConsoleError)
+}
+
+/// Copies `payload` into `spi_frames`, `frame_size` bytes per frame, and sets `num_frames` to the
+/// number of frames used.
+///
+/// If `num_frames` is too small, it is set to the number of frames required and
+/// `OtLibStatus::BufferTooSmall` is returned without copying anything.
+fn copy_to_frames(
+    payload: &[u8],
+    frame_size: usize,
+    spi_frames: &mut [DutSpiFrame],
+    num_frames: &mut usize,
+) -> Result<()> {
+    let num_frames_required = frames_required(payload.len(), frame_size);
+    if *num_frames < num_frames_required {
+        let num_frames_provided = *num_frames;
+        // Report the required number of frames so the caller can retry with a large enough buffer.
+        *num_frames = num_frames_required;
+        bail_status!(
+            OtLibStatus::BufferTooSmall,
+            "Not enough frames ({} frames of size {} bytes) allocated to receive payload of length {}",
+            num_frames_provided,
+            frame_size,
+            payload.len()
+        );
+    }
+    for (spi_frame, chunk) in spi_frames.iter_mut().zip(payload.chunks(frame_size)) {
+        spi_frame.payload[..chunk.len()].copy_from_slice(chunk);
+        spi_frame.size = chunk.len();
+    }
+    *num_frames = num_frames_required;
+    Ok(())
+}
+
+/// Response received over the console: how the exchange ended, the payload, and its CRC string.
+type Response = (ExitStatus, Vec<u8>, String);
+
+/// Default success and failure response envelopes.
+const DEFAULT_SUCCESS_REGEX: &str = r"RESP_OK:(.*) CRC:([0-9]+)\n";
+const DEFAULT_FAILURE_REGEX: &str = r"RESP_ERR:(.*) CRC:([0-9]+)\n";
+
+/// Default envelopes of binary responses, whose payload may contain any byte, including newlines.
+const DEFAULT_BINARY_SUCCESS_REGEX: &str = r"(?s-u)RESP_OK:(.*?) CRC:([0-9]+)\n";
+const DEFAULT_BINARY_FAILURE_REGEX: &str = r"(?s-u)RESP_ERR:(.*?) CRC:([0-9]+)\n";
+
+/// Regexes matching the success and failure response envelopes.
+///
+/// Each regex must have exactly two capture groups: the payload first, then its CRC as a decimal
+/// number.
+enum ResponseRegexes {
+    /// Matched against the console output interpreted as UTF-8.
+    Text { success: Regex, failure: Regex },
+    /// Matched against the raw console output bytes.
+    Binary {
+        success: BytesRegex,
+        failure: BytesRegex,
+    },
+}
+
+/// Compiles the response envelope regexes, using the defaults for empty `success` or `failure`.
+///
+/// A regex that does not compile or does not have the two expected capture groups is reported as
+/// `OtLibStatus::InvalidArgument`.
+fn response_regexes(success: &str, failure: &str, binary: bool) -> Result<ResponseRegexes> {
+    let (default_success, default_failure) = if binary {
+        (DEFAULT_BINARY_SUCCESS_REGEX, DEFAULT_BINARY_FAILURE_REGEX)
+    } else {
+        (DEFAULT_SUCCESS_REGEX, DEFAULT_FAILURE_REGEX)
+    };
+    let success = if success.is_empty() {
+        default_success
+    } else {
+        success
+    };
+    let failure = if failure.is_empty() {
+        default_failure
+    } else {
+        failure
+    };
+    fn check_groups(name: &str, regex: &str, captures_len: usize) -> Result<()> {
+        // `captures_len` includes the implicit group of the whole match.
+        if captures_len != 3 {
+            bail_status!(
+                OtLibStatus::InvalidArgument,
+                "The {} regex ({}) must have 2 capture groups (payload, crc), found {}.",
+                name,
+                regex,
+                captures_len - 1
+            );
+        }
+        Ok(())
+    }
+    if binary {
+        let compile = |name, regex| -> Result<BytesRegex> {
+            let compiled = BytesRegex::new(regex)
+                .with_context(|| format!("Invalid {} regex: {}.", name, regex))
+                .status(OtLibStatus::InvalidArgument)?;
+            check_groups(name, regex, compiled.captures_len())?;
+            Ok(compiled)
+        };
+        Ok(ResponseRegexes::Binary {
+            success: compile("success", success)?,
+            failure: compile("failure", failure)?,
+        })
+    } else {
+        let compile = |name, regex| -> Result<Regex> {
+            let compiled = Regex::new(regex)
+                .with_context(|| format!("Invalid {} regex: {}.", name, regex))
+                .status(OtLibStatus::InvalidArgument)?;
+            check_groups(name, regex, compiled.captures_len())?;
+            Ok(compiled)
+        };
+        Ok(ResponseRegexes::Text {
+            success: compile("success", success)?,
+            failure: compile("failure", failure)?,
+        })
+    }
+}
+
+/// How a `UartConsole` formats the console output it echoes to stdout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ConsoleFormat {
+    /// Prefix each line with a timestamp.
+    timestamp: bool,
+    /// Treat the start of the output as the start of a line, so that with `timestamp` its first
+    /// line is timestamped too.
+    newline: bool,
+}
+
+impl ConsoleFormat {
+    /// The format used unless the caller selects another one.
+    const DEFAULT: ConsoleFormat = ConsoleFormat {
+        timestamp: true,
+        newline: true,
+    };
+}
+
+/// Where the console output received along with a response is echoed to.
+enum ConsoleEcho<'w> {
+    /// Nowhere.
+    Quiet,
+    /// To stdout, or to the log for binary responses.
+    Stdout,
+    /// To a writer of the caller, e.g. the capture file of `OtLibConsoleRxCapture(...)`. Binary
+    /// responses are written as is.
+    Capture(&'w mut dyn Write),
+}
+
+impl ConsoleEcho<'_> {
+    /// Echoes to stdout unless `quiet`.
+    fn new(quiet: bool) -> Self {
+        if quiet {
+            ConsoleEcho::Quiet
+        } else {
+            ConsoleEcho::Stdout
+        }
+    }
+}
+
+/// Receives a RESP_OK or RESP_ERR response over the console `device` with a `UartConsole`, which
+/// interprets the console output as UTF-8.
+fn receive_text<T>(
+    device: &T,
+    success: &Regex,
+    failure: &Regex,
+    echo: ConsoleEcho<'_>,
+    format: ConsoleFormat,
+    timeout_ms: u64,
+) -> Result<Response>
+where
+    T: ConsoleDevice + ?Sized,
+{
+    // Instantiate a "UartConsole", which is really just a console buffer.
+    let mut console = UartConsole {
+        timeout: Some(Duration::from_millis(timeout_ms)),
+        timestamp: format.timestamp,
+        newline: format.newline,
+        exit_success: Some(success.clone()),
+        exit_failure: Some(failure.clone()),
+        ..Default::default()
+    };
+
+    // Select where the console output goes, if anywhere.
+    let mut stdout = std::io::stdout();
+    let out: Option<&mut dyn Write> = match echo {
+        ConsoleEcho::Quiet => None,
+        ConsoleEcho::Stdout => Some(&mut stdout),
+        ConsoleEcho::Capture(w) => Some(w),
+    };
+
+    // Receive the payload from DUT.
+    let result = console
+        .interact(device, None, out)
+        .status(OtLibStatus::ConsoleError)?;
+    let (name, cap) = match result {
+        ExitStatus::ExitSuccess => ("RESP_OK", console.captures(result)),
+        ExitStatus::ExitFailure => ("RESP_ERR", console.captures(result)),
+        _ => return Ok((result, Vec::new(), String::new())),
+    };
+    let cap = cap.with_context(|| format!("{} capture", name))?;
+    let payload = cap.get(1).with_context(|| format!("{} group", name))?;
+    let crc_str = cap.get(2).context("CRC group")?;
+    Ok((
+        result,
+        payload.as_str().as_bytes().to_vec(),
+        crc_str.as_str().to_string(),
+    ))
+}
+
+/// Matches the console output `buffer` against the `success` and `failure` envelopes.
+///
+/// Returns the response and the length of `buffer` up to the end of the matched envelope, or
+/// none if `buffer` does not hold a complete response yet.
+fn match_binary(
+    buffer: &[u8],
+    success: &BytesRegex,
+    failure: &BytesRegex,
+) -> Result<Option<(Response, usize)>> {
+    let responses = [
+        (ExitStatus::ExitSuccess, success),
+        (ExitStatus::ExitFailure, failure),
+    ];
+    for (result, regex) in responses.iter() {
+        if let Some(cap) = regex.captures(buffer) {
+            let end = cap.get(0).context("response match")?.end();
+            let payload = cap.get(1).context("payload group")?.as_bytes();
+            // The CRC group only matches ASCII digits.
+            let crc_str = std::str::from_utf8(cap.get(2).context("CRC group")?.as_bytes())?;
+            return Ok(Some((
+                (*result, payload.to_vec(), crc_str.to_string()),
+                end,
+            )));
+        }
+    }
+    Ok(None)
+}
+
+/// Same as `match_binary(...)`, for the envelopes of text responses.
+///
+/// Only the valid UTF-8 prefix of `buffer` is matched, as it may end with part of a character.
+fn match_text(
+    buffer: &[u8],
+    success: &Regex,
+    failure: &Regex,
+) -> Result<Option<(Response, usize)>> {
+    let text = match std::str::from_utf8(buffer) {
+        Ok(text) => text,
+        Err(e) => std::str::from_utf8(&buffer[..e.valid_up_to()])?,
+    };
+    let responses = [
+        (ExitStatus::ExitSuccess, success),
+        (ExitStatus::ExitFailure, failure),
+    ];
+    for (result, regex) in responses.iter() {
+        if let Some(cap) = regex.captures(text) {
+            let end = cap.get(0).context("response match")?.end();
+            let payload = cap.get(1).context("payload group")?.as_str();
+            let crc_str = cap.get(2).context("CRC group")?.as_str();
+            return Ok(Some((
+                (*result, payload.as_bytes().to_vec(), crc_str.to_string()),
+                end,
+            )));
+        }
+    }
+    Ok(None)
+}
+
+/// Logs the lines of the console output `buffer` completed by its bytes from `from` on.
+///
+/// Console output is read in pieces that may hold no or part of a line, so it is logged one whole
+/// line at a time instead of one read at a time. Blank lines are not logged.
+fn log_new_lines(buffer: &[u8], from: usize) {
+    let start = buffer[..from]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    let Some(end) = buffer.iter().rposition(|&b| b == b'\n') else {
+        return;
+    };
+    if end < start {
+        return;
+    }
+    for line in buffer[start..end].split(|&b| b == b'\n') {
+        log_line(line);
+    }
+}
+
+/// Logs the unterminated line `buffer` ends with at `end`, once no more output is read for it.
+///
+/// Nothing is logged if the line was already logged by `log_new_lines(...)`.
+fn log_partial_line(buffer: &[u8], end: usize) {
+    if buffer[end..].contains(&b'\n') {
+        return;
+    }
+    let start = buffer[..end]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    log_line(&buffer[start..end]);
+}
+
+/// Logs a single line of console output, unless it is blank.
+fn log_line(line: &[u8]) {
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim_end();
+    if !line.is_empty() {
+        log_info!("{}", line);
+    }
+}
+
+/// Receives a RESP_OK or RESP_ERR response over the console `device` as raw bytes, for binary
+/// payloads that are not valid UTF-8. The payload may contain newlines.
+fn receive_binary<T>(
+    device: &T,
+    success: &BytesRegex,
+    failure: &BytesRegex,
+    mut echo: ConsoleEcho<'_>,
+    timeout_ms: u64,
+) -> Result<Response>
+where
+    T: ConsoleDevice + ?Sized,
+{
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 256];
+    let response = loop {
+        if let Some((response, _)) = match_binary(&buffer, success, failure)? {
+            break response;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            break (ExitStatus::Timeout, Vec::new(), String::new());
+        }
+        let len = device
+            .console_read(&mut chunk, deadline - now)
+            .status(OtLibStatus::ConsoleError)?;
+        if len == 0 {
+            continue;
+        }
+        if let ConsoleEcho::Capture(ref mut w) = echo {
+            w.write_all(&chunk[..len])
+                .context("Could not write the console capture.")
+                .status(OtLibStatus::ConsoleError)?;
+        }
+        buffer.extend_from_slice(&chunk[..len]);
+        if let ConsoleEcho::Stdout = echo {
+            log_new_lines(&buffer, buffer.len() - len);
+        }
+    };
+    if let ConsoleEcho::Stdout = echo {
+        log_partial_line(&buffer, buffer.len());
+    }
+    Ok(response)
+}
+
+/// Waits for `sync_str` (unless empty), then receives a response payload over the console
+/// `device` into `spi_frames`, setting `num_frames` to the number of frames used.
+///
+/// With `ResponseRegexes::Binary` the payload is handled as opaque bytes rather than UTF-8 text,
+/// and the CRC is computed over the raw bytes. A failure response is copied the same way and
+/// reported as `OtLibStatus::DeviceError`.
+#[allow(clippy::too_many_arguments)]
+fn console_rx<T>(
+    device: &T,
+    sync_str: &str,
+    spi_frames: &mut [DutSpiFrame],
+    num_frames: &mut usize,
+    frame_size: usize,
+    skip_crc_check: bool,
+    crc_kind: OtLibCrcKind,
+    regexes: &ResponseRegexes,
+    echo: ConsoleEcho<'_>,
+    format: ConsoleFormat,
+    timeout_ms: u64,
+) -> Result<()>
+where
+    T: ConsoleDevice + ?Sized,
+{
+    let response = receive_response(device, sync_str, regexes, echo, format, timeout_ms)?;
+    complete_rx(response, skip_crc_check, crc_kind, |payload| {
+        copy_to_frames(payload, frame_size, spi_frames, num_frames)
+    })
+}
+
+/// Waits for `sync_str` (unless empty), then receives a response over the console `device`.
+///
+/// `format` applies to text responses only; binary responses are echoed to the log as is.
+fn receive_response<T>(
+    device: &T,
+    sync_str: &str,
+    regexes: &ResponseRegexes,
+    echo: ConsoleEcho<'_>,
+    format: ConsoleFormat,
+    timeout_ms: u64,
+) -> Result<Response>
+where
+    T: ConsoleDevice + ?Sized,
+{
+    // Wait for the sync message over the console.
+    if !sync_str.is_empty() {
+        wait_for(device, sync_str, Duration::from_millis(timeout_ms))
+            .with_context(|| format!("Device sync ({}) message missed.", sync_str))?;
+    }
+
+    match regexes {
+        ResponseRegexes::Text { success, failure } => {
+            receive_text(device, success, failure, echo, format, timeout_ms)
+        }
+        ResponseRegexes::Binary { success, failure } => {
+            receive_binary(device, success, failure, echo, timeout_ms)
+        }
+    }
+}
+
+/// Reports that a console wait is still in progress, with the time elapsed since it started.
+pub type OtLibHeartbeatCallback = extern "C" fn(elapsed_ms: u64);
+
+/// Interval at which `HeartbeatConsole` calls its callback.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Console device that calls `on_heartbeat` about every `interval` while reads of `device` block,
+/// so that a caller waiting on a slow device can show that it is still alive.
+///
+/// Reads are cut into slices of at most `interval`; those that time out return no bytes, which the
+/// console helpers already treat as "nothing received yet". Without `on_heartbeat` the reads are
+/// passed through unchanged.
+struct HeartbeatConsole<'d, T: ?Sized> {
+    device: &'d T,
+    on_heartbeat: Option<OtLibHeartbeatCallback>,
+    interval: Duration,
+    start: Instant,
+    next_beat: Cell<Instant>,
+}
+
+impl<'d, T: ConsoleDevice + ?Sized> HeartbeatConsole<'d, T> {
+    fn new(device: &'d T, on_heartbeat: Option<OtLibHeartbeatCallback>) -> Self {
+        Self::with_interval(device, on_heartbeat, HEARTBEAT_INTERVAL)
+    }
+
+    fn with_interval(
+        device: &'d T,
+        on_heartbeat: Option<OtLibHeartbeatCallback>,
+        interval: Duration,
+    ) -> Self {
+        let start = Instant::now();
+        HeartbeatConsole {
+            device,
+            on_heartbeat,
+            interval,
+            start,
+            next_beat: Cell::new(start + interval),
+        }
+    }
+}
+
+impl<T: ConsoleDevice + ?Sized> ConsoleDevice for HeartbeatConsole<'_, T> {
+    fn console_read(&self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        let Some(on_heartbeat) = self.on_heartbeat else {
+            return self.device.console_read(buf, timeout);
+        };
+        let now = Instant::now();
+        if now >= self.next_beat.get() {
+            on_heartbeat(u64::try_from(self.start.elapsed().as_millis()).unwrap_or(u64::MAX));
+            self.next_beat.set(now + self.interval);
+        }
+        let slice = self.next_beat.get().saturating_duration_since(now);
+        self.device.console_read(buf, timeout.min(slice))
+    }
+
+    fn console_write(&self, buf: &[u8]) -> Result<()> {
+        self.device.console_write(buf)
+    }
+}
+
+/// Reports the chunks of a response payload received by `OtLibConsoleRxStream(...)`.
+pub type OtLibFrameCallback = extern "C" fn(data: *const u8, len: usize);
+
+/// Same as `console_rx(...)`, but hands the payload to `on_frame` in chunks of at most
+/// `frame_size` bytes instead of copying it into frames.
+#[allow(clippy::too_many_arguments)]
+fn console_rx_stream<T>(
+    device: &T,
+    sync_str: &str,
+    on_frame: OtLibFrameCallback,
+    frame_size: usize,
+    skip_crc_check: bool,
+    crc_kind: OtLibCrcKind,
+    regexes: &ResponseRegexes,
+    quiet: bool,
+    timeout_ms: u64,
+) -> Result<()>
+where
+    T: ConsoleDevice + ?Sized,
+{
+    let response = receive_response(
+        device,
+        sync_str,
+        regexes,
+        ConsoleEcho::new(quiet),
+        ConsoleFormat::DEFAULT,
+        timeout_ms,
+    )?;
+    complete_rx(response, skip_crc_check, crc_kind, |payload| {
+        for chunk in payload.chunks(frame_size) {
+            on_frame(chunk.as_ptr(), chunk.len());
+        }
+        Ok(())
+    })
+}
+
+/// Checks the CRC of the received `response` and hands its payload to `deliver`, see
+/// `console_rx(...)`.
+fn complete_rx(
+    (result, payload, crc_str): Response,
+    skip_crc_check: bool,
+    crc_kind: OtLibCrcKind,
+    deliver: impl FnOnce(&[u8]) -> Result<()>,
+) -> Result<()> {
+    match result {
+        ExitStatus::ExitSuccess => {
+            if !skip_crc_check {
+                check_console_crc(&payload, &crc_str, crc_kind).context("CRC check failed.")?;
+            }
+            deliver(&payload)
+        }
+        ExitStatus::ExitFailure => {
+            check_console_crc(&payload, &crc_str, crc_kind)?;
+            deliver(&payload)?;
+            bail_status!(
+                OtLibStatus::DeviceError,
+                "{}",
+                String::from_utf8_lossy(&payload)
+            )
+        }
+        ExitStatus::Timeout => bail_status!(OtLibStatus::ConsoleTimeout, "Timed Out"),
+        _ => bail_status!(OtLibStatus::ConsoleError, "Impossible result: {:?}", result),
+    }
+}
+
+/// Receive time slice used when the caller passes a `slice_ms` of zero.
+const DEFAULT_RX_POLL_SLICE: Duration = Duration::from_millis(50);
+
+/// Reads the console `device` for at most `slice`, appending the output to `pending`, and
+/// completes the response once `pending` holds one, as `console_rx(...)` does.
+///
+/// Fails with `OtLibStatus::WouldBlock` if the response is not complete yet; `pending` then keeps
+/// the partial response for the next call. Once complete, the response is removed from `pending`.
+/// Unless `quiet`, the output is logged one whole line at a time across calls.
+#[allow(clippy::too_many_arguments)]
+fn console_rx_poll<T>(
+    device: &T,
+    pending: &mut Vec<u8>,
+    spi_frames: &mut [DutSpiFrame],
+    num_frames: &mut usize,
+    frame_size: usize,
+    skip_crc_check: bool,
+    crc_kind: OtLibCrcKind,
+    regexes: &ResponseRegexes,
+    quiet: bool,
+    slice: Duration,
+) -> Result<()>
+where
+    T: ConsoleDevice + ?Sized,
+{
+    let match_response = |pending: &[u8]| match regexes {
+        ResponseRegexes::Text { success, failure } => match_text(pending, success, failure),
+        ResponseRegexes::Binary { success, failure } => match_binary(pending, success, failure),
+    };
+    let deadline = Instant::now() + slice;
+    let mut chunk = [0u8; 256];
+    let mut matched = match_response(pending)?;
+    // Read at least once, even with an empty `slice`.
+    while matched.is_none() {
+        let len = device
+            .console_read(
+                &mut chunk,
+                deadline.saturating_duration_since(Instant::now()),
+            )
+            .status(OtLibStatus::ConsoleError)?;
+        if len > 0 {
+            pending.extend_from_slice(&chunk[..len]);
+            if !quiet {
+                log_new_lines(pending, pending.len() - len);
+            }
+            matched = match_response(pending)?;
+        }
+        if matched.is_none() && Instant::now() >= deadline {
+            bail_status!(
+                OtLibStatus::WouldBlock,
+                "No complete response received yet."
+            );
+        }
+    }
+    let (response, len) = matched.context("response")?;
+    if !quiet {
+        log_partial_line(pending, len);
+    }
+    pending.drain(..len);
+    complete_rx(response, skip_crc_check, crc_kind, |payload| {
+        copy_to_frames(payload, frame_size, spi_frames, num_frames)
+    })
+}
+
+/// Waits for `sync_str` (unless empty), then sends `spi_frame` over the console `device`.
+fn console_tx<T>(device: &T, sync_str: &str, spi_frame: &[u8], timeout_ms: u64) -> Result<()>
+where
+    T: ConsoleDevice + ?Sized,
+{
+    // Wait for the sync message over the console.
+    if !sync_str.is_empty() {
+        wait_for(device, sync_str, Duration::from_millis(timeout_ms))
+            .with_context(|| format!("Device sync ({}) message missed.", sync_str))?;
+    }
+
+    // Send data to the DUT over the console.
+    device
+        .console_write(spi_frame)
+        .context("Unable to write to console.")
+        .status(OtLibStatus::ConsoleError)
+}
+
+/// Format byte of a compressed TX payload holding a raw deflate stream (RFC 1951).
+const COMPRESSED_FORMAT_DEFLATE: u8 = 1;
+
+/// Size of the header that precedes a compressed TX payload.
+const COMPRESSED_HEADER_SIZE: usize = 9;
+
+/// Deflates `payload` for a device that inflates its console input, and prefixes the header the
+/// device expects:
+///
+/// | Offset | Size | Field                                           |
+/// |--------|------|-------------------------------------------------|
+/// | 0      | 1    | Format, `COMPRESSED_FORMAT_DEFLATE`             |
+/// | 1      | 4    | Length of the uncompressed payload, little endian |
+/// | 5      | 4    | Length of the deflate stream, little endian     |
+/// | 9      |      | Raw deflate stream (RFC 1951), no zlib wrapper  |
+///
+/// A payload whose length does not fit into the header is reported as
+/// `OtLibStatus::InvalidArgument`.
+fn compress_payload(payload: &[u8]) -> Result<Vec<u8>> {
+    let Ok(uncompressed_len) = u32::try_from(payload.len()) else {
+        bail_status!(
+            OtLibStatus::InvalidArgument,
+            "Payload of {} bytes is too large to compress.",
+            payload.len()
+        );
+    };
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder
+        .write_all(payload)
+        .context("Unable to compress the payload.")?;
+    let stream = encoder
+        .finish()
+        .context("Unable to compress the payload.")?;
+    let compressed_len = u32::try_from(stream.len())
+        .context("Compressed payload too large.")
+        .status(OtLibStatus::InvalidArgument)?;
+
+    let mut framed = Vec::with_capacity(COMPRESSED_HEADER_SIZE + stream.len());
+    framed.push(COMPRESSED_FORMAT_DEFLATE);
+    framed.extend_from_slice(&uncompressed_len.to_le_bytes());
+    framed.extend_from_slice(&compressed_len.to_le_bytes());
+    framed.extend_from_slice(&stream);
+    Ok(framed)
+}
+
+/// Returns the bytes to send for the `payload` of a TX call, compressed with
+/// `compress_payload(...)` if `compress` is set.
+fn tx_payload(payload: &[u8], compress: bool) -> Result<Cow<'_, [u8]>> {
+    Ok(if compress {
+        Cow::Owned(compress_payload(payload)?)
+    } else {
+        Cow::Borrowed(payload)
+    })
+}
+
+/// Sends `request` over the console `device`, then receives the response into `spi_frames`, as
+/// `console_tx(...)` and `console_rx(...)` would without a sync message, but over the same device
+/// so that no response bytes are lost in between.
+#[allow(clippy::too_many_arguments)]
+fn console_transact<T>(
+    device: &T,
+    request: &[u8],
+    spi_frames: &mut [DutSpiFrame],
+    num_frames: &mut usize,
+    frame_size: usize,
+    skip_crc_check: bool,
+    crc_kind: OtLibCrcKind,
+    regexes: &ResponseRegexes,
+    quiet: bool,
+    timeout_ms: u64,
+) -> Result<()>
+where
+    T: ConsoleDevice + ?Sized,
+{
+    console_tx(device, "", request, timeout_ms).context("Unable to send the request.")?;
+    console_rx(
+        device,
+        "",
+        spi_frames,
+        num_frames,
+        frame_size,
+        skip_crc_check,
+        crc_kind,
+        regexes,
+        ConsoleEcho::new(quiet),
+        ConsoleFormat::DEFAULT,
+        timeout_ms,
+    )
+}
+
+/// Reads and discards the console output of `device` for `drain_ms`, e.g. stale output of a
+/// previous operation that would otherwise be matched by the next one.
+///
+/// Returns the number of bytes discarded.
+fn console_flush<T>(device: &T, drain_ms: u64) -> Result<usize>
+where
+    T: ConsoleDevice + ?Sized,
+{
+    let deadline = Instant::now() + Duration::from_millis(drain_ms);
+    let mut chunk = [0u8; 256];
+    let mut discarded = 0;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(discarded);
+        }
+        discarded += device
+            .console_read(&mut chunk, deadline - now)
+            .context("Unable to read from console.")
+            .status(OtLibStatus::ConsoleError)?;
+    }
+}
+
+/// Rejects empty frames and frames larger than `CONSOLE_BUFFER_MAX_SIZE`.
+fn check_frames(frames: &[DutSpiFrame]) -> Result<()> {
+    for (i, frame) in frames.iter().enumerate() {
+        if frame.size == 0 || frame.size > CONSOLE_BUFFER_MAX_SIZE {
+            bail_status!(
+                OtLibStatus::InvalidArgument,
+                "Frame {} has invalid size {} (must be 1 to {} bytes).",
+                i,
+                frame.size,
+                CONSOLE_BUFFER_MAX_SIZE
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Waits for `sync_str` (unless empty), then sends `frames` in order over the console `device`.
+///
+/// Each frame is a separate `console_write`, so the SPI console paces the frames with its
+/// TX-ready handshake.
+fn console_tx_frames<T>(
+    device: &T,
+    sync_str: &str,
+    frames: &[DutSpiFrame],
+    timeout_ms: u64,
+) -> Result<()>
+where
+    T: ConsoleDevice + ?Sized,
+{
+    check_frames(frames)?;
+
+    // Wait for the sync message over the console.
+    if !sync_str.is_empty() {
+        wait_for(device, sync_str, Duration::from_millis(timeout_ms))
+            .with_context(|| format!("Device sync ({}) message missed.", sync_str))?;
+    }
+
+    // Send the frames to the DUT over the console.
+    for (i, frame) in frames.iter().enumerate() {
+        device
+            .console_write(&frame.payload[..frame.size])
+            .with_context(|| format!("Unable to write frame {} to console.", i))
+            .status(OtLibStatus::ConsoleError)?;
+    }
+    Ok(())
+}
+
+/// Waits for a message matching `c_msg` to be received over the console.
+///
+/// While waiting, `on_heartbeat` is called about every 500 ms with the time elapsed, unless it is
+/// null.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleWaitForRx(
+    transport: *const OtLibTransport,
+    kind: OtLibConsoleKind,
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+    spi_speed_hz: u32,
+    spi_mode: OtLibSpiMode,
+    pull_mode: OtLibPullMode,
+    active_low: bool,
+    c_msg: *mut c_char,
+    timeout_ms: u64,
+    on_heartbeat: Option<OtLibHeartbeatCallback>,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleWaitForRx", || {
+        check_not_null(transport, "transport")?;
+        check_not_null(spi_interface, "spi_interface")?;
+        check_not_null(tx_ready_pin, "tx_ready_pin")?;
+        check_not_null(c_msg, "c_msg")?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let spi_args = SpiConsoleArgs {
+            spi_interface,
+            tx_ready_pin,
+            spi_params: spi_params(spi_speed_hz, spi_mode),
+            pull_mode,
+            active_low,
+        };
+
+        // Unpack msg string.
+        let msg = cstr_to_str(c_msg)?;
+
+        if dry_run() {
+            return check_console(transport, kind, &spi_args);
+        }
+
+        // Get handle to the console.
+        with_console!(guard, kind, &spi_args, |device| {
+            console_wait_for_rx(
+                &HeartbeatConsole::new(device, on_heartbeat),
+                msg,
+                timeout_ms,
+            )
+        })
+    })
+}
+
+/// Same as `OtLibConsoleWaitForRx(...)`, but also copies the text captured by the capture group
+/// `group` of `c_msg` into `buf` of `buf_len` bytes, NUL terminated, and sets `out_len` to its
+/// length. Group 0 is the whole message.
+///
+/// A capture that does not fit into `buf` is truncated and reported as
+/// `OtLibStatus::BufferTooSmall`, with `out_len` set to the length that would have been needed.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleWaitForRxCapture(
+    transport: *const OtLibTransport,
+    kind: OtLibConsoleKind,
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+    spi_speed_hz: u32,
+    spi_mode: OtLibSpiMode,
+    pull_mode: OtLibPullMode,
+    active_low: bool,
+    c_msg: *mut c_char,
+    group: usize,
+    buf: *mut c_char,
+    buf_len: usize,
+    out_len: *mut usize,
+    timeout_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleWaitForRxCapture", || {
+        check_not_null(transport, "transport")?;
+        check_not_null(spi_interface, "spi_interface")?;
+        check_not_null(tx_ready_pin, "tx_ready_pin")?;
+        check_not_null(c_msg, "c_msg")?;
+        check_not_null(buf, "buf")?;
+        check_not_empty(buf_len, "buf_len")?;
+        check_not_null(out_len, "out_len")?;
+        let msg = cstr_to_str(c_msg)?;
+        check_capture_group(msg, group)?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let spi_args = SpiConsoleArgs {
+            spi_interface,
+            tx_ready_pin,
+            spi_params: spi_params(spi_speed_hz, spi_mode),
+            pull_mode,
+            active_low,
+        };
+
+        if dry_run() {
+            return check_console(transport, kind, &spi_args);
+        }
+
+        // Get handle to the console.
+        let capture = with_console!(guard, kind, &spi_args, |device| {
+            console_wait_for_capture(device, msg, group, timeout_ms)
+        })?;
+        // SAFETY: `out_len` was checked to be non-null and must point to a `usize`.
+        copy_capture(&capture, buf, buf_len, unsafe { &mut *out_len })
+    })
+}
+
+/// Waits for the device to dump its UDS certificate over the console, and copies the decoded DER
+/// certificate into `out` of `len` bytes, setting `out_len` to its length.
+///
+/// The format of the message is up to the device firmware: `uds_cert_regex` must match it, with
+/// the DER certificate, hex or base64 encoded, in its capture group 1. A regex without that group
+/// is reported as `OtLibStatus::InvalidArgument`.
+///
+/// A certificate that does not fit into `out` is reported as `OtLibStatus::BufferTooSmall`, with
+/// `out_len` set to its length, so that the caller can retry with a larger buffer on the next
+/// boot. One that does not decode is reported as `OtLibStatus::ConsoleError`.
+#[no_mangle]
+pub extern "C" fn OtLibExtractUdsCert(
+    transport: *const OtLibTransport,
+    kind: OtLibConsoleKind,
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+    spi_speed_hz: u32,
+    spi_mode: OtLibSpiMode,
+    pull_mode: OtLibPullMode,
+    active_low: bool,
+    uds_cert_regex: *const c_char,
+    out: *mut u8,
+    len: usize,
+    out_len: *mut usize,
+    timeout_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibExtractUdsCert", || {
+        check_not_null(transport, "transport")?;
+        check_not_null(spi_interface, "spi_interface")?;
+        check_not_null(tx_ready_pin, "tx_ready_pin")?;
+        check_not_null(uds_cert_regex, "uds_cert_regex")?;
+        check_not_null(out, "out")?;
+        check_not_empty(len, "len")?;
+        check_not_null(out_len, "out_len")?;
+        let uds_cert_regex = cstr_to_str(uds_cert_regex)?;
+        check_capture_group(uds_cert_regex, UDS_CERT_GROUP)?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let spi_args = SpiConsoleArgs {
+            spi_interface,
+            tx_ready_pin,
+            spi_params: spi_params(spi_speed_hz, spi_mode),
+            pull_mode,
+            active_low,
+        };
+
+        if dry_run() {
+            return check_console(transport, kind, &spi_args);
+        }
+
+        // Get handle to the console.
+        let encoded = with_console!(guard, kind, &spi_args, |device| {
+            console_wait_for_capture(device, uds_cert_regex, UDS_CERT_GROUP, timeout_ms)
+        })
+        .context("UDS certificate not received.")?;
+        let der = decode_cert(&encoded)?;
+        // SAFETY: `out_len` was checked to be non-null and must point to a `usize`.
+        copy_bytes(&der, out, len, unsafe { &mut *out_len })
+    })
+}
+
+/// Waits for a message matching any of the `patterns_count` regexes in `patterns` to be received
+/// over the console, and sets `out_index` to the index of the pattern that matched.
+///
+/// The patterns must not match empty text. Fails with `OtLibStatus::ConsoleTimeout` if none
+/// matched within `timeout_ms`.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleWaitForAnyOf(
+    transport: *const OtLibTransport,
+    kind: OtLibConsoleKind,
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+    spi_speed_hz: u32,
+    spi_mode: OtLibSpiMode,
+    pull_mode: OtLibPullMode,
+    active_low: bool,
+    patterns: *const *const c_char,
+    patterns_count: usize,
+    out_index: *mut usize,
+    timeout_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleWaitForAnyOf", || {
+        check_not_null(transport, "transport")?;
+        check_not_null(spi_interface, "spi_interface")?;
+        check_not_null(tx_ready_pin, "tx_ready_pin")?;
+        check_not_null(out_index, "out_index")?;
+        let any_of = any_of_regex(&cstr_array_to_vec(patterns, patterns_count)?)?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let spi_args = SpiConsoleArgs {
+            spi_interface,
+            tx_ready_pin,
+            spi_params: spi_params(spi_speed_hz, spi_mode),
+            pull_mode,
+            active_low,
+        };
+
+        if dry_run() {
+            return check_console(transport, kind, &spi_args);
+        }
+
+        // Get handle to the console.
+        let index = with_console!(guard, kind, &spi_args, |device| {
+            console_wait_for_any_of(device, &any_of, timeout_ms)
+        })?;
+        // SAFETY: `out_index` was checked to be non-null and must point to a `usize`.
+        unsafe { *out_index = index };
+        Ok(())
+    })
+}
+
+/// Receives a response over the console into the `num_frames` frames at `spi_frames`, and sets
+/// `num_frames` to the number of frames used.
+///
+/// Each frame is filled with up to `frame_size` bytes, which must match the
+/// `kDutTxMaxSpiFrameSizeInBytes` of the device build. A `frame_size` of 0 selects the default of
+/// 2020 bytes, and one that exceeds the payload capacity of a frame is rejected.
+///
+/// Unless `quiet`, the console output is echoed to stdout. With `timestamp` each of its lines is
+/// prefixed with a timestamp, and with `newline` the start of the output counts as the start of a
+/// line. Clear `timestamp` to keep the echo machine readable.
+///
+/// While waiting for the response, `on_heartbeat` is called about every 500 ms with the time
+/// elapsed, unless it is null.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleRx(
+    transport: *const OtLibTransport,
+    kind: OtLibConsoleKind,
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+    spi_speed_hz: u32,
+    spi_mode: OtLibSpiMode,
+    pull_mode: OtLibPullMode,
+    active_low: bool,
+    sync_msg: *mut c_char,
+    spi_frames: *mut DutSpiFrame,
+    num_frames: *mut usize,
+    frame_size: usize,
+    skip_crc_check: bool,
+    crc_kind: OtLibCrcKind,
+    binary: bool,
+    success_regex: *const c_char,
+    failure_regex: *const c_char,
+    quiet: bool,
+    timestamp: bool,
+    newline: bool,
+    timeout_ms: u64,
+    on_heartbeat: Option<OtLibHeartbeatCallback>,
+) -> OtLibStatus {
+    console_rx_ffi(
+        "OtLibConsoleRx",
+        transport,
+        kind,
+        spi_interface,
+        tx_ready_pin,
+        spi_speed_hz,
+        spi_mode,
+        pull_mode,
+        active_low,
+        sync_msg,
+        spi_frames,
+        num_frames,
+        frame_size,
+        skip_crc_check,
+        crc_kind,
+        binary,
+        success_regex,
+        failure_regex,
+        quiet,
+        None,
+        ConsoleFormat { timestamp, newline },
+        timeout_ms,
+        on_heartbeat,
+    )
+}
+
+/// Same as `OtLibConsoleRx(...)`, but writes the console output to the open file descriptor
+/// `capture_fd` instead of stdout, e.g. so that each test slot keeps its own console log. The
+/// descriptor stays owned by the caller and is not closed.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleRxCapture(
+    transport: *const OtLibTransport,
+    kind: OtLibConsoleKind,
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+    spi_speed_hz: u32,
+    spi_mode: OtLibSpiMode,
+    pull_mode: OtLibPullMode,
+    active_low: bool,
+    sync_msg: *mut c_char,
+    spi_frames: *mut DutSpiFrame,
+    num_frames: *mut usize,
+    frame_size: usize,
+    skip_crc_check: bool,
+    crc_kind: OtLibCrcKind,
+    binary: bool,
+    success_regex: *const c_char,
+    failure_regex: *const c_char,
+    capture_fd: c_int,
+    timestamp: bool,
+    newline: bool,
+    timeout_ms: u64,
+    on_heartbeat: Option<OtLibHeartbeatCallback>,
+) -> OtLibStatus {
+    console_rx_ffi(
+        "OtLibConsoleRxCapture",
+        transport,
+        kind,
+        spi_interface,
+        tx_ready_pin,
+        spi_speed_hz,
+        spi_mode,
+        pull_mode,
+        active_low,
+        sync_msg,
+        spi_frames,
+        num_frames,
+        frame_size,
+        skip_crc_check,
+        crc_kind,
+        binary,
+        success_regex,
+        failure_regex,
+        false,
+        Some(capture_fd),
+        ConsoleFormat { timestamp, newline },
+        timeout_ms,
+        on_heartbeat,
+    )
+}
+
+/// Implements `OtLibConsoleRx(...)` and `OtLibConsoleRxCapture(...)`.
+#[allow(clippy::too_many_arguments)]
+fn console_rx_ffi(
+    name: &str,
+    transport: *const OtLibTransport,
+    kind: OtLibConsoleKind,
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+    spi_speed_hz: u32,
+    spi_mode: OtLibSpiMode,
+    pull_mode: OtLibPullMode,
+    active_low: bool,
+    sync_msg: *mut c_char,
+    spi_frames: *mut DutSpiFrame,
+    num_frames: *mut usize,
+    frame_size: usize,
+    skip_crc_check: bool,
+    crc_kind: OtLibCrcKind,
+    binary: bool,
+    success_regex: *const c_char,
+    failure_regex: *const c_char,
+    quiet: bool,
+    capture_fd: Option<c_int>,
+    format: ConsoleFormat,
+    timeout_ms: u64,
+    on_heartbeat: Option<OtLibHeartbeatCallback>,
+) -> OtLibStatus {
+    ffi_call(name, || {
+        let _timer = OpTimer::start(OtLibOp::ConsoleRx);
+        check_not_null(transport, "transport")?;
+        check_not_null(spi_interface, "spi_interface")?;
+        check_not_null(tx_ready_pin, "tx_ready_pin")?;
+        check_not_null(sync_msg, "sync_msg")?;
+        check_not_null(spi_frames, "spi_frames")?;
+        check_not_null(num_frames, "num_frames")?;
+        check_not_null(success_regex, "success_regex")?;
+        check_not_null(failure_regex, "failure_regex")?;
+        // SAFETY: num_frames should be a valid pointer to memory allocated by the caller.
+        let num_frames = unsafe { &mut *num_frames };
+        check_not_empty(*num_frames, "num_frames")?;
+        let frame_size = console_frame_size(frame_size)?;
+        if let Some(fd) = capture_fd {
+            if fd < 0 {
+                bail_status!(
+                    OtLibStatus::InvalidArgument,
+                    "Invalid capture file descriptor {}.",
+                    fd
+                );
+            }
+        }
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let spi_args = SpiConsoleArgs {
+            spi_interface,
+            tx_ready_pin,
+            spi_params: spi_params(spi_speed_hz, spi_mode),
+            pull_mode,
+            active_low,
+        };
+
+        // Unpack sync_msg string.
+        let sync_str = cstr_to_str(sync_msg)?;
+        let regexes = response_regexes(
+            cstr_to_str(success_regex)?,
+            cstr_to_str(failure_regex)?,
+            binary,
+        )?;
+
+        // SAFETY: spi_frames should be a valid pointer to `num_frames` frames allocated by the
+        // caller.
+        let spi_frames = unsafe { slice::from_raw_parts_mut(spi_frames, *num_frames) };
+
+        if dry_run() {
+            return check_console(transport, kind, &spi_args);
+        }
+
+        // SAFETY: The capture file descriptor was checked to be non-negative and must be open for
+        // writing. The file is never dropped, so that the descriptor stays open for the caller.
+        let mut capture = capture_fd.map(|fd| ManuallyDrop::new(unsafe { File::from_raw_fd(fd) }));
+
+        // Get handle to the console.
+        with_console!(guard, kind, &spi_args, |device| {
+            let echo = match capture.as_deref_mut() {
+                Some(file) => ConsoleEcho::Capture(file),
+                None => ConsoleEcho::new(quiet),
+            };
+            console_rx(
+                &HeartbeatConsole::new(device, on_heartbeat),
+                sync_str,
+                spi_frames,
+                num_frames,
+                frame_size,
+                skip_crc_check,
+                crc_kind,
+                &regexes,
+                echo,
+                format,
+                timeout_ms,
+            )
+        })
+    })
+}
+
+/// Sends the `spi_frame_size` bytes at `spi_frame` over the console, once `sync_msg` was received
+/// unless it is empty.
+///
+/// With `compress` the bytes are deflated and prefixed with the header described at
+/// `compress_payload(...)`, for device firmware that inflates its input. Uncompressed bytes are
+/// sent as is.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleTx(
+    transport: *const OtLibTransport,
+    kind: OtLibConsoleKind,
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+    spi_speed_hz: u32,
+    spi_mode: OtLibSpiMode,
+    pull_mode: OtLibPullMode,
+    active_low: bool,
+    sync_msg: *mut c_char,
+    spi_frame: *mut u8,
+    spi_frame_size: usize,
+    compress: bool,
+    timeout_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleTx", || {
+        let _timer = OpTimer::start(OtLibOp::ConsoleTx);
+        check_not_null(transport, "transport")?;
+        check_not_null(spi_interface, "spi_interface")?;
+        check_not_null(tx_ready_pin, "tx_ready_pin")?;
+        check_not_null(sync_msg, "sync_msg")?;
+        check_not_null(spi_frame, "spi_frame")?;
+        check_not_empty(spi_frame_size, "spi_frame_size")?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let spi_args = SpiConsoleArgs {
+            spi_interface,
+            tx_ready_pin,
+            spi_params: spi_params(spi_speed_hz, spi_mode),
+            pull_mode,
+            active_low,
+        };
+
+        // Unpack sync_msg string.
+        let sync_str = cstr_to_str(sync_msg)?;
+
+        // SAFETY: spi_frame should be a valid pointer to `spi_frame_size` bytes allocated by the
+        // caller.
+        let spi_frame = unsafe { slice::from_raw_parts(spi_frame as *const u8, spi_frame_size) };
+        let spi_frame = tx_payload(spi_frame, compress)?;
+
+        if dry_run() {
+            return check_console(transport, kind, &spi_args);
+        }
+
+        // Get handle to the console.
+        with_console!(guard, kind, &spi_args, |device| {
+            console_tx(device, sync_str, &spi_frame, timeout_ms)
+        })
+    })
+}
+
+/// Same as `OtLibConsoleRx(...)`, but calls `on_frame` with each chunk of at most `frame_size`
+/// bytes of the payload, in order, instead of copying it into caller-provided frames. The caller
+/// does not need to know the size of the response in advance, and can e.g. write it to disk as it
+/// goes.
+///
+/// The chunks are handed over once the whole response was received and, unless `skip_crc_check`
+/// is set, its CRC checked, so `on_frame` never sees a corrupted payload. The chunks of an error
+/// response are handed over the same way before `OtLibStatus::DeviceError` is returned.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleRxStream(
+    transport: *const OtLibTransport,
+    kind: OtLibConsoleKind,
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+    spi_speed_hz: u32,
+    spi_mode: OtLibSpiMode,
+    pull_mode: OtLibPullMode,
+    active_low: bool,
+    sync_msg: *mut c_char,
+    on_frame: Option<OtLibFrameCallback>,
+    frame_size: usize,
+    skip_crc_check: bool,
+    crc_kind: OtLibCrcKind,
+    binary: bool,
+    success_regex: *const c_char,
+    failure_regex: *const c_char,
+    quiet: bool,
+    timeout_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleRxStream", || {
+        let _timer = OpTimer::start(OtLibOp::ConsoleRx);
+        check_not_null(transport, "transport")?;
+        check_not_null(spi_interface, "spi_interface")?;
+        check_not_null(tx_ready_pin, "tx_ready_pin")?;
+        check_not_null(sync_msg, "sync_msg")?;
+        check_not_null(success_regex, "success_regex")?;
+        check_not_null(failure_regex, "failure_regex")?;
+        let on_frame = frame_callback(on_frame)?;
+        let frame_size = console_frame_size(frame_size)?;
+        let sync_str = cstr_to_str(sync_msg)?;
+        let regexes = response_regexes(
+            cstr_to_str(success_regex)?,
+            cstr_to_str(failure_regex)?,
+            binary,
+        )?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let spi_args = SpiConsoleArgs {
+            spi_interface,
+            tx_ready_pin,
+            spi_params: spi_params(spi_speed_hz, spi_mode),
+            pull_mode,
+            active_low,
+        };
+
+        if dry_run() {
+            return check_console(transport, kind, &spi_args);
+        }
+
+        // Get handle to the console.
+        with_console!(guard, kind, &spi_args, |device| {
+            console_rx_stream(
+                device,
+                sync_str,
+                on_frame,
+                frame_size,
+                skip_crc_check,
+                crc_kind,
+                &regexes,
+                quiet,
+                timeout_ms,
+            )
+        })
+    })
+}
+
+/// Unwraps the `on_frame` callback argument, which must not be null.
+fn frame_callback(on_frame: Option<OtLibFrameCallback>) -> Result<OtLibFrameCallback> {
+    match on_frame {
+        Some(on_frame) => Ok(on_frame),
+        None => bail_status!(OtLibStatus::InvalidArgument, "`on_frame` must not be null."),
+    }
+}
+
+/// Sends the request `tx_frame` of `tx_frame_size` bytes over the console, then receives the
+/// response into `spi_frames` as `OtLibConsoleRx(...)` does, with the default text response
+/// envelopes.
+///
+/// Unlike an `OtLibConsoleTx(...)` followed by an `OtLibConsoleRx(...)`, the console is opened
+/// once for both, so a response sent by a fast device right after the request is not missed.
+/// `timeout_ms` applies to each of the two steps.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleTransact(
+    transport: *const OtLibTransport,
+    kind: OtLibConsoleKind,
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+    spi_speed_hz: u32,
+    spi_mode: OtLibSpiMode,
+    pull_mode: OtLibPullMode,
+    active_low: bool,
+    tx_frame: *const u8,
+    tx_frame_size: usize,
+    spi_frames: *mut DutSpiFrame,
+    num_frames: *mut usize,
+    frame_size: usize,
+    skip_crc_check: bool,
+    crc_kind: OtLibCrcKind,
+    quiet: bool,
+    timeout_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleTransact", || {
+        let _timer = OpTimer::start(OtLibOp::ConsoleTransact);
+        check_not_null(transport, "transport")?;
+        check_not_null(spi_interface, "spi_interface")?;
+        check_not_null(tx_ready_pin, "tx_ready_pin")?;
+        check_not_null(tx_frame, "tx_frame")?;
+        check_not_empty(tx_frame_size, "tx_frame_size")?;
+        check_not_null(spi_frames, "spi_frames")?;
+        check_not_null(num_frames, "num_frames")?;
+        // SAFETY: num_frames should be a valid pointer to memory allocated by the caller.
+        let num_frames = unsafe { &mut *num_frames };
+        check_not_empty(*num_frames, "num_frames")?;
+        let frame_size = console_frame_size(frame_size)?;
+        let regexes = response_regexes("", "", /*binary=*/ false)?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let spi_args = SpiConsoleArgs {
+            spi_interface,
+            tx_ready_pin,
+            spi_params: spi_params(spi_speed_hz, spi_mode),
+            pull_mode,
+            active_low,
+        };
+
+        // SAFETY: tx_frame should be a valid pointer to `tx_frame_size` bytes allocated by the
+        // caller.
+        let request = unsafe { slice::from_raw_parts(tx_frame, tx_frame_size) };
+        // SAFETY: spi_frames should be a valid pointer to `num_frames` frames allocated by the
+        // caller.
+        let spi_frames = unsafe { slice::from_raw_parts_mut(spi_frames, *num_frames) };
+
+        if dry_run() {
+            return check_console(transport, kind, &spi_args);
+        }
+
+        // Get handle to the console.
+        with_console!(guard, kind, &spi_args, |device| {
+            console_transact(
+                device,
+                request,
+                spi_frames,
+                num_frames,
+                frame_size,
+                skip_crc_check,
+                crc_kind,
+                &regexes,
+                quiet,
+                timeout_ms,
+            )
+        })
+    })
+}
+
+/// Same as `OtLibConsoleTx(...)`, but sends `num_frames` frames, e.g. a payload larger than a
+/// single frame that was split with the same framing `OtLibConsoleRx(...)` uses.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleTxFrames(
+    transport: *const OtLibTransport,
+    kind: OtLibConsoleKind,
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+    spi_speed_hz: u32,
+    spi_mode: OtLibSpiMode,
+    pull_mode: OtLibPullMode,
+    active_low: bool,
+    sync_msg: *mut c_char,
+    frames: *const DutSpiFrame,
+    num_frames: usize,
+    timeout_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleTxFrames", || {
+        let _timer = OpTimer::start(OtLibOp::ConsoleTx);
+        check_not_null(transport, "transport")?;
+        check_not_null(spi_interface, "spi_interface")?;
+        check_not_null(tx_ready_pin, "tx_ready_pin")?;
+        check_not_null(sync_msg, "sync_msg")?;
+        check_not_null(frames, "frames")?;
+        check_not_empty(num_frames, "num_frames")?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let spi_args = SpiConsoleArgs {
+            spi_interface,
+            tx_ready_pin,
+            spi_params: spi_params(spi_speed_hz, spi_mode),
+            pull_mode,
+            active_low,
+        };
+
+        // Unpack sync_msg string.
+        let sync_str = cstr_to_str(sync_msg)?;
+
+        // SAFETY: frames should be a valid pointer to `num_frames` frames allocated by the caller.
+        let frames = unsafe { slice::from_raw_parts(frames, num_frames) };
+
+        if dry_run() {
+            return check_console(transport, kind, &spi_args);
+        }
+
+        // Get handle to the console.
+        with_console!(guard, kind, &spi_args, |device| {
+            console_tx_frames(device, sync_str, frames, timeout_ms)
+        })
+    })
+}
+
+/// Discards the console output received for `drain_ms`, so that stale output of a previous
+/// operation is not matched by the next one.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleFlush(
+    transport: *const OtLibTransport,
+    kind: OtLibConsoleKind,
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+    spi_speed_hz: u32,
+    spi_mode: OtLibSpiMode,
+    pull_mode: OtLibPullMode,
+    active_low: bool,
+    drain_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleFlush", || {
+        check_not_null(transport, "transport")?;
+        check_not_null(spi_interface, "spi_interface")?;
+        check_not_null(tx_ready_pin, "tx_ready_pin")?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let spi_args = SpiConsoleArgs {
+            spi_interface,
+            tx_ready_pin,
+            spi_params: spi_params(spi_speed_hz, spi_mode),
+            pull_mode,
+            active_low,
+        };
+
+        if dry_run() {
+            return check_console(transport, kind, &spi_args);
+        }
+
+        // Get handle to the console.
+        with_console!(guard, kind, &spi_args, |device| {
+            let discarded = console_flush(device, drain_ms)?;
+            log_info!("Discarded {} bytes of console output.", discarded);
+            Ok(())
+        })
+    })
+}
+
+/// A SPI console kept open across console calls, so that buffered console state is not lost
+/// between them. Created by `OtLibConsoleOpen(...)` and released by `OtLibConsoleClose(...)`.
+pub struct SpiConsoleHandle {
+    // Borrows `_spi` and `_tx_ready_pin`, so it must be declared (and thus dropped) first.
+    device: SpiConsoleDevice<'static>,
+    // Boxed so that the `Rc` the device borrows does not move with the handle.
+    _tx_ready_pin: Box<Rc<dyn GpioPin>>,
+    _spi: Rc<dyn Target>,
+    /// Restores the SPI clock speed and mode the console changed when the handle is closed.
+    _spi_settings: SpiSettings,
+    /// Console output of a response still being received by `OtLibConsoleRxPoll(...)`.
+    pending: RefCell<Vec<u8>>,
+    /// Cancellation of the transport the console was opened on, see `OtLibRequestCancel(...)`.
+    cancel: CancelToken,
+}
+
+impl SpiConsoleHandle {
+    fn new(
+        spi: Rc<dyn Target>,
+        tx_ready_pin: Rc<dyn GpioPin>,
+        spi_settings: SpiSettings,
+        cancel: CancelToken,
+    ) -> Result<Self> {
+        let tx_ready_pin = Box::new(tx_ready_pin);
+        // SAFETY: The SPI target and the boxed TX-ready pin are heap allocations owned by the
+        // handle, so they do not move and outlive `device`, which is dropped first.
+        let (spi_ref, tx_ready_pin_ref): (&'static dyn Target, &'static Rc<dyn GpioPin>) = unsafe {
+            (
+                &*Rc::as_ptr(&spi),
+                &*(&*tx_ready_pin as *const Rc<dyn GpioPin>),
+            )
+        };
+        let device = SpiConsoleDevice::new(
+            spi_ref,
+            Some(tx_ready_pin_ref),
+            /*ignore_frame_num=*/ true,
+        )
+        .status(OtLibStatus::ConsoleError)?;
+        Ok(SpiConsoleHandle {
+            device,
+            _tx_ready_pin: tx_ready_pin,
+            _spi: spi,
+            _spi_settings: spi_settings,
+            pending: RefCell::new(Vec::new()),
+            cancel,
+        })
+    }
+
+    /// The console device, whose reads are cancelled by `OtLibRequestCancel(...)`.
+    fn console(&self) -> CancellableConsole<'_, SpiConsoleDevice<'static>> {
+        CancellableConsole::new(&self.device, &self.cancel)
+    }
+}
+
+/// Opens a SPI console that can be reused across the `OtLibConsoleHandle*(...)` calls.
+///
+/// An empty `spi_interface` or `tx_ready_pin` selects the default. The console SPI runs at
+/// `spi_speed_hz` in `spi_mode`, e.g. for a throughput the default clock does not reliably reach;
+/// a speed of 0 and `OtLibSpiMode::Default` keep the defaults of the SPI interface, which gets its
+/// previous speed and mode back once the handle is closed. The TX-ready pin is an input with
+/// `pull_mode`, and with `active_low` signals ready at a low level.
+///
+/// Returns null and sets the last error on failure. The handle must be released with
+/// `OtLibConsoleClose(...)` before the transport is destroyed, and shares the SPI and GPIO
+/// resources of the transport, so it must not be used concurrently with calls on that transport,
+/// and, like the transport, must only be used on the thread that created the transport.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleOpen(
+    transport: *const OtLibTransport,
+    spi_interface: *const c_char,
+    tx_ready_pin: *const c_char,
+    spi_speed_hz: u32,
+    spi_mode: OtLibSpiMode,
+    pull_mode: OtLibPullMode,
+    active_low: bool,
+) -> *mut SpiConsoleHandle {
+    let result = catch_panic(|| {
+        check_not_null(transport, "transport")?;
+        check_not_null(spi_interface, "spi_interface")?;
+        check_not_null(tx_ready_pin, "tx_ready_pin")?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let (spi, device_console_tx_ready_pin, spi_settings) = spi_console_handles(
+            transport,
+            &SpiConsoleArgs {
+                spi_interface,
+                tx_ready_pin,
+                spi_params: spi_params(spi_speed_hz, spi_mode),
+                pull_mode,
+                active_low,
+            },
+        )?;
+        SpiConsoleHandle::new(
+            spi,
+            device_console_tx_ready_pin,
+            spi_settings,
+            guard.cancel_token().clone(),
+        )
+    });
+    match result {
+        Ok(handle) => Box::into_raw(Box::new(handle)),
+        Err(e) => {
+            log_error!("OtLibConsoleOpen failed: {:#}", e);
+            set_last_error(&e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Releases a console handle returned by `OtLibConsoleOpen(...)`. A null handle is ignored.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleClose(handle: *mut SpiConsoleHandle) {
+    if handle.is_null() {
+        return;
+    }
+    // SAFETY: The handle passed from C side should be the pointer returned by the call to
+    // `OtLibConsoleOpen(...)`, and must not be used after this call.
+    drop(unsafe { Box::from_raw(handle) });
+}
+
+/// Same as `OtLibConsoleWaitForRx(...)`, over a console opened with `OtLibConsoleOpen(...)`.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleHandleWaitForRx(
+    handle: *const SpiConsoleHandle,
+    c_msg: *mut c_char,
+    timeout_ms: u64,
+    on_heartbeat: Option<OtLibHeartbeatCallback>,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleHandleWaitForRx", || {
+        check_not_null(handle, "handle")?;
+        check_not_null(c_msg, "c_msg")?;
+
+        // SAFETY: The handle passed from C side should be the pointer returned by the call to
+        // `OtLibConsoleOpen(...)`.
+        let handle: &SpiConsoleHandle = unsafe { &*handle };
+        let msg = cstr_to_str(c_msg)?;
+        if dry_run() {
+            return Ok(());
+        }
+        console_wait_for_rx(
+            &HeartbeatConsole::new(&handle.console(), on_heartbeat),
+            msg,
+            timeout_ms,
+        )
+    })
+}
+
+/// Same as `OtLibConsoleWaitForRxCapture(...)`, over a console opened with
+/// `OtLibConsoleOpen(...)`.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleHandleWaitForRxCapture(
+    handle: *const SpiConsoleHandle,
+    c_msg: *mut c_char,
+    group: usize,
+    buf: *mut c_char,
+    buf_len: usize,
+    out_len: *mut usize,
+    timeout_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleHandleWaitForRxCapture", || {
+        check_not_null(handle, "handle")?;
+        check_not_null(c_msg, "c_msg")?;
+        check_not_null(buf, "buf")?;
+        check_not_empty(buf_len, "buf_len")?;
+        check_not_null(out_len, "out_len")?;
+        let msg = cstr_to_str(c_msg)?;
+        check_capture_group(msg, group)?;
+
+        // SAFETY: The handle passed from C side should be the pointer returned by the call to
+        // `OtLibConsoleOpen(...)`.
+        let handle: &SpiConsoleHandle = unsafe { &*handle };
+        if dry_run() {
+            return Ok(());
+        }
+        let capture = console_wait_for_capture(&handle.console(), msg, group, timeout_ms)?;
+        // SAFETY: `out_len` was checked to be non-null and must point to a `usize`.
+        copy_capture(&capture, buf, buf_len, unsafe { &mut *out_len })
+    })
+}
+
+/// Same as `OtLibExtractUdsCert(...)`, over a console opened with `OtLibConsoleOpen(...)`.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleHandleExtractUdsCert(
+    handle: *const SpiConsoleHandle,
+    uds_cert_regex: *const c_char,
+    out: *mut u8,
+    len: usize,
+    out_len: *mut usize,
+    timeout_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleHandleExtractUdsCert", || {
+        check_not_null(handle, "handle")?;
+        check_not_null(uds_cert_regex, "uds_cert_regex")?;
+        check_not_null(out, "out")?;
+        check_not_empty(len, "len")?;
+        check_not_null(out_len, "out_len")?;
+        let uds_cert_regex = cstr_to_str(uds_cert_regex)?;
+        check_capture_group(uds_cert_regex, UDS_CERT_GROUP)?;
+
+        // SAFETY: The handle passed from C side should be the pointer returned by the call to
+        // `OtLibConsoleOpen(...)`.
+        let handle: &SpiConsoleHandle = unsafe { &*handle };
+        if dry_run() {
+            return Ok(());
+        }
+        let encoded = console_wait_for_capture(
+            &handle.console(),
+            uds_cert_regex,
+            UDS_CERT_GROUP,
+            timeout_ms,
+        )
+        .context("UDS certificate not received.")?;
+        let der = decode_cert(&encoded)?;
+        // SAFETY: `out_len` was checked to be non-null and must point to a `usize`.
+        copy_bytes(&der, out, len, unsafe { &mut *out_len })
+    })
+}
+
+/// Same as `OtLibConsoleWaitForAnyOf(...)`, over a console opened with `OtLibConsoleOpen(...)`.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleHandleWaitForAnyOf(
+    handle: *const SpiConsoleHandle,
+    patterns: *const *const c_char,
+    patterns_count: usize,
+    out_index: *mut usize,
+    timeout_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleHandleWaitForAnyOf", || {
+        check_not_null(handle, "handle")?;
+        check_not_null(out_index, "out_index")?;
+        let any_of = any_of_regex(&cstr_array_to_vec(patterns, patterns_count)?)?;
+
+        // SAFETY: The handle passed from C side should be the pointer returned by the call to
+        // `OtLibConsoleOpen(...)`.
+        let handle: &SpiConsoleHandle = unsafe { &*handle };
+        if dry_run() {
+            return Ok(());
+        }
+        let index = console_wait_for_any_of(&handle.console(), &any_of, timeout_ms)?;
+        // SAFETY: `out_index` was checked to be non-null and must point to a `usize`.
+        unsafe { *out_index = index };
+        Ok(())
+    })
+}
+
+/// Same as `OtLibConsoleRx(...)`, over a console opened with `OtLibConsoleOpen(...)`.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleHandleRx(
+    handle: *const SpiConsoleHandle,
+    sync_msg: *mut c_char,
+    spi_frames: *mut DutSpiFrame,
+    num_frames: *mut usize,
+    frame_size: usize,
+    skip_crc_check: bool,
+    crc_kind: OtLibCrcKind,
+    binary: bool,
+    success_regex: *const c_char,
+    failure_regex: *const c_char,
+    quiet: bool,
+    timestamp: bool,
+    newline: bool,
+    timeout_ms: u64,
+    on_heartbeat: Option<OtLibHeartbeatCallback>,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleHandleRx", || {
+        let _timer = OpTimer::start(OtLibOp::ConsoleRx);
+        check_not_null(handle, "handle")?;
+        check_not_null(sync_msg, "sync_msg")?;
+        check_not_null(spi_frames, "spi_frames")?;
+        check_not_null(num_frames, "num_frames")?;
+        check_not_null(success_regex, "success_regex")?;
+        check_not_null(failure_regex, "failure_regex")?;
+        // SAFETY: num_frames should be a valid pointer to memory allocated by the caller.
+        let num_frames = unsafe { &mut *num_frames };
+        check_not_empty(*num_frames, "num_frames")?;
+        let frame_size = console_frame_size(frame_size)?;
+
+        // SAFETY: The handle passed from C side should be the pointer returned by the call to
+        // `OtLibConsoleOpen(...)`.
+        let handle: &SpiConsoleHandle = unsafe { &*handle };
+        let sync_str = cstr_to_str(sync_msg)?;
+        let regexes = response_regexes(
+            cstr_to_str(success_regex)?,
+            cstr_to_str(failure_regex)?,
+            binary,
+        )?;
+        // SAFETY: spi_frames should be a valid pointer to `num_frames` frames allocated by the
+        // caller.
+        let spi_frames = unsafe { slice::from_raw_parts_mut(spi_frames, *num_frames) };
+        if dry_run() {
+            return Ok(());
+        }
+        console_rx(
+            &HeartbeatConsole::new(&handle.console(), on_heartbeat),
+            sync_str,
+            spi_frames,
+            num_frames,
+            frame_size,
+            skip_crc_check,
+            crc_kind,
+            &regexes,
+            ConsoleEcho::new(quiet),
+            ConsoleFormat { timestamp, newline },
+            timeout_ms,
+        )
+    })
+}
+
+/// Same as `OtLibConsoleTransact(...)`, over a console opened with `OtLibConsoleOpen(...)`.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleHandleTransact(
+    handle: *const SpiConsoleHandle,
+    tx_frame: *const u8,
+    tx_frame_size: usize,
+    spi_frames: *mut DutSpiFrame,
+    num_frames: *mut usize,
+    frame_size: usize,
+    skip_crc_check: bool,
+    crc_kind: OtLibCrcKind,
+    quiet: bool,
+    timeout_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleHandleTransact", || {
+        let _timer = OpTimer::start(OtLibOp::ConsoleTransact);
+        check_not_null(handle, "handle")?;
+        check_not_null(tx_frame, "tx_frame")?;
+        check_not_empty(tx_frame_size, "tx_frame_size")?;
+        check_not_null(spi_frames, "spi_frames")?;
+        check_not_null(num_frames, "num_frames")?;
+        // SAFETY: num_frames should be a valid pointer to memory allocated by the caller.
+        let num_frames = unsafe { &mut *num_frames };
+        check_not_empty(*num_frames, "num_frames")?;
+        let frame_size = console_frame_size(frame_size)?;
+        let regexes = response_regexes("", "", /*binary=*/ false)?;
+
+        // SAFETY: The handle passed from C side should be the pointer returned by the call to
+        // `OtLibConsoleOpen(...)`.
+        let handle: &SpiConsoleHandle = unsafe { &*handle };
+        // SAFETY: tx_frame should be a valid pointer to `tx_frame_size` bytes allocated by the
+        // caller.
+        let request = unsafe { slice::from_raw_parts(tx_frame, tx_frame_size) };
+        // SAFETY: spi_frames should be a valid pointer to `num_frames` frames allocated by the
+        // caller.
+        let spi_frames = unsafe { slice::from_raw_parts_mut(spi_frames, *num_frames) };
+        if dry_run() {
+            return Ok(());
+        }
+        console_transact(
+            &handle.console(),
+            request,
+            spi_frames,
+            num_frames,
+            frame_size,
+            skip_crc_check,
+            crc_kind,
+            &regexes,
+            quiet,
+            timeout_ms,
+        )
+    })
+}
+
+/// Same as `OtLibConsoleRxStream(...)`, over a console opened with `OtLibConsoleOpen(...)`.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleHandleRxStream(
+    handle: *const SpiConsoleHandle,
+    sync_msg: *mut c_char,
+    on_frame: Option<OtLibFrameCallback>,
+    frame_size: usize,
+    skip_crc_check: bool,
+    crc_kind: OtLibCrcKind,
+    binary: bool,
+    success_regex: *const c_char,
+    failure_regex: *const c_char,
+    quiet: bool,
+    timeout_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleHandleRxStream", || {
+        let _timer = OpTimer::start(OtLibOp::ConsoleRx);
+        check_not_null(handle, "handle")?;
+        check_not_null(sync_msg, "sync_msg")?;
+        check_not_null(success_regex, "success_regex")?;
+        check_not_null(failure_regex, "failure_regex")?;
+        let on_frame = frame_callback(on_frame)?;
+        let frame_size = console_frame_size(frame_size)?;
+
+        // SAFETY: The handle passed from C side should be the pointer returned by the call to
+        // `OtLibConsoleOpen(...)`.
+        let handle: &SpiConsoleHandle = unsafe { &*handle };
+        let sync_str = cstr_to_str(sync_msg)?;
+        let regexes = response_regexes(
+            cstr_to_str(success_regex)?,
+            cstr_to_str(failure_regex)?,
+            binary,
+        )?;
+        if dry_run() {
+            return Ok(());
+        }
+        console_rx_stream(
+            &handle.console(),
+            sync_str,
+            on_frame,
+            frame_size,
+            skip_crc_check,
+            crc_kind,
+            &regexes,
+            quiet,
+            timeout_ms,
+        )
+    })
+}
+
+/// Receives a response over a console opened with `OtLibConsoleOpen(...)` without blocking for
+/// longer than `slice_ms` (50ms if zero), so that the caller can keep other work going while the
+/// DUT is busy.
+///
+/// Fails with `OtLibStatus::WouldBlock` while the response is incomplete: the output received so
+/// far is kept by the handle, and the caller should poll again. Once the response is complete, it
+/// is handled as by `OtLibConsoleHandleRx(...)`. There is no sync message; wait for it with
+/// `OtLibConsoleHandleWaitForRx(...)` first if needed. The other console calls on the handle do
+/// not see the output kept for an incomplete response.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleRxPoll(
+    handle: *const SpiConsoleHandle,
+    spi_frames: *mut DutSpiFrame,
+    num_frames: *mut usize,
+    frame_size: usize,
+    skip_crc_check: bool,
+    crc_kind: OtLibCrcKind,
+    binary: bool,
+    success_regex: *const c_char,
+    failure_regex: *const c_char,
+    quiet: bool,
+    slice_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleRxPoll", || {
+        check_not_null(handle, "handle")?;
+        check_not_null(spi_frames, "spi_frames")?;
+        check_not_null(num_frames, "num_frames")?;
+        check_not_null(success_regex, "success_regex")?;
+        check_not_null(failure_regex, "failure_regex")?;
+        // SAFETY: num_frames should be a valid pointer to memory allocated by the caller.
+        let num_frames = unsafe { &mut *num_frames };
+        check_not_empty(*num_frames, "num_frames")?;
+        let frame_size = console_frame_size(frame_size)?;
+
+        // SAFETY: The handle passed from C side should be the pointer returned by the call to
+        // `OtLibConsoleOpen(...)`.
+        let handle: &SpiConsoleHandle = unsafe { &*handle };
+        let regexes = response_regexes(
+            cstr_to_str(success_regex)?,
+            cstr_to_str(failure_regex)?,
+            binary,
+        )?;
+        // SAFETY: spi_frames should be a valid pointer to `num_frames` frames allocated by the
+        // caller.
+        let spi_frames = unsafe { slice::from_raw_parts_mut(spi_frames, *num_frames) };
+        let slice = match slice_ms {
+            0 => DEFAULT_RX_POLL_SLICE,
+            ms => Duration::from_millis(ms),
+        };
+        if dry_run() {
+            return Ok(());
+        }
+        console_rx_poll(
+            &handle.console(),
+            &mut handle.pending.borrow_mut(),
+            spi_frames,
+            num_frames,
+            frame_size,
+            skip_crc_check,
+            crc_kind,
+            &regexes,
+            quiet,
+            slice,
+        )
+    })
+}
+
+/// Same as `OtLibConsoleTx(...)`, over a console opened with `OtLibConsoleOpen(...)`.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleHandleTx(
+    handle: *const SpiConsoleHandle,
+    sync_msg: *mut c_char,
+    spi_frame: *mut u8,
+    spi_frame_size: usize,
+    compress: bool,
+    timeout_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleHandleTx", || {
+        let _timer = OpTimer::start(OtLibOp::ConsoleTx);
+        check_not_null(handle, "handle")?;
+        check_not_null(sync_msg, "sync_msg")?;
+        check_not_null(spi_frame, "spi_frame")?;
+        check_not_empty(spi_frame_size, "spi_frame_size")?;
+
+        // SAFETY: The handle passed from C side should be the pointer returned by the call to
+        // `OtLibConsoleOpen(...)`.
+        let handle: &SpiConsoleHandle = unsafe { &*handle };
+        let sync_str = cstr_to_str(sync_msg)?;
+        // SAFETY: spi_frame should be a valid pointer to `spi_frame_size` bytes allocated by the
+        // caller.
+        let spi_frame = unsafe { slice::from_raw_parts(spi_frame as *const u8, spi_frame_size) };
+        let spi_frame = tx_payload(spi_frame, compress)?;
+        if dry_run() {
+            return Ok(());
+        }
+        console_tx(&handle.console(), sync_str, &spi_frame, timeout_ms)
+    })
+}
+
+/// Same as `OtLibConsoleTxFrames(...)`, over a console opened with `OtLibConsoleOpen(...)`.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleHandleTxFrames(
+    handle: *const SpiConsoleHandle,
+    sync_msg: *mut c_char,
+    frames: *const DutSpiFrame,
+    num_frames: usize,
+    timeout_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleHandleTxFrames", || {
+        let _timer = OpTimer::start(OtLibOp::ConsoleTx);
+        check_not_null(handle, "handle")?;
+        check_not_null(sync_msg, "sync_msg")?;
+        check_not_null(frames, "frames")?;
+        check_not_empty(num_frames, "num_frames")?;
+
+        // SAFETY: The handle passed from C side should be the pointer returned by the call to
+        // `OtLibConsoleOpen(...)`.
+        let handle: &SpiConsoleHandle = unsafe { &*handle };
+        let sync_str = cstr_to_str(sync_msg)?;
+        // SAFETY: frames should be a valid pointer to `num_frames` frames allocated by the caller.
+        let frames = unsafe { slice::from_raw_parts(frames, num_frames) };
+        if dry_run() {
+            return Ok(());
+        }
+        console_tx_frames(&handle.console(), sync_str, frames, timeout_ms)
+    })
+}
+
+/// Same as `OtLibConsoleFlush(...)`, over a console opened with `OtLibConsoleOpen(...)`.
+#[no_mangle]
+pub extern "C" fn OtLibConsoleHandleFlush(
+    handle: *const SpiConsoleHandle,
+    drain_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibConsoleHandleFlush", || {
+        check_not_null(handle, "handle")?;
+
+        // SAFETY: The handle passed from C side should be the pointer returned by the call to
+        // `OtLibConsoleOpen(...)`.
+        let handle: &SpiConsoleHandle = unsafe { &*handle };
+        if dry_run() {
+            return Ok(());
+        }
+        let discarded = console_flush(&handle.console(), drain_ms)?;
+        log_info!("Discarded {} bytes of console output.", discarded);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    use crate::ffi::tests::dangling;
+    use crate::log::tests::{record, LOGGED};
+    use crate::log::{OtLibLogLevel, OtLibSetLogCallback};
+    use crate::mock::MockConsole;
+
+    const EMPTY_FRAME: DutSpiFrame = DutSpiFrame {
+        payload: [0; CONSOLE_BUFFER_MAX_SIZE],
+        size: 0,
+    };
+
+    /// Receives a binary response from a console replaying `script` into 3 frames.
+    fn rx_binary(script: &[u8]) -> (Result<()>, [DutSpiFrame; 3], usize) {
+        rx_script(script, CONSOLE_BUFFER_MAX_SIZE, /*binary=*/ true)
+    }
+
+    /// Receives a text response from a console replaying `script` a few bytes per read, as the
+    /// UART console delivers it, into 3 frames.
+    fn rx_text(script: &[u8]) -> (Result<()>, [DutSpiFrame; 3], usize) {
+        rx_script(script, 5, /*binary=*/ false)
+    }
+
+    /// Receives a response from a console replaying `script` at most `chunk_size` bytes per read
+    /// into 3 frames.
+    fn rx_script(
+        script: &[u8],
+        chunk_size: usize,
+        binary: bool,
+    ) -> (Result<()>, [DutSpiFrame; 3], usize) {
+        let console = MockConsole::with_chunk_size(script, chunk_size);
+        let regexes = response_regexes("", "", binary).unwrap();
+        let mut spi_frames = [EMPTY_FRAME; 3];
+        let mut num_frames = spi_frames.len();
+        let result = console_rx(
+            &console,
+            "",
+            &mut spi_frames,
+            &mut num_frames,
+            CONSOLE_BUFFER_MAX_SIZE,
+            false,
+            OtLibCrcKind::IsoHdlc,
+            &regexes,
+            ConsoleEcho::Quiet,
+            ConsoleFormat::DEFAULT,
+            100,
+        );
+        (result, spi_frames, num_frames)
+    }
+
+    fn response(envelope: &str, payload: &[u8], crc: u32) -> Vec<u8> {
+        let mut response = format!("boot log\n{}:", envelope).into_bytes();
+        response.extend_from_slice(payload);
+        response.extend_from_slice(format!(" CRC:{}\n", crc).as_bytes());
+        response
+    }
+
+    #[test]
+    fn rx_splits_checked_response_into_frames() {
+        let payload: Vec<u8> = (0..=255)
+            .cycle()
+            .take(CONSOLE_BUFFER_MAX_SIZE + 3)
+            .collect();
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&payload);
+        let (result, spi_frames, num_frames) = rx_binary(&response("RESP_OK", &payload, crc));
+        result.unwrap();
+        assert_eq!(num_frames, 2);
+        assert_eq!(spi_frames[0].size, CONSOLE_BUFFER_MAX_SIZE);
+        assert_eq!(
+            &spi_frames[1].payload[..3],
+            &payload[CONSOLE_BUFFER_MAX_SIZE..]
+        );
+    }
+
+    #[test]
+    fn crc_is_checked_over_raw_bytes() {
+        // Not valid UTF-8.
+        let payload = [0xff, 0x00, 0xc3, 0x28];
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&payload);
+        check_console_crc(&payload, &crc.to_string(), OtLibCrcKind::IsoHdlc).unwrap();
+        let lossy = String::from_utf8_lossy(&payload);
+        let err = check_console_crc(lossy.as_bytes(), &crc.to_string(), OtLibCrcKind::IsoHdlc)
+            .unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::CrcMismatch);
+        let err = check_console_crc(&payload, "0x1234", OtLibCrcKind::IsoHdlc).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::CrcMismatch);
+    }
+
+    /// Receives a binary response over `console`, echoing it to the log, and returns the lines
+    /// logged.
+    fn rx_binary_logged(console: &impl ConsoleDevice, timeout_ms: u64) -> Vec<String> {
+        let regexes = response_regexes("", "", /*binary=*/ true).unwrap();
+        let mut spi_frames = [EMPTY_FRAME; 3];
+        let mut num_frames = spi_frames.len();
+        OtLibSetLogCallback(Some(record));
+        let _ = console_rx(
+            console,
+            "",
+            &mut spi_frames,
+            &mut num_frames,
+            CONSOLE_BUFFER_MAX_SIZE,
+            false,
+            OtLibCrcKind::IsoHdlc,
+            &regexes,
+            ConsoleEcho::Stdout,
+            ConsoleFormat::DEFAULT,
+            timeout_ms,
+        );
+        OtLibSetLogCallback(None);
+        LOGGED
+            .with(RefCell::take)
+            .into_iter()
+            .map(|(_, msg)| msg)
+            .collect()
+    }
+
+    #[test]
+    fn rx_logs_console_output_by_line() {
+        let payload = b"{\"x\":1}";
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(payload);
+        let console = MockConsole::with_chunk_size(&response("RESP_OK", payload, crc), 3);
+        assert_eq!(
+            rx_binary_logged(&console, 100),
+            vec![
+                "boot log".to_string(),
+                format!("RESP_OK:{{\"x\":1}} CRC:{}", crc)
+            ]
+        );
+    }
+
+    #[test]
+    fn rx_does_not_log_empty_reads() {
+        let console = MockConsole::new(b"");
+        // Cut the wait into many reads that return nothing.
+        let console = HeartbeatConsole::with_interval(
+            &console,
+            Some(record_heartbeat),
+            Duration::from_millis(1),
+        );
+        assert!(rx_binary_logged(&console, 20).is_empty());
+        HEARTBEATS.with(RefCell::take);
+    }
+
+    #[test]
+    fn rx_rejects_response_with_bad_crc() {
+        let payload = br#"{"status":"ok"}"#;
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(payload);
+        let (result, _, _) = rx_binary(&response("RESP_OK", payload, crc ^ 1));
+        assert_eq!(
+            OtLibStatus::from_error(&result.unwrap_err()),
+            OtLibStatus::CrcMismatch
+        );
+    }
+
+    #[test]
+    fn rx_reports_error_response_as_device_error() {
+        let payload = br#"{"error":"otp locked"}"#;
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(payload);
+        let (result, spi_frames, num_frames) = rx_binary(&response("RESP_ERR", payload, crc));
+        assert_eq!(
+            OtLibStatus::from_error(&result.unwrap_err()),
+            OtLibStatus::DeviceError
+        );
+        assert_eq!(num_frames, 1);
+        assert_eq!(&spi_frames[0].payload[..spi_frames[0].size], payload);
+    }
+
+    #[test]
+    fn text_rx_copies_checked_response_into_frames() {
+        let payload = br#"{"status":"ok"}"#;
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(payload);
+        let (result, spi_frames, num_frames) = rx_text(&response("RESP_OK", payload, crc));
+        result.unwrap();
+        assert_eq!(num_frames, 1);
+        assert_eq!(&spi_frames[0].payload[..spi_frames[0].size], payload);
+    }
+
+    #[test]
+    fn text_rx_reports_error_response_as_device_error() {
+        let payload = br#"{"error":"otp locked"}"#;
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(payload);
+        let (result, spi_frames, num_frames) = rx_text(&response("RESP_ERR", payload, crc));
+        assert_eq!(
+            OtLibStatus::from_error(&result.unwrap_err()),
+            OtLibStatus::DeviceError
+        );
+        assert_eq!(num_frames, 1);
+        assert_eq!(&spi_frames[0].payload[..spi_frames[0].size], payload);
+    }
+
+    #[test]
+    fn text_rx_rejects_response_with_bad_crc() {
+        let payload = br#"{"status":"ok"}"#;
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(payload);
+        let (result, _, _) = rx_text(&response("RESP_OK", payload, crc ^ 1));
+        assert_eq!(
+            OtLibStatus::from_error(&result.unwrap_err()),
+            OtLibStatus::CrcMismatch
+        );
+    }
+
+    #[test]
+    fn rx_writes_console_output_to_capture() {
+        let payload = b"{\"x\":1}";
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(payload);
+        let script = response("RESP_OK", payload, crc);
+        let console = MockConsole::with_chunk_size(&script, CONSOLE_BUFFER_MAX_SIZE);
+        let regexes = response_regexes("", "", /*binary=*/ true).unwrap();
+        let mut spi_frames = [EMPTY_FRAME; 1];
+        let mut num_frames = spi_frames.len();
+        let mut capture = Vec::new();
+        console_rx(
+            &console,
+            "",
+            &mut spi_frames,
+            &mut num_frames,
+            CONSOLE_BUFFER_MAX_SIZE,
+            false,
+            OtLibCrcKind::IsoHdlc,
+            &regexes,
+            ConsoleEcho::Capture(&mut capture),
+            ConsoleFormat::DEFAULT,
+            100,
+        )
+        .unwrap();
+        assert_eq!(capture, script);
+        assert_eq!(&spi_frames[0].payload[..payload.len()], payload);
+    }
+
+    #[test]
+    fn rx_capture_rejects_invalid_fd() {
+        let mut num_frames = 1usize;
+        let status = OtLibConsoleRxCapture(
+            dangling(),
+            OtLibConsoleKind::Spi,
+            dangling(),
+            dangling(),
+            0,
+            OtLibSpiMode::Default,
+            OtLibPullMode::None,
+            false,
+            dangling(),
+            dangling(),
+            &mut num_frames,
+            0,
+            false,
+            OtLibCrcKind::IsoHdlc,
+            false,
+            dangling(),
+            dangling(),
+            -1,
+            true,
+            true,
+            0,
+            None,
+        );
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn flush_discards_stale_output() {
+        let console = MockConsole::with_chunk_size(b"stale RESP_OK", 4);
+        assert_eq!(console_flush(&console, 10).unwrap(), 13);
+        assert_eq!(console_flush(&console, 0).unwrap(), 0);
+        assert_eq!(
+            OtLibConsoleHandleFlush(std::ptr::null(), 0),
+            OtLibStatus::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn rx_times_out_without_response() {
+        let (result, _, _) = rx_binary(b"boot log\n");
+        assert_eq!(
+            OtLibStatus::from_error(&result.unwrap_err()),
+            OtLibStatus::ConsoleTimeout
+        );
+    }
+
+    #[test]
+    fn rx_poll_keeps_partial_response_across_calls() {
+        let regexes = response_regexes("", "", /*binary=*/ true).unwrap();
+        let payload = b"{\"x\":1}";
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(payload);
+        let mut script = response("RESP_OK", payload, crc);
+        script.extend(b"next");
+        let console = MockConsole::with_chunk_size(&script, 8);
+        let mut pending = Vec::new();
+        let mut frames = [EMPTY_FRAME; 3];
+        let mut polls = 0;
+        let result = loop {
+            let mut num_frames = frames.len();
+            let result = console_rx_poll(
+                &console,
+                &mut pending,
+                &mut frames,
+                &mut num_frames,
+                CONSOLE_BUFFER_MAX_SIZE,
+                false,
+                OtLibCrcKind::IsoHdlc,
+                &regexes,
+                true,
+                Duration::ZERO,
+            );
+            polls += 1;
+            match result {
+                Err(e) if OtLibStatus::from_error(&e) == OtLibStatus::WouldBlock => continue,
+                result => break result.map(|_| num_frames),
+            }
+        };
+        assert_eq!(result.unwrap(), 1);
+        assert!(polls > 1);
+        assert_eq!(&frames[0].payload[..frames[0].size], payload);
+        // Output received after the response is kept for the next call.
+        assert!(b"next".starts_with(&pending));
+    }
+
+    /// Polls a console replaying `script` in chunks of 8 bytes until the response is complete or
+    /// `max_polls` calls returned `OtLibStatus::WouldBlock`, and returns the lines logged.
+    fn rx_poll_logged(script: &[u8], max_polls: usize) -> Vec<String> {
+        let regexes = response_regexes("", "", /*binary=*/ true).unwrap();
+        let console = MockConsole::with_chunk_size(script, 8);
+        let mut pending = Vec::new();
+        let mut frames = [EMPTY_FRAME; 3];
+        OtLibSetLogCallback(Some(record));
+        for _ in 0..max_polls {
+            let mut num_frames = frames.len();
+            let result = console_rx_poll(
+                &console,
+                &mut pending,
+                &mut frames,
+                &mut num_frames,
+                CONSOLE_BUFFER_MAX_SIZE,
+                false,
+                OtLibCrcKind::IsoHdlc,
+                &regexes,
+                false,
+                Duration::ZERO,
+            );
+            match result {
+                Err(e) if OtLibStatus::from_error(&e) == OtLibStatus::WouldBlock => continue,
+                result => {
+                    result.unwrap();
+                    break;
+                }
+            }
+        }
+        OtLibSetLogCallback(None);
+        LOGGED
+            .with(RefCell::take)
+            .into_iter()
+            .map(|(_, msg)| msg)
+            .collect()
+    }
+
+    #[test]
+    fn rx_poll_logs_console_output_by_line() {
+        let payload = b"{\"x\":1}";
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(payload);
+        assert_eq!(
+            rx_poll_logged(&response("RESP_OK", payload, crc), 100),
+            vec![
+                "boot log".to_string(),
+                format!("RESP_OK:{{\"x\":1}} CRC:{}", crc)
+            ]
+        );
+    }
+
+    #[test]
+    fn rx_poll_does_not_log_empty_reads() {
+        assert!(rx_poll_logged(b"", 10).is_empty());
+    }
+
+    /// Runs the response flow on two threads side by side, as the threads driving the DUTs of a
+    /// multi-slot station do, to check that each thread keeps its own log and last error. There is
+    /// no transport here: each thread receives its responses from its own `MockConsole`.
+    #[test]
+    fn console_flows_keep_per_thread_state() {
+        const ROUNDS: usize = 20;
+        let barrier = std::sync::Barrier::new(2);
+        std::thread::scope(|scope| {
+            for slot in 0..2 {
+                let barrier = &barrier;
+                scope.spawn(move || {
+                    // Each thread drives its own DUT, with its own log and last error.
+                    let payload = format!("{{\"slot\":{}}}", slot).into_bytes();
+                    let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&payload);
+                    let regexes = response_regexes("", "", /*binary=*/ true).unwrap();
+                    OtLibSetLogCallback(Some(record));
+                    barrier.wait();
+                    for round in 0..ROUNDS {
+                        let envelope = if round + 1 < ROUNDS {
+                            "RESP_OK"
+                        } else {
+                            "RESP_ERR"
+                        };
+                        let script = response(envelope, &payload, crc);
+                        let console = MockConsole::with_chunk_size(&script, 7);
+                        let mut spi_frames = [EMPTY_FRAME; 3];
+                        let mut num_frames = spi_frames.len();
+                        let status = ffi_call("test", || {
+                            console_rx(
+                                &console,
+                                "",
+                                &mut spi_frames,
+                                &mut num_frames,
+                                CONSOLE_BUFFER_MAX_SIZE,
+                                false,
+                                OtLibCrcKind::IsoHdlc,
+                                &regexes,
+                                ConsoleEcho::Stdout,
+                                ConsoleFormat::DEFAULT,
+                                100,
+                            )
+                        });
+                        let expected = if round + 1 < ROUNDS {
+                            OtLibStatus::Ok
+                        } else {
+                            OtLibStatus::DeviceError
+                        };
+                        assert_eq!(status, expected);
+                        assert_eq!(num_frames, 1);
+                        assert_eq!(&spi_frames[0].payload[..spi_frames[0].size], payload);
+                    }
+                    OtLibSetLogCallback(None);
+
+                    let mut buf = [0 as c_char; 64];
+                    crate::status::OtLibGetLastError(buf.as_mut_ptr(), buf.len());
+                    // SAFETY: `OtLibGetLastError(...)` always NUL terminates `buf`.
+                    let msg = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+                    assert_eq!(msg.to_bytes(), payload);
+                    // The console output of the DUT, without the error logged by `ffi_call(...)`.
+                    let echoed: Vec<_> = LOGGED
+                        .with(RefCell::take)
+                        .into_iter()
+                        .filter(|(level, _)| *level == OtLibLogLevel::Info as i32)
+                        .map(|(_, msg)| msg)
+                        .collect();
+                    assert_eq!(echoed.len(), 2 * ROUNDS);
+                    let payload = String::from_utf8(payload).unwrap();
+                    assert!(echoed
+                        .iter()
+                        .all(|msg| msg == "boot log" || msg.contains(&payload)));
+                });
+            }
+        });
+    }
+
+    thread_local! {
+        static HEARTBEATS: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+    }
+
+    extern "C" fn record_heartbeat(elapsed_ms: u64) {
+        HEARTBEATS.with(|heartbeats| heartbeats.borrow_mut().push(elapsed_ms));
+    }
+
+    #[test]
+    fn heartbeat_is_called_while_waiting() {
+        let console = MockConsole::new(b"");
+        let heartbeat = HeartbeatConsole::with_interval(
+            &console,
+            Some(record_heartbeat),
+            Duration::from_millis(5),
+        );
+        let regexes = response_regexes("", "", /*binary=*/ true).unwrap();
+        let (result, _, _) = receive_response(
+            &heartbeat,
+            "",
+            &regexes,
+            ConsoleEcho::Quiet,
+            ConsoleFormat::DEFAULT,
+            30,
+        )
+        .unwrap();
+        assert!(matches!(result, ExitStatus::Timeout));
+        let heartbeats = HEARTBEATS.with(|heartbeats| heartbeats.take());
+        assert!(heartbeats.len() >= 3, "{:?}", heartbeats);
+        assert!(heartbeats.windows(2).all(|w| w[0] < w[1]));
+
+        // Without a callback reads are passed through.
+        let bytes = MockConsole::new(b"boot");
+        let quiet = HeartbeatConsole::new(&bytes, None);
+        let mut buf = [0u8; 8];
+        assert_eq!(quiet.console_read(&mut buf, Duration::ZERO).unwrap(), 4);
+    }
+
+    thread_local! {
+        static STREAMED: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+    }
+
+    extern "C" fn record_frame(data: *const u8, len: usize) {
+        // SAFETY: `console_rx_stream(...)` passes a chunk of `len` bytes at `data`.
+        let chunk = unsafe { slice::from_raw_parts(data, len) };
+        STREAMED.with(|streamed| streamed.borrow_mut().push(chunk.to_vec()));
+    }
+
+    #[test]
+    fn rx_stream_hands_over_payload_in_chunks() {
+        let regexes = response_regexes("", "", /*binary=*/ true).unwrap();
+        let payload: Vec<u8> = (0..=255)
+            .cycle()
+            .take(2 * CONSOLE_BUFFER_MAX_SIZE + 5)
+            .collect();
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&payload);
+        let console = MockConsole::new(&response("RESP_OK", &payload, crc));
+        console_rx_stream(
+            &console,
+            "",
+            record_frame,
+            CONSOLE_BUFFER_MAX_SIZE,
+            false,
+            OtLibCrcKind::IsoHdlc,
+            &regexes,
+            true,
+            1000,
+        )
+        .unwrap();
+        let streamed = STREAMED.with(|streamed| streamed.take());
+        let sizes: Vec<usize> = streamed.iter().map(Vec::len).collect();
+        assert_eq!(
+            sizes,
+            vec![CONSOLE_BUFFER_MAX_SIZE, CONSOLE_BUFFER_MAX_SIZE, 5]
+        );
+        assert_eq!(streamed.concat(), payload);
+
+        let status = OtLibConsoleHandleRxStream(
+            dangling(),
+            dangling(),
+            None,
+            0,
+            false,
+            OtLibCrcKind::IsoHdlc,
+            false,
+            dangling(),
+            dangling(),
+            true,
+            0,
+        );
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn transact_writes_request_then_reads_response() {
+        let regexes = response_regexes("", "", /*binary=*/ true).unwrap();
+        let payload = b"{\"status\":\"ok\"}";
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(payload);
+        let console = MockConsole::new(&response("RESP_OK", payload, crc));
+        let mut frames = [EMPTY_FRAME; 2];
+        let mut num_frames = frames.len();
+        console_transact(
+            &console,
+            b"{\"cmd\":1}",
+            &mut frames,
+            &mut num_frames,
+            CONSOLE_BUFFER_MAX_SIZE,
+            false,
+            OtLibCrcKind::IsoHdlc,
+            &regexes,
+            true,
+            1000,
+        )
+        .unwrap();
+        assert_eq!(console.written(), b"{\"cmd\":1}");
+        assert_eq!(num_frames, 1);
+        assert_eq!(&frames[0].payload[..frames[0].size], payload);
+
+        let status = OtLibConsoleHandleTransact(
+            dangling(),
+            std::ptr::null(),
+            1,
+            dangling(),
+            &mut num_frames,
+            0,
+            false,
+            OtLibCrcKind::IsoHdlc,
+            true,
+            0,
+        );
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn compressed_payload_has_header() {
+        let payload = br#"{"cert":"AAAAAAAAAAAAAAAA"}"#;
+        let framed = compress_payload(payload).unwrap();
+        assert_eq!(framed[0], COMPRESSED_FORMAT_DEFLATE);
+        assert_eq!(&framed[1..5], &(payload.len() as u32).to_le_bytes());
+        let stream_len = (framed.len() - COMPRESSED_HEADER_SIZE) as u32;
+        assert_eq!(
+            &framed[5..COMPRESSED_HEADER_SIZE],
+            &stream_len.to_le_bytes()
+        );
+
+        assert_eq!(&*tx_payload(payload, false).unwrap(), payload);
+        assert_eq!(&*tx_payload(payload, true).unwrap(), &framed[..]);
+    }
+
+    #[test]
+    fn tx_frames_writes_each_frame() {
+        let console = MockConsole::new(b"");
+        let mut frames = [EMPTY_FRAME; 2];
+        frames[0].payload[..3].copy_from_slice(b"{\"a");
+        frames[0].size = 3;
+        frames[1].payload[..2].copy_from_slice(b"\"}");
+        frames[1].size = 2;
+        console_tx_frames(&console, "", &frames, 0).unwrap();
+        assert_eq!(console.written(), b"{\"a\"}");
+    }
+
+    #[test]
+    fn frames_required_rounds_up() {
+        const SIZE: usize = CONSOLE_BUFFER_MAX_SIZE;
+        assert_eq!(frames_required(0, SIZE), 0);
+        assert_eq!(frames_required(1, SIZE), 1);
+        assert_eq!(frames_required(SIZE, SIZE), 1);
+        assert_eq!(frames_required(SIZE + 1, SIZE), 2);
+        assert_eq!(frames_required(3 * SIZE - 1, SIZE), 3);
+        assert_eq!(frames_required(SIZE, 1000), 3);
+    }
+
+    #[test]
+    fn frame_size_defaults_to_and_is_bounded_by_frame_capacity() {
+        assert_eq!(console_frame_size(0).unwrap(), CONSOLE_BUFFER_MAX_SIZE);
+        assert_eq!(console_frame_size(512).unwrap(), 512);
+        let err = console_frame_size(CONSOLE_BUFFER_MAX_SIZE + 1).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn copy_to_frames_uses_frame_size() {
+        let mut spi_frames = [EMPTY_FRAME; 3];
+        let mut num_frames = spi_frames.len();
+        copy_to_frames(b"0123456789", 4, &mut spi_frames, &mut num_frames).unwrap();
+        assert_eq!(num_frames, 3);
+        let sizes: Vec<usize> = spi_frames.iter().map(|frame| frame.size).collect();
+        assert_eq!(sizes, vec![4, 4, 2]);
+        assert_eq!(&spi_frames[2].payload[..2], b"89");
+    }
+
+    #[test]
+    fn copy_to_frames_splits_json() {
+        let empty = DutSpiFrame {
+            payload: [0; CONSOLE_BUFFER_MAX_SIZE],
+            size: 0,
+        };
+        let mut spi_frames = [empty; 3];
+        let json = "x".repeat(CONSOLE_BUFFER_MAX_SIZE + 10);
+        let mut num_frames = spi_frames.len();
+        copy_to_frames(
+            json.as_bytes(),
+            CONSOLE_BUFFER_MAX_SIZE,
+            &mut spi_frames,
+            &mut num_frames,
+        )
+        .unwrap();
+        assert_eq!(num_frames, 2);
+        assert_eq!(spi_frames[0].size, CONSOLE_BUFFER_MAX_SIZE);
+        assert_eq!(spi_frames[1].size, 10);
+        assert_eq!(&spi_frames[1].payload[..10], &json.as_bytes()[..10]);
+        assert_eq!(spi_frames[2].size, 0);
+    }
+
+    #[test]
+    fn copy_to_frames_reports_required_frames() {
+        let empty = DutSpiFrame {
+            payload: [0; CONSOLE_BUFFER_MAX_SIZE],
+            size: 0,
+        };
+        let mut spi_frames = [empty; 1];
+        let json = "x".repeat(2 * CONSOLE_BUFFER_MAX_SIZE + 1);
+        let mut num_frames = spi_frames.len();
+        let err = copy_to_frames(
+            json.as_bytes(),
+            CONSOLE_BUFFER_MAX_SIZE,
+            &mut spi_frames,
+            &mut num_frames,
+        )
+        .unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::BufferTooSmall);
+        assert_eq!(
+            num_frames,
+            frames_required(json.len(), CONSOLE_BUFFER_MAX_SIZE)
+        );
+        assert_eq!(spi_frames[0].size, 0);
+    }
+
+    #[test]
+    fn uds_cert_is_decoded_from_hex_or_base64() {
+        let der = [0x30, 0x82, 0x01, 0xff];
+        assert_eq!(decode_cert("308201ff").unwrap(), der);
+        assert_eq!(decode_cert("308201FF").unwrap(), der);
+        assert_eq!(decode_cert("MIIB/w==").unwrap(), der);
+        for encoded in ["MIIB/w", "0401", "not a cert"] {
+            let err = decode_cert(encoded).unwrap_err();
+            assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::ConsoleError);
+        }
+    }
+
+    #[test]
+    fn copy_bytes_reports_required_length() {
+        let mut buf = [0u8; 4];
+        let mut out_len = 0;
+        copy_bytes(&[1, 2, 3], buf.as_mut_ptr(), buf.len(), &mut out_len).unwrap();
+        assert_eq!((out_len, buf), (3, [1, 2, 3, 0]));
+        let err = copy_bytes(&[9; 5], buf.as_mut_ptr(), buf.len(), &mut out_len).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::BufferTooSmall);
+        assert_eq!((out_len, buf), (5, [1, 2, 3, 0]));
+    }
+
+    #[test]
+    fn capture_group_must_exist() {
+        assert!(check_capture_group("DEVICE_ID: (.*)", 0).is_ok());
+        assert!(check_capture_group("DEVICE_ID: (.*)", 1).is_ok());
+        for (msg, group) in [("DEVICE_ID: (.*)", 2), ("DEVICE_ID: (", 1)] {
+            let err = check_capture_group(msg, group).unwrap_err();
+            assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+        }
+
+        let msg = CString::new("DEVICE_ID: .*").unwrap();
+        let mut buf = [0 as c_char; 8];
+        let mut len = 0;
+        let status = OtLibConsoleWaitForRxCapture(
+            dangling(),
+            OtLibConsoleKind::Spi,
+            dangling(),
+            dangling(),
+            0,
+            OtLibSpiMode::Default,
+            OtLibPullMode::None,
+            false,
+            msg.as_ptr() as *mut c_char,
+            1,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut len,
+            0,
+        );
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn capture_is_truncated_to_buffer() {
+        let mut buf = [0x55 as c_char; 8];
+        let mut len = 0;
+        copy_capture("0123", buf.as_mut_ptr(), buf.len(), &mut len).unwrap();
+        assert_eq!(len, 4);
+        // SAFETY: `buf` was NUL terminated by `copy_capture(...)`.
+        assert_eq!(
+            unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) }.to_bytes(),
+            b"0123"
+        );
+
+        let err = copy_capture("0123456789", buf.as_mut_ptr(), buf.len(), &mut len).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::BufferTooSmall);
+        assert_eq!(len, 10);
+        // SAFETY: `buf` was NUL terminated by `copy_capture(...)`.
+        assert_eq!(
+            unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) }.to_bytes(),
+            b"0123456"
+        );
+    }
+
+    #[test]
+    fn any_of_regex_tracks_the_group_of_each_pattern() {
+        let patterns = [r"PASS", r"FAIL (\d+)", r"ERROR"].map(String::from);
+        let any_of = any_of_regex(&patterns).unwrap();
+        assert_eq!(any_of.regex, r"(PASS)|(FAIL (\d+))|(ERROR)");
+        assert_eq!(any_of.groups, vec![1, 2, 4]);
+
+        let captures = [r"FAIL 3", "", "FAIL 3", "3", ""].map(String::from);
+        assert_eq!(matched_pattern(&any_of, &captures), Some(1));
+        let captures = ["ERROR", "", "", "", "ERROR"].map(String::from);
+        assert_eq!(matched_pattern(&any_of, &captures), Some(2));
+        assert_eq!(matched_pattern(&any_of, &[]), None);
+    }
+
+    #[test]
+    fn any_of_rejects_invalid_patterns() {
+        for patterns in [
+            vec![],
+            vec![String::from("OK"), String::from("(")],
+            vec![String::from("OK"), String::from("")],
+            vec![String::from("OK"), String::from("(ERROR)?")],
+        ] {
+            let err = any_of_regex(&patterns).err().unwrap();
+            assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+        }
+        let pattern = CString::new("(").unwrap();
+        let patterns = [pattern.as_ptr()];
+        let mut index = 0;
+        let status = OtLibConsoleWaitForAnyOf(
+            dangling(),
+            OtLibConsoleKind::Spi,
+            dangling(),
+            dangling(),
+            0,
+            OtLibSpiMode::Default,
+            OtLibPullMode::None,
+            false,
+            patterns.as_ptr(),
+            patterns.len(),
+            &mut index,
+            0,
+        );
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+        let status = OtLibConsoleHandleWaitForAnyOf(dangling(), std::ptr::null(), 0, &mut index, 0);
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn response_regexes_need_payload_and_crc_groups() {
+        for binary in [false, true] {
+            assert!(response_regexes("", "", binary).is_ok());
+            assert!(response_regexes(r"OK:(.*) CRC:(\d+)", r"ERR:(.*) CRC:(\d+)", binary).is_ok());
+            for (success, failure) in [(r"OK:(.*)", ""), ("", r"ERR:(.*) (\d+) (\d+)"), ("(", "")] {
+                let err = response_regexes(success, failure, binary).err().unwrap();
+                assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+            }
+        }
+    }
+
+    #[test]
+    fn tx_frames_rejects_invalid_frame_sizes() {
+        let mut frames = [DutSpiFrame {
+            payload: [0; CONSOLE_BUFFER_MAX_SIZE],
+            size: CONSOLE_BUFFER_MAX_SIZE,
+        }; 2];
+        assert!(check_frames(&frames).is_ok());
+        frames[1].size = 0;
+        let err = check_frames(&frames).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+        frames[1].size = CONSOLE_BUFFER_MAX_SIZE + 1;
+        let err = check_frames(&frames).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+        let status = OtLibConsoleTxFrames(
+            dangling(),
+            OtLibConsoleKind::Spi,
+            dangling(),
+            dangling(),
+            0,
+            OtLibSpiMode::Default,
+            OtLibPullMode::None,
+            false,
+            dangling(),
+            frames.as_ptr(),
+            0,
+            0,
+        );
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn console_open_rejects_null_arguments() {
+        assert!(OtLibConsoleOpen(
+            std::ptr::null(),
+            dangling(),
+            dangling(),
+            0,
+            OtLibSpiMode::Default,
+            OtLibPullMode::None,
+            false,
+        )
+        .is_null());
+        assert!(OtLibConsoleOpen(
+            dangling(),
+            std::ptr::null(),
+            dangling(),
+            0,
+            OtLibSpiMode::Default,
+            OtLibPullMode::None,
+            false,
+        )
+        .is_null());
+        // Closing a null handle is a no-op.
+        OtLibConsoleClose(std::ptr::null_mut());
+    }
+}
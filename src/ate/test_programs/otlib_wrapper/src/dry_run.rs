@@ -0,0 +1,44 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::log::log_info;
+
+/// Set by `OtLibSetDryRun(...)`.
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables the dry-run mode of the whole process, e.g. to lint provisioning recipes
+/// in CI without a DUT.
+///
+/// In dry-run mode the functions that drive the DUT validate their arguments as usual, i.e. check
+/// that the files they are given exist, that tokens have the right size, that regexes compile and
+/// that the pins and interfaces they name are defined by the transport, then return
+/// `OtLibStatus::Ok` without touching the hardware. Their out-parameters are left untouched.
+///
+/// Transports are still created, so that names can be resolved against their configuration.
+#[no_mangle]
+pub extern "C" fn OtLibSetDryRun(enabled: bool) {
+    DRY_RUN.store(enabled, Ordering::Relaxed);
+    log_info!("Dry run {}.", if enabled { "enabled" } else { "disabled" });
+}
+
+/// Whether the calling function should return once its arguments are validated, without
+/// performing its hardware action, see `OtLibSetDryRun(...)`.
+pub(crate) fn dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_dry_run_toggles_dry_run() {
+        OtLibSetDryRun(true);
+        assert!(dry_run());
+        OtLibSetDryRun(false);
+        assert!(!dry_run());
+    }
+}
@@ -0,0 +1,358 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::status::{bail_status, OtLibStatus, StatusContext};
+
+/// Fails with `OtLibStatus::InvalidArgument` if the argument `name` is a null pointer.
+///
+/// All pointer arguments of an FFI function should be checked before any of them is
+/// dereferenced.
+pub(crate) fn check_not_null<T>(ptr: *const T, name: &str) -> Result<()> {
+    if ptr.is_null() {
+        bail_status!(OtLibStatus::InvalidArgument, "`{}` must not be null.", name);
+    }
+    Ok(())
+}
+
+/// Fails with `OtLibStatus::InvalidArgument` if the length argument `name` is zero.
+pub(crate) fn check_not_empty(len: usize, name: &str) -> Result<()> {
+    if len == 0 {
+        bail_status!(OtLibStatus::InvalidArgument, "`{}` must not be zero.", name);
+    }
+    Ok(())
+}
+
+/// Fails with `OtLibStatus::InvalidArgument` if there is no file at `path`, given as the argument
+/// `name`.
+pub(crate) fn check_file_exists(path: &Path, name: &str) -> Result<()> {
+    if !path.is_file() {
+        bail_status!(
+            OtLibStatus::InvalidArgument,
+            "`{}` not found: {:?}.",
+            name,
+            path
+        );
+    }
+    Ok(())
+}
+
+/// Borrows a NUL-terminated C string argument as a `&str`.
+///
+/// Fails with `OtLibStatus::InvalidArgument` if `ptr` is null or the string is not valid UTF-8.
+/// A non-null `ptr` must point to a NUL-terminated string that outlives the returned slice.
+pub(crate) fn cstr_to_str<'a>(ptr: *const c_char) -> Result<&'a str> {
+    check_not_null(ptr, "string")?;
+    // SAFETY: The string must be set by the caller and be NUL-terminated.
+    let cstr = unsafe { CStr::from_ptr(ptr) };
+    cstr.to_str().status(OtLibStatus::InvalidArgument)
+}
+
+/// Copies an array of `len` NUL-terminated C strings into a `Vec<String>`.
+///
+/// `ptr` may be null if `len` is zero. Fails with `OtLibStatus::InvalidArgument` if `ptr` or any
+/// of the strings is null, or if any of the strings is not valid UTF-8.
+pub(crate) fn cstr_array_to_vec(ptr: *const *const c_char, len: usize) -> Result<Vec<String>> {
+    if len == 0 {
+        return Ok(vec![]);
+    }
+    check_not_null(ptr, "string array")?;
+    // SAFETY: The array must be set by the caller and hold `len` string pointers.
+    let strings = unsafe { std::slice::from_raw_parts(ptr, len) };
+    strings
+        .iter()
+        .map(|&s| cstr_to_str(s).map(String::from))
+        .collect()
+}
+
+/// Copies `msg` into the caller's buffer `buf` of `buf_len` bytes.
+///
+/// At most `buf_len - 1` bytes are copied and the result is always NUL terminated. Returns the
+/// length of the full message (excluding the NUL terminator), so a return value `>= buf_len`
+/// indicates the message was truncated. `buf` may be null to only query the length.
+pub(crate) fn copy_to_cstr_buf(msg: &str, buf: *mut c_char, buf_len: usize) -> usize {
+    if !buf.is_null() && buf_len > 0 {
+        let len = msg.len().min(buf_len - 1);
+        // SAFETY: buf should be a valid pointer to `buf_len` bytes allocated by the caller.
+        let buf = unsafe { std::slice::from_raw_parts_mut(buf as *mut u8, buf_len) };
+        buf[..len].copy_from_slice(&msg.as_bytes()[..len]);
+        buf[len] = 0;
+    }
+    msg.len()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::ptr::NonNull;
+
+    use crate::bitstream::OtLibFpgaLoadBitstreamBuf;
+    use crate::boot::OtLibCheckTransportImgBoot;
+    use crate::console::{
+        OtLibConsoleHandleExtractUdsCert, OtLibConsoleHandleRx, OtLibConsoleHandleTx,
+        OtLibConsoleHandleWaitForRx, OtLibConsoleKind, OtLibConsoleRx, OtLibConsoleTx,
+        OtLibCrcKind,
+    };
+    use crate::gpio::{
+        OtLibApplyStrapping, OtLibGpioRead, OtLibGpioWrite, OtLibPinMode, OtLibPullMode,
+        OtLibRemoveStrapping, OtLibSetPinMode,
+    };
+    use crate::otp::OtLibReadOtp;
+    use crate::spi::OtLibSpiMode;
+    use crate::transport::OtLibTransport;
+    use crate::DutSpiFrame;
+
+    /// A non-null pointer that must never be dereferenced, used to check that argument
+    /// validation fails before any pointer is used.
+    pub(crate) fn dangling<T>() -> *mut T {
+        NonNull::dangling().as_ptr()
+    }
+
+    #[test]
+    fn null_pointer_is_invalid_argument() {
+        let err = check_not_null(std::ptr::null::<u8>(), "buf").unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+        assert!(check_not_null(dangling::<u8>(), "buf").is_ok());
+    }
+
+    #[test]
+    fn zero_length_is_invalid_argument() {
+        let err = check_not_empty(0, "len").unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+        assert!(check_not_empty(1, "len").is_ok());
+    }
+
+    #[test]
+    fn cstr_to_str_rejects_invalid_strings() {
+        let err = cstr_to_str(std::ptr::null()).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+
+        let invalid_utf8 = b"\xff\xfe\0";
+        let err = cstr_to_str(invalid_utf8.as_ptr() as *const c_char).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+
+        let valid = b"IOA5\0";
+        assert_eq!(
+            cstr_to_str(valid.as_ptr() as *const c_char).unwrap(),
+            "IOA5"
+        );
+    }
+
+    #[test]
+    fn cstr_array_to_vec_copies_strings() {
+        assert!(cstr_array_to_vec(std::ptr::null(), 0).unwrap().is_empty());
+
+        let strings = [c"a".as_ptr(), c"bc".as_ptr()];
+        assert_eq!(cstr_array_to_vec(strings.as_ptr(), 2).unwrap(), ["a", "bc"]);
+
+        let err = cstr_array_to_vec(std::ptr::null(), 1).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+    }
+
+    /// Calls `OtLibConsoleRx(...)` on a SPI console with the given arguments.
+    fn console_rx(
+        transport: *const OtLibTransport,
+        sync_msg: *mut c_char,
+        spi_frames: *mut DutSpiFrame,
+        num_frames: *mut usize,
+    ) -> OtLibStatus {
+        OtLibConsoleRx(
+            transport,
+            OtLibConsoleKind::Spi,
+            dangling(),
+            dangling(),
+            0,
+            OtLibSpiMode::Default,
+            OtLibPullMode::None,
+            false,
+            sync_msg,
+            spi_frames,
+            num_frames,
+            0,
+            false,
+            OtLibCrcKind::IsoHdlc,
+            false,
+            dangling(),
+            dangling(),
+            true,
+            true,
+            true,
+            0,
+            None,
+        )
+    }
+
+    /// Calls `OtLibConsoleTx(...)` on a SPI console with the given arguments.
+    fn console_tx(
+        transport: *const OtLibTransport,
+        tx_ready_pin: *const c_char,
+        sync_msg: *mut c_char,
+        spi_frame: *mut u8,
+        spi_frame_size: usize,
+    ) -> OtLibStatus {
+        OtLibConsoleTx(
+            transport,
+            OtLibConsoleKind::Spi,
+            dangling(),
+            tx_ready_pin,
+            0,
+            OtLibSpiMode::Default,
+            OtLibPullMode::None,
+            false,
+            sync_msg,
+            spi_frame,
+            spi_frame_size,
+            false,
+            0,
+        )
+    }
+
+    /// Calls an exported function with fixed arguments.
+    type Call = fn() -> OtLibStatus;
+
+    /// Each case passes one invalid argument, and only dangling pointers otherwise, so it must
+    /// fail before any pointer is used.
+    #[test]
+    fn exported_functions_reject_invalid_arguments() {
+        use std::ptr::{null, null_mut};
+
+        let cases: [(&str, Call); 24] = [
+            ("OtLibApplyStrapping name", || {
+                OtLibApplyStrapping(dangling(), null())
+            }),
+            ("OtLibRemoveStrapping name", || {
+                OtLibRemoveStrapping(dangling(), null())
+            }),
+            ("OtLibGpioRead out_level", || {
+                OtLibGpioRead(dangling(), dangling(), null_mut())
+            }),
+            ("OtLibGpioWrite pin", || {
+                OtLibGpioWrite(dangling(), null(), true)
+            }),
+            ("OtLibSetPinMode transport", || {
+                OtLibSetPinMode(null(), dangling(), OtLibPinMode::Input)
+            }),
+            ("OtLibReadOtp partition", || {
+                let name = c"NO_SUCH_PARTITION";
+                OtLibReadOtp(
+                    dangling(),
+                    dangling(),
+                    0,
+                    false,
+                    name.as_ptr(),
+                    dangling(),
+                    0,
+                )
+            }),
+            ("OtLibFpgaLoadBitstreamBuf data", || {
+                OtLibFpgaLoadBitstreamBuf(dangling(), null(), 16, 0, 0, true, None)
+            }),
+            ("OtLibFpgaLoadBitstreamBuf len", || {
+                OtLibFpgaLoadBitstreamBuf(dangling(), dangling(), 0, 0, 0, true, None)
+            }),
+            ("OtLibCheckTransportImgBoot out_result", || {
+                OtLibCheckTransportImgBoot(
+                    dangling(),
+                    dangling(),
+                    0,
+                    dangling(),
+                    dangling(),
+                    null(),
+                    0,
+                    null_mut(),
+                    dangling(),
+                    dangling(),
+                )
+            }),
+            ("OtLibCheckTransportImgBoot out_rom_ext_boot_us", || {
+                OtLibCheckTransportImgBoot(
+                    dangling(),
+                    dangling(),
+                    0,
+                    dangling(),
+                    dangling(),
+                    null(),
+                    0,
+                    dangling(),
+                    null_mut(),
+                    dangling(),
+                )
+            }),
+            ("OtLibConsoleRx transport", || {
+                console_rx(null(), dangling(), dangling(), dangling())
+            }),
+            ("OtLibConsoleRx sync_msg", || {
+                console_rx(dangling(), null_mut(), dangling(), dangling())
+            }),
+            ("OtLibConsoleRx spi_frames", || {
+                console_rx(dangling(), dangling(), null_mut(), dangling())
+            }),
+            ("OtLibConsoleRx num_frames", || {
+                console_rx(dangling(), dangling(), dangling(), null_mut())
+            }),
+            ("OtLibConsoleRx *num_frames", || {
+                console_rx(dangling(), dangling(), dangling(), &mut 0)
+            }),
+            ("OtLibConsoleTx transport", || {
+                console_tx(null(), dangling(), dangling(), dangling(), 1)
+            }),
+            ("OtLibConsoleTx tx_ready_pin", || {
+                console_tx(dangling(), null(), dangling(), dangling(), 1)
+            }),
+            ("OtLibConsoleTx sync_msg", || {
+                console_tx(dangling(), dangling(), null_mut(), dangling(), 1)
+            }),
+            ("OtLibConsoleTx spi_frame", || {
+                console_tx(dangling(), dangling(), dangling(), null_mut(), 1)
+            }),
+            ("OtLibConsoleTx spi_frame_size", || {
+                console_tx(dangling(), dangling(), dangling(), dangling(), 0)
+            }),
+            ("OtLibConsoleHandleWaitForRx handle", || {
+                OtLibConsoleHandleWaitForRx(null(), dangling(), 0, None)
+            }),
+            ("OtLibConsoleHandleRx handle", || {
+                OtLibConsoleHandleRx(
+                    null(),
+                    dangling(),
+                    dangling(),
+                    &mut 1,
+                    0,
+                    false,
+                    OtLibCrcKind::IsoHdlc,
+                    false,
+                    dangling(),
+                    dangling(),
+                    true,
+                    true,
+                    true,
+                    0,
+                    None,
+                )
+            }),
+            ("OtLibConsoleHandleExtractUdsCert uds_cert_regex", || {
+                let regex = c"UDS_CERT: .*";
+                OtLibConsoleHandleExtractUdsCert(
+                    dangling(),
+                    regex.as_ptr(),
+                    dangling(),
+                    1,
+                    dangling(),
+                    0,
+                )
+            }),
+            ("OtLibConsoleHandleTx spi_frame_size", || {
+                OtLibConsoleHandleTx(dangling(), dangling(), dangling(), 0, false, 0)
+            }),
+        ];
+        for (case, call) in cases {
+            assert_eq!(call(), OtLibStatus::InvalidArgument, "{}", case);
+        }
+    }
+}
@@ -0,0 +1,302 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::os::raw::c_char;
+use std::rc::Rc;
+
+use anyhow::{Context, Result};
+
+use opentitanlib::app::{PinStrapping, TransportWrapper};
+use opentitanlib::io::gpio::{GpioPin, PinMode, PullMode};
+
+use crate::dry_run::dry_run;
+use crate::ffi::{check_not_null, cstr_to_str};
+use crate::status::{ffi_call, OtLibStatus, StatusContext};
+use crate::transport::{acquire_transport, OtLibTransport};
+
+/// Looks up the pin strapping `name` in the transport configuration.
+///
+/// A strapping the configuration does not define is reported as `OtLibStatus::InvalidArgument`.
+fn pin_strapping(transport: &TransportWrapper, name: *const c_char) -> Result<PinStrapping> {
+    let name = cstr_to_str(name)?;
+    transport
+        .pin_strapping(name)
+        .with_context(|| format!("Unknown pin strapping: {}.", name))
+        .status(OtLibStatus::InvalidArgument)
+}
+
+#[no_mangle]
+pub extern "C" fn OtLibApplyStrapping(
+    transport: *const OtLibTransport,
+    name: *const c_char,
+) -> OtLibStatus {
+    ffi_call("OtLibApplyStrapping", || {
+        check_not_null(transport, "transport")?;
+        check_not_null(name, "name")?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let strapping = pin_strapping(transport, name)?;
+        if dry_run() {
+            return Ok(());
+        }
+        strapping
+            .apply()
+            .context("Could not apply pin strapping.")
+            .status(OtLibStatus::TransportError)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn OtLibRemoveStrapping(
+    transport: *const OtLibTransport,
+    name: *const c_char,
+) -> OtLibStatus {
+    ffi_call("OtLibRemoveStrapping", || {
+        check_not_null(transport, "transport")?;
+        check_not_null(name, "name")?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let strapping = pin_strapping(transport, name)?;
+        if dry_run() {
+            return Ok(());
+        }
+        strapping
+            .remove()
+            .context("Could not remove pin strapping.")
+            .status(OtLibStatus::TransportError)
+    })
+}
+
+/// Direction and pull of a GPIO pin.
+///
+/// NOTE: must match the definition of `DutPinMode` in src/ate/test_programs/dut_lib/dut_lib.h
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtLibPinMode {
+    /// Input without pull resistor.
+    Input = 0,
+    /// Input with a pull-up resistor.
+    InputPullUp = 1,
+    /// Input with a pull-down resistor.
+    InputPullDown = 2,
+    /// Push-pull output.
+    PushPull = 3,
+    /// Open-drain output with a pull-up resistor.
+    OpenDrain = 4,
+}
+
+impl OtLibPinMode {
+    fn modes(self) -> (PinMode, PullMode) {
+        match self {
+            OtLibPinMode::Input => (PinMode::Input, PullMode::None),
+            OtLibPinMode::InputPullUp => (PinMode::Input, PullMode::PullUp),
+            OtLibPinMode::InputPullDown => (PinMode::Input, PullMode::PullDown),
+            OtLibPinMode::PushPull => (PinMode::PushPull, PullMode::None),
+            OtLibPinMode::OpenDrain => (PinMode::OpenDrain, PullMode::PullUp),
+        }
+    }
+}
+
+/// Pull resistor of a GPIO input.
+///
+/// NOTE: must match the definition of `DutPullMode` in src/ate/test_programs/dut_lib/dut_lib.h
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtLibPullMode {
+    /// No pull resistor.
+    None = 0,
+    /// A pull-up resistor.
+    PullUp = 1,
+    /// A pull-down resistor.
+    PullDown = 2,
+}
+
+impl From<OtLibPullMode> for PullMode {
+    fn from(pull_mode: OtLibPullMode) -> Self {
+        match pull_mode {
+            OtLibPullMode::None => PullMode::None,
+            OtLibPullMode::PullUp => PullMode::PullUp,
+            OtLibPullMode::PullDown => PullMode::PullDown,
+        }
+    }
+}
+
+/// A GPIO pin whose signal is active-low, so that it reads and writes as asserted (true) at a low
+/// level, e.g. a console TX-ready pin the board inverts.
+pub(crate) struct ActiveLowPin(pub(crate) Rc<dyn GpioPin>);
+
+impl GpioPin for ActiveLowPin {
+    fn read(&self) -> Result<bool> {
+        Ok(!self.0.read()?)
+    }
+
+    fn write(&self, value: bool) -> Result<()> {
+        self.0.write(!value)
+    }
+
+    fn set_mode(&self, mode: PinMode) -> Result<()> {
+        self.0.set_mode(mode)
+    }
+
+    fn set_pull_mode(&self, mode: PullMode) -> Result<()> {
+        self.0.set_pull_mode(mode)
+    }
+}
+
+/// Looks up the GPIO pin `pin` in the transport configuration.
+///
+/// A pin the configuration does not define is reported as `OtLibStatus::InvalidArgument`.
+fn gpio_pin(transport: &TransportWrapper, pin: *const c_char) -> Result<Rc<dyn GpioPin>> {
+    let pin = cstr_to_str(pin)?;
+    transport
+        .gpio_pin(pin)
+        .with_context(|| format!("Unknown GPIO pin: {}.", pin))
+        .status(OtLibStatus::InvalidArgument)
+}
+
+#[no_mangle]
+pub extern "C" fn OtLibGpioRead(
+    transport: *const OtLibTransport,
+    pin: *const c_char,
+    out_level: *mut bool,
+) -> OtLibStatus {
+    ffi_call("OtLibGpioRead", || {
+        check_not_null(transport, "transport")?;
+        check_not_null(pin, "pin")?;
+        check_not_null(out_level, "out_level")?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let gpio = gpio_pin(transport, pin)?;
+        if dry_run() {
+            return Ok(());
+        }
+        let level = gpio
+            .read()
+            .context("Could not read GPIO pin.")
+            .status(OtLibStatus::TransportError)?;
+
+        // SAFETY: `out_level` was checked to be non-null and must point to a writable `bool`.
+        unsafe { *out_level = level };
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn OtLibGpioWrite(
+    transport: *const OtLibTransport,
+    pin: *const c_char,
+    level: bool,
+) -> OtLibStatus {
+    ffi_call("OtLibGpioWrite", || {
+        check_not_null(transport, "transport")?;
+        check_not_null(pin, "pin")?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let gpio = gpio_pin(transport, pin)?;
+        if dry_run() {
+            return Ok(());
+        }
+        gpio.write(level)
+            .context("Could not write GPIO pin.")
+            .status(OtLibStatus::TransportError)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn OtLibSetPinMode(
+    transport: *const OtLibTransport,
+    pin: *const c_char,
+    mode: OtLibPinMode,
+) -> OtLibStatus {
+    ffi_call("OtLibSetPinMode", || {
+        check_not_null(transport, "transport")?;
+        check_not_null(pin, "pin")?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        let gpio = gpio_pin(transport, pin)?;
+        if dry_run() {
+            return Ok(());
+        }
+        let (pin_mode, pull_mode) = mode.modes();
+        gpio.set_mode(pin_mode)
+            .context("Unable to set GPIO pin mode.")
+            .status(OtLibStatus::TransportError)?;
+        gpio.set_pull_mode(pull_mode)
+            .context("Unable to set GPIO pull mode.")
+            .status(OtLibStatus::TransportError)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pin that holds the level last written to it.
+    struct MockPin(std::cell::Cell<bool>);
+
+    impl GpioPin for MockPin {
+        fn read(&self) -> Result<bool> {
+            Ok(self.0.get())
+        }
+
+        fn write(&self, value: bool) -> Result<()> {
+            self.0.set(value);
+            Ok(())
+        }
+
+        fn set_mode(&self, _mode: PinMode) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_pull_mode(&self, _mode: PullMode) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn active_low_pin_inverts_the_level() {
+        let pin = Rc::new(MockPin(std::cell::Cell::new(false)));
+        let active_low = ActiveLowPin(pin.clone());
+        assert!(active_low.read().unwrap());
+        active_low.write(false).unwrap();
+        assert!(pin.read().unwrap());
+        assert!(!active_low.read().unwrap());
+        assert_eq!(PullMode::from(OtLibPullMode::PullUp), PullMode::PullUp);
+    }
+
+    #[test]
+    fn pin_modes_map_onto_opentitanlib_modes() {
+        assert_eq!(
+            OtLibPinMode::Input.modes(),
+            (PinMode::Input, PullMode::None)
+        );
+        assert_eq!(
+            OtLibPinMode::InputPullUp.modes(),
+            (PinMode::Input, PullMode::PullUp)
+        );
+        assert_eq!(
+            OtLibPinMode::PushPull.modes(),
+            (PinMode::PushPull, PullMode::None)
+        );
+        assert_eq!(
+            OtLibPinMode::OpenDrain.modes(),
+            (PinMode::OpenDrain, PullMode::PullUp)
+        );
+    }
+}
@@ -0,0 +1,619 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::ops::{Deref, DerefMut};
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::slice;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use opentitanlib::app::TransportWrapper;
+use opentitanlib::io::jtag::{Jtag, JtagParams, JtagTap};
+use opentitanlib::transport::Capability;
+
+use crate::dry_run::dry_run;
+use crate::ffi::{check_not_empty, check_not_null, cstr_to_str};
+use crate::status::{bail_status, defer_teardown_error, ffi_call, OtLibStatus, StatusContext};
+use crate::strap::StrapGuard;
+use crate::transport::{acquire_transport, OtLibTransport};
+
+/// JTAG adapter speed used when the caller passes an `adapter_speed_khz` of zero.
+const DEFAULT_ADAPTER_SPEED_KHZ: u64 = 1000;
+
+/// Name of the RISC-V TAP in the OpenOCD configuration used by opentitanlib.
+const RISCV_TAP_NAME: &str = "riscv.tap";
+
+/// JTAG instruction selecting the IDCODE register of the RISC-V debug transport module.
+const IDCODE_INSTRUCTION: u32 = 0x1;
+
+/// Optional JTAG TAP selection passed in from C.
+///
+/// NOTE: must match the definition of `DutJtagTap` in src/ate/test_programs/dut_lib/dut_lib.h
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtLibJtagTap {
+    /// No TAP.
+    None = 0,
+    /// The life cycle controller TAP.
+    LcTap = 1,
+    /// The RISC-V debug module TAP.
+    RiscvTap = 2,
+}
+
+impl OtLibJtagTap {
+    pub(crate) fn tap(self) -> Option<JtagTap> {
+        match self {
+            OtLibJtagTap::None => None,
+            OtLibJtagTap::LcTap => Some(JtagTap::LcTap),
+            OtLibJtagTap::RiscvTap => Some(JtagTap::RiscvTap),
+        }
+    }
+}
+
+/// Builds the OpenOCD JTAG parameters for the FFI arguments `openocd`, `adapter_speed_khz` and
+/// `jtag_log_stdio`.
+///
+/// `jtag_log_stdio` forwards the OpenOCD output to the test program's output, to diagnose
+/// misbehaving JTAG connections.
+pub(crate) fn jtag_params(
+    openocd: &str,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+) -> JtagParams {
+    JtagParams {
+        openocd: PathBuf::from(openocd),
+        adapter_speed_khz: match adapter_speed_khz {
+            0 => DEFAULT_ADAPTER_SPEED_KHZ,
+            x => u64::from(x),
+        },
+        log_stdio: jtag_log_stdio,
+    }
+}
+
+/// Fails with `OtLibStatus::Unsupported` if `transport` has no JTAG interface, as is the case for
+/// the Ti50 emulator backend.
+pub(crate) fn check_jtag_supported(transport: &TransportWrapper) -> Result<()> {
+    transport
+        .capabilities()
+        .and_then(|capabilities| capabilities.request(Capability::JTAG).ok())
+        .context("JTAG is not supported by this transport.")
+        .status(OtLibStatus::Unsupported)
+}
+
+/// Runs `f` with the transport behind the FFI handle `transport` and the JTAG parameters for
+/// `openocd_path`, `adapter_speed_khz` and `jtag_log_stdio`, the common prologue of the JTAG FFI
+/// functions.
+///
+/// Null `transport` or `openocd_path` arguments are reported as `OtLibStatus::InvalidArgument`, a
+/// transport without JTAG as `OtLibStatus::Unsupported`. In dry-run mode `f` is not called.
+pub(crate) fn with_jtag_transport(
+    transport: *const OtLibTransport,
+    openocd_path: *const c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    f: impl FnOnce(&TransportWrapper, &JtagParams) -> Result<()>,
+) -> Result<()> {
+    check_not_null(transport, "transport")?;
+    check_not_null(openocd_path, "openocd_path")?;
+
+    // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned by
+    // the call to `OtLibFpgaTransportInit(...)`.
+    let guard = unsafe { acquire_transport(transport)? };
+    let transport: &TransportWrapper = &guard;
+    check_jtag_supported(transport)?;
+
+    // Unpack OpenOCD path string.
+    let openocd_path = cstr_to_str(openocd_path)?;
+    let jtag_params = jtag_params(openocd_path, adapter_speed_khz, jtag_log_stdio);
+
+    if dry_run() {
+        return Ok(());
+    }
+
+    f(transport, &jtag_params)
+}
+
+/// A JTAG connection that is closed when the guard is dropped, so that no exit path leaves OpenOCD
+/// holding the adapter for the next call.
+///
+/// A failure to disconnect is a teardown failure, see `defer_teardown_error(...)`: it does not
+/// interrupt the operation, but is reported once the FFI call completes.
+pub(crate) struct JtagGuard<'t>(Option<Box<dyn Jtag + 't>>);
+
+impl<'t> JtagGuard<'t> {
+    pub(crate) fn new(jtag: Box<dyn Jtag + 't>) -> Self {
+        JtagGuard(Some(jtag))
+    }
+
+    /// Closes the connection now, rather than when the guard goes out of scope.
+    pub(crate) fn disconnect(self) {
+        drop(self);
+    }
+
+    /// Hands the connection over to a function that closes it itself.
+    pub(crate) fn into_inner(mut self) -> Box<dyn Jtag + 't> {
+        // The connection is only taken here and on drop.
+        self.0.take().unwrap()
+    }
+}
+
+impl<'t> Deref for JtagGuard<'t> {
+    type Target = dyn Jtag + 't;
+
+    fn deref(&self) -> &Self::Target {
+        // The connection is only taken by `into_inner(...)`, which consumes the guard.
+        self.0.as_deref().unwrap()
+    }
+}
+
+impl DerefMut for JtagGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.as_deref_mut().unwrap()
+    }
+}
+
+impl Drop for JtagGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(jtag) = self.0.take() {
+            defer_teardown_error(jtag.disconnect().context("Could not disconnect from JTAG."));
+        }
+    }
+}
+
+/// Applies the RISC-V TAP straps, resets the DUT if `reset` is set and connects to the RISC-V TAP
+/// to run `f`.
+///
+/// The JTAG connection is closed and the straps are removed even if `f` fails.
+pub(crate) fn with_riscv_tap<T>(
+    transport: &TransportWrapper,
+    jtag_params: &JtagParams,
+    reset: bool,
+    f: impl FnOnce(&mut dyn Jtag) -> Result<T>,
+) -> Result<T> {
+    let riscv_tap = StrapGuard::apply(transport, "PINMUX_TAP_RISCV", "RISC-V TAP")?;
+    if reset {
+        transport
+            .reset_target(Duration::from_millis(50), true)
+            .context("Could not reset chip.")
+            .status(OtLibStatus::TransportError)?;
+    }
+    let mut jtag = jtag_params
+        .create(transport)
+        .and_then(|chain| chain.connect(JtagTap::RiscvTap))
+        .context("Could not connect to RISC-V TAP.")
+        .status(OtLibStatus::JtagError)
+        .map(JtagGuard::new)?;
+    let value = f(&mut *jtag)?;
+    jtag.disconnect();
+    riscv_tap.remove();
+    Ok(value)
+}
+
+/// Parses the hexadecimal `output` of an OpenOCD command reading `what`.
+fn parse_hex_u32(output: &str, what: &str) -> Result<u32> {
+    let output = output.trim();
+    u32::from_str_radix(output.strip_prefix("0x").unwrap_or(output), 16)
+        .with_context(|| format!("Unexpected {} read result `{}`.", what, output))
+        .status(OtLibStatus::JtagError)
+}
+
+/// Parses the hexadecimal output of an OpenOCD `drscan` of the IDCODE register.
+fn parse_idcode(scan: &str) -> Result<u32> {
+    let idcode = parse_hex_u32(scan, "IDCODE")?;
+    // IEEE 1149.1 requires the LSB of an IDCODE to be set, a cleared bit means the scan did not
+    // go through the IDCODE register.
+    if idcode & 1 == 0 {
+        bail_status!(
+            OtLibStatus::JtagError,
+            "Invalid IDCODE {:#010x}, the LSB is not set.",
+            idcode
+        );
+    }
+    Ok(idcode)
+}
+
+/// Scans the IDCODE register of the connected RISC-V TAP.
+fn read_idcode(jtag: &mut dyn Jtag) -> Result<u32> {
+    let openocd = jtag.as_raw().status(OtLibStatus::JtagError)?;
+    openocd
+        .execute(&format!(
+            "irscan {} {:#x}",
+            RISCV_TAP_NAME, IDCODE_INSTRUCTION
+        ))
+        .context("Could not select the IDCODE register.")
+        .status(OtLibStatus::JtagError)?;
+    let scan = openocd
+        .execute(&format!("drscan {} 32 0", RISCV_TAP_NAME))
+        .context("Could not scan the IDCODE register.")
+        .status(OtLibStatus::JtagError)?;
+    parse_idcode(&scan)
+}
+
+/// Reads the JTAG IDCODE of the DUT over the RISC-V TAP.
+#[no_mangle]
+pub extern "C" fn OtLibReadJtagIdcode(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    out_idcode: *mut u32,
+) -> OtLibStatus {
+    ffi_call("OtLibReadJtagIdcode", || {
+        check_not_null(out_idcode, "out_idcode")?;
+
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                let idcode =
+                    with_riscv_tap(transport, jtag_params, /*reset=*/ true, read_idcode)?;
+
+                // SAFETY: `out_idcode` was checked to be non-null and must point to a writable
+                // `u32`.
+                unsafe { *out_idcode = idcode };
+                Ok(())
+            },
+        )
+    })
+}
+
+/// Reads the debug module interface register at `addr` of the connected RISC-V TAP.
+fn read_dmi_reg(jtag: &mut dyn Jtag, addr: u32) -> Result<u32> {
+    let output = jtag
+        .as_raw()
+        .status(OtLibStatus::JtagError)?
+        .execute(&format!("riscv dmi_read {:#x}", addr))
+        .with_context(|| format!("Could not read DMI register {:#x}.", addr))
+        .status(OtLibStatus::JtagError)?;
+    parse_hex_u32(&output, "DMI register")
+}
+
+/// Writes `value` to the debug module interface register at `addr` of the connected RISC-V TAP.
+fn write_dmi_reg(jtag: &mut dyn Jtag, addr: u32, value: u32) -> Result<()> {
+    jtag.as_raw()
+        .status(OtLibStatus::JtagError)?
+        .execute(&format!("riscv dmi_write {:#x} {:#x}", addr, value))
+        .with_context(|| format!("Could not write DMI register {:#x}.", addr))
+        .status(OtLibStatus::JtagError)?;
+    Ok(())
+}
+
+/// Reads the RISC-V debug module interface register at `addr` over the RISC-V TAP, without
+/// resetting the DUT.
+///
+/// CSRs and GPRs are reachable through the abstract command registers of the debug module, see
+/// the RISC-V Debug Specification. As the DUT is not reset, the debug module state is kept across
+/// calls, e.g. for a read-modify-write, but the RISC-V TAP strapping must already have been latched
+/// by an earlier reset, e.g. with `OtLibApplyStrapping(...)` of "PINMUX_TAP_RISCV" followed by
+/// `OtLibResetTarget(...)`.
+#[no_mangle]
+pub extern "C" fn OtLibJtagReadReg(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    addr: u32,
+    out: *mut u32,
+) -> OtLibStatus {
+    ffi_call("OtLibJtagReadReg", || {
+        check_not_null(out, "out")?;
+
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                let value =
+                    with_riscv_tap(transport, jtag_params, /*reset=*/ false, |jtag| {
+                        read_dmi_reg(jtag, addr)
+                    })?;
+
+                // SAFETY: `out` was checked to be non-null and must point to a writable `u32`.
+                unsafe { *out = value };
+                Ok(())
+            },
+        )
+    })
+}
+
+/// Writes `val` to the RISC-V debug module interface register at `addr` over the RISC-V TAP,
+/// without resetting the DUT.
+///
+/// Like for `OtLibJtagReadReg(...)`, the RISC-V TAP strapping must already have been latched.
+#[no_mangle]
+pub extern "C" fn OtLibJtagWriteReg(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    addr: u32,
+    val: u32,
+) -> OtLibStatus {
+    ffi_call("OtLibJtagWriteReg", || {
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                with_riscv_tap(transport, jtag_params, /*reset=*/ false, |jtag| {
+                    write_dmi_reg(jtag, addr, val)
+                })
+            },
+        )
+    })
+}
+
+/// Size in bytes of the accesses made by `OtLibJtagMemRead(...)` and `OtLibJtagMemWrite(...)`.
+const WORD_SIZE: usize = std::mem::size_of::<u32>();
+
+/// Fails with `OtLibStatus::InvalidArgument` unless `addr` and `len` are non-zero multiples of the
+/// 32-bit word size.
+fn check_word_aligned(addr: u32, len: usize) -> Result<()> {
+    check_not_empty(len, "len")?;
+    if !(addr as usize).is_multiple_of(WORD_SIZE) || !len.is_multiple_of(WORD_SIZE) {
+        bail_status!(
+            OtLibStatus::InvalidArgument,
+            "Memory access of {} bytes at {:#x} is not {}-byte aligned.",
+            len,
+            addr,
+            WORD_SIZE
+        );
+    }
+    Ok(())
+}
+
+/// Halts the CPU and reads `buf.len()` bytes of DUT memory at `addr` into `buf`.
+fn read_memory(jtag: &mut dyn Jtag, addr: u32, buf: &mut [u8]) -> Result<()> {
+    jtag.halt()
+        .context("Could not halt the CPU.")
+        .status(OtLibStatus::JtagError)?;
+    let mut words = vec![0u32; buf.len() / WORD_SIZE];
+    let read = jtag
+        .read_memory32(addr, &mut words)
+        .with_context(|| format!("Could not read memory at {:#x}.", addr))
+        .status(OtLibStatus::JtagError)?;
+    if read != words.len() {
+        bail_status!(
+            OtLibStatus::JtagError,
+            "Read {} of {} words at {:#x}.",
+            read,
+            words.len(),
+            addr
+        );
+    }
+    for (chunk, word) in buf.chunks_exact_mut(WORD_SIZE).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    Ok(())
+}
+
+/// Halts the CPU and writes `buf` to DUT memory at `addr`.
+fn write_memory(jtag: &mut dyn Jtag, addr: u32, buf: &[u8]) -> Result<()> {
+    jtag.halt()
+        .context("Could not halt the CPU.")
+        .status(OtLibStatus::JtagError)?;
+    let words: Vec<u32> = buf
+        .chunks_exact(WORD_SIZE)
+        .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect();
+    jtag.write_memory32(addr, &words)
+        .with_context(|| format!("Could not write memory at {:#x}.", addr))
+        .status(OtLibStatus::JtagError)
+}
+
+/// Halts the CPU over the RISC-V TAP without resetting the DUT and reads `len` bytes of memory at
+/// `addr` into `buf`, e.g. to validate what an SRAM program wrote.
+///
+/// `addr` and `len` must be multiples of 4, the memory is read in 32-bit words and stored little
+/// endian. Like for `OtLibCpuHalt(...)`, the CPU stays halted afterwards, and like for
+/// `OtLibJtagReadReg(...)`, the RISC-V TAP strapping must already have been latched.
+#[no_mangle]
+pub extern "C" fn OtLibJtagMemRead(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    addr: u32,
+    buf: *mut u8,
+    len: usize,
+) -> OtLibStatus {
+    ffi_call("OtLibJtagMemRead", || {
+        check_not_null(buf, "buf")?;
+        check_word_aligned(addr, len)?;
+
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                // SAFETY: `buf` was checked to be non-null and must point to `len` writable bytes.
+                let buf = unsafe { slice::from_raw_parts_mut(buf, len) };
+                with_riscv_tap(transport, jtag_params, /*reset=*/ false, |jtag| {
+                    read_memory(jtag, addr, buf)
+                })
+            },
+        )
+    })
+}
+
+/// Halts the CPU over the RISC-V TAP without resetting the DUT and writes the `len` bytes of `buf`
+/// to memory at `addr`.
+///
+/// `addr` and `len` must be multiples of 4, `buf` is written in little endian 32-bit words. As for
+/// `OtLibJtagMemRead(...)`, the CPU stays halted and the strapping must already have been latched.
+#[no_mangle]
+pub extern "C" fn OtLibJtagMemWrite(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    addr: u32,
+    buf: *const u8,
+    len: usize,
+) -> OtLibStatus {
+    ffi_call("OtLibJtagMemWrite", || {
+        check_not_null(buf, "buf")?;
+        check_word_aligned(addr, len)?;
+
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                // SAFETY: `buf` was checked to be non-null and must point to `len` readable bytes.
+                let buf = unsafe { slice::from_raw_parts(buf, len) };
+                with_riscv_tap(transport, jtag_params, /*reset=*/ false, |jtag| {
+                    write_memory(jtag, addr, buf)
+                })
+            },
+        )
+    })
+}
+
+/// Halts the CPU over the RISC-V TAP without resetting the DUT.
+///
+/// The JTAG session is not kept between calls: the connection is closed and the RISC-V TAP straps
+/// are removed before returning, and the CPU stays halted until `OtLibCpuResume(...)` or the next
+/// reset of the DUT.
+#[no_mangle]
+pub extern "C" fn OtLibCpuHalt(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+) -> OtLibStatus {
+    ffi_call("OtLibCpuHalt", || {
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                with_riscv_tap(transport, jtag_params, /*reset=*/ false, |jtag| {
+                    jtag.halt()
+                        .context("Could not halt the CPU.")
+                        .status(OtLibStatus::JtagError)
+                })
+            },
+        )
+    })
+}
+
+/// Resumes the CPU over the RISC-V TAP without resetting the DUT.
+///
+/// Like `OtLibCpuHalt(...)`, this establishes a new JTAG session and closes it before returning.
+#[no_mangle]
+pub extern "C" fn OtLibCpuResume(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+) -> OtLibStatus {
+    ffi_call("OtLibCpuResume", || {
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                with_riscv_tap(transport, jtag_params, /*reset=*/ false, |jtag| {
+                    jtag.resume()
+                        .context("Could not resume the CPU.")
+                        .status(OtLibStatus::JtagError)
+                })
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::ffi::tests::dangling;
+
+    #[test]
+    fn zero_adapter_speed_uses_default() {
+        assert_eq!(jtag_params("openocd", 0, false).adapter_speed_khz, 1000);
+        assert_eq!(jtag_params("openocd", 200, false).adapter_speed_khz, 200);
+    }
+
+    #[test]
+    fn jtag_log_stdio_reaches_jtag_params() {
+        assert!(!jtag_params("openocd", 0, false).log_stdio);
+        assert!(jtag_params("openocd", 0, true).log_stdio);
+    }
+
+    #[test]
+    fn jtag_taps_map_onto_opentitanlib_taps() {
+        assert_eq!(OtLibJtagTap::None.tap(), None);
+        assert_eq!(OtLibJtagTap::LcTap.tap(), Some(JtagTap::LcTap));
+        assert_eq!(OtLibJtagTap::RiscvTap.tap(), Some(JtagTap::RiscvTap));
+    }
+
+    #[test]
+    fn idcode_scans_are_parsed() {
+        assert_eq!(parse_idcode("10001cdf\n").unwrap(), 0x10001cdf);
+        assert_eq!(parse_idcode("0x10001cdf").unwrap(), 0x10001cdf);
+        assert!(parse_idcode("00000000").is_err());
+        assert!(parse_idcode("invalid").is_err());
+    }
+
+    #[test]
+    fn dmi_reads_are_parsed() {
+        assert_eq!(parse_hex_u32("0x00000c03", "DMI register").unwrap(), 0xc03);
+        assert!(parse_hex_u32("", "DMI register").is_err());
+    }
+
+    #[test]
+    fn jtag_read_reg_rejects_null_out() {
+        let status = OtLibJtagReadReg(dangling(), dangling(), 0, false, 0x11, std::ptr::null_mut());
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn read_jtag_idcode_rejects_null_out_idcode() {
+        let status = OtLibReadJtagIdcode(dangling(), dangling(), 0, false, std::ptr::null_mut());
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn memory_accesses_must_be_word_aligned() {
+        assert!(check_word_aligned(0x1000_0000, 8).is_ok());
+        for (addr, len) in [(0x1000_0002, 8), (0x1000_0000, 6), (0x1000_0000, 0)] {
+            let status = OtLibJtagMemRead(dangling(), dangling(), 0, false, addr, dangling(), len);
+            assert_eq!(status, OtLibStatus::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn jtag_transport_rejects_null_handles_without_running_f() {
+        for (transport, openocd_path) in [
+            (std::ptr::null_mut(), dangling()),
+            (dangling(), std::ptr::null_mut()),
+        ] {
+            let err = with_jtag_transport(transport, openocd_path, 0, false, |_, _| {
+                panic!("`f` must not run without a transport")
+            })
+            .unwrap_err();
+            assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn cpu_halt_and_resume_reject_null_openocd_path() {
+        let status = OtLibCpuHalt(dangling(), std::ptr::null_mut(), 0, false);
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+        let status = OtLibCpuResume(dangling(), std::ptr::null_mut(), 0, false);
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+}
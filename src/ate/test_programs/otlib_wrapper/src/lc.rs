@@ -0,0 +1,1247 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::os::raw::c_char;
+use std::slice;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use zeroize::Zeroizing;
+
+use cp_lib::reset_and_lock;
+use opentitanlib::app::TransportWrapper;
+use opentitanlib::dif::lc_ctrl::{DifLcCtrlState, LcCtrlReg};
+use opentitanlib::io::jtag::{Jtag, JtagParams, JtagTap};
+use opentitanlib::test_utils::lc_transition::{trigger_lc_transition, trigger_volatile_raw_unlock};
+use util_lib::hash_lc_token;
+
+use crate::ffi::{check_not_null, cstr_to_str};
+use crate::jtag::{with_jtag_transport, JtagGuard, OtLibJtagTap};
+use crate::log::log_warn;
+use crate::metrics::{OpTimer, OtLibOp};
+use crate::status::{bail_status, ffi_call, OtLibStatus, StatusContext};
+use crate::strap::{StrapGuard, Straps};
+use crate::transport::OtLibTransport;
+use crate::DutSpiFrame;
+
+/// Resets the DUT with the CPU TAP strapped and locks it, transitioning it from TEST_UNLOCKED0 to
+/// TEST_LOCKED0.
+///
+/// Both the JTAG connection and `reset_and_lock(...)` use `adapter_speed_khz`, zero selecting
+/// `DEFAULT_ADAPTER_SPEED_KHZ`; lower it for harnesses with long JTAG cables.
+#[no_mangle]
+pub extern "C" fn OtLibResetAndLock(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+) -> OtLibStatus {
+    ffi_call("OtLibResetAndLock", || {
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                // Set CPU TAP straps, reset and lock the chip.
+                reset_and_lock(transport, jtag_params, Duration::from_millis(50))
+                    .context("Failed to lock the DUT.")
+                    .status(OtLibStatus::LcTransitionError)
+            },
+        )
+    })
+}
+
+/// Performs the strap and reset portion of `OtLibResetAndLock(...)` without locking the DUT:
+/// applies the CPU TAP straps, resets the DUT holding reset for `reset_delay_ms` milliseconds, and
+/// checks that the RISC-V TAP can be connected to.
+///
+/// The CPU TAP straps are left applied on success, so that the DUT stays as the lock would find
+/// it, e.g. for one more JTAG read before `OtLibResetAndLock(...)`; they are removed on failure,
+/// or by the next function using the RISC-V TAP.
+#[no_mangle]
+pub extern "C" fn OtLibResetNoLock(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    reset_delay_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibResetNoLock", || {
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                // Set CPU TAP straps and reset the chip, as `reset_and_lock(...)` does before
+                // locking it.
+                let riscv_tap = StrapGuard::apply(transport, "PINMUX_TAP_RISCV", "RISC-V TAP")?;
+                transport
+                    .reset_target(Duration::from_millis(reset_delay_ms), true)
+                    .context("Could not reset chip.")
+                    .status(OtLibStatus::TransportError)?;
+                jtag_params
+                    .create(transport)
+                    .and_then(|chain| chain.connect(JtagTap::RiscvTap))
+                    .context("Could not connect to RISC-V TAP.")
+                    .status(OtLibStatus::JtagError)
+                    .map(JtagGuard::new)?
+                    .disconnect();
+                riscv_tap.keep();
+                Ok(())
+            },
+        )
+    })
+}
+
+/// Size in bytes of an LC transition token.
+const LC_TOKEN_SIZE: usize = 16;
+
+// Time the DUT is held in reset to latch the LC TAP straps.
+const LC_RESET_DELAY: Duration = Duration::from_millis(50);
+
+/// Unpacks the `token_size` bytes at `token` into the words of an LC transition token, hashing
+/// them with `hash_lc_token(...)` first if `token_is_hashed` is set.
+///
+/// A `token_size` of 0 is for transitions that do not require a token, and cannot be hashed; any
+/// other size than `LC_TOKEN_SIZE` is reported as `OtLibStatus::InvalidArgument`. The words are
+/// wiped when the returned token is dropped, so pass it on by reference. Only the copies that
+/// opentitanlib takes by value, when the token is finally written to the LC controller, are out
+/// of reach and left unwiped.
+fn lc_token(
+    token: *const u8,
+    token_size: usize,
+    token_is_hashed: bool,
+) -> Result<Option<Zeroizing<[u32; 4]>>> {
+    match token_size {
+        0 if token_is_hashed => bail_status!(
+            OtLibStatus::InvalidArgument,
+            "A hashed LC token must be {} bytes long, got none.",
+            LC_TOKEN_SIZE
+        ),
+        0 => return Ok(None),
+        LC_TOKEN_SIZE => check_not_null(token, "token")?,
+        _ => bail_status!(
+            OtLibStatus::InvalidArgument,
+            "LC token must be 0 or {} bytes long, got {} bytes.",
+            LC_TOKEN_SIZE,
+            token_size
+        ),
+    }
+
+    // SAFETY: `token` was checked to be non-null and must point to `LC_TOKEN_SIZE` readable
+    // bytes.
+    let token_bytes = unsafe { slice::from_raw_parts(token, LC_TOKEN_SIZE) };
+    if token_is_hashed {
+        let hashed = hash_lc_token(token_bytes).context("Could not hash the LC token.")?;
+        let words = hashed
+            .into_inner()
+            .map_err(|_| anyhow!("Hashed LC token is not {} bytes long.", LC_TOKEN_SIZE))?;
+        return Ok(Some(Zeroizing::new(words)));
+    }
+    let mut words = Zeroizing::new([0u32; 4]);
+    for (word, bytes) in words.iter_mut().zip(token_bytes.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    Ok(Some(words))
+}
+
+/// Parses the hex string `token_hex`, of 0 or `2 * LC_TOKEN_SIZE` digits, into the words of an LC
+/// transition token, like `lc_token(...)` does for the bytes the string encodes.
+///
+/// The digits encode the token bytes in order, e.g. `000102...` is the token `[0x00, 0x01, ...]`.
+fn lc_token_from_hex(
+    token_hex: &str,
+    token_is_hashed: bool,
+) -> Result<Option<Zeroizing<[u32; 4]>>> {
+    if !token_hex.is_empty() && token_hex.len() != 2 * LC_TOKEN_SIZE {
+        bail_status!(
+            OtLibStatus::InvalidArgument,
+            "LC token must be 0 or {} hex digits long, got {} characters.",
+            2 * LC_TOKEN_SIZE,
+            token_hex.len()
+        );
+    }
+    if !token_hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        bail_status!(
+            OtLibStatus::InvalidArgument,
+            "LC token is not a hex string."
+        );
+    }
+    let mut token = Zeroizing::new([0u8; LC_TOKEN_SIZE]);
+    for (byte, digits) in token.iter_mut().zip(token_hex.as_bytes().chunks_exact(2)) {
+        // The digits were checked above, so they are valid UTF-8 and parse.
+        *byte = u8::from_str_radix(std::str::from_utf8(digits)?, 16)?;
+    }
+    lc_token(token.as_ptr(), token_hex.len() / 2, token_is_hashed)
+}
+
+/// Reads the raw encoding of the LC state over `jtag`, which must be connected to the LC TAP.
+fn read_lc_state(jtag: &mut dyn Jtag) -> Result<u32> {
+    jtag.read_lc_ctrl_reg(&LcCtrlReg::LcState)
+        .context("Could not read the LC state.")
+        .status(OtLibStatus::JtagError)
+}
+
+/// The straps applied to connect to the LC TAP, which are removed when dropped, see
+/// `apply_lc_tap_straps(...)`.
+struct LcTapStraps<'t, T: Straps + ?Sized = TransportWrapper> {
+    // Dropped in declaration order, the reverse of the order the straps are applied in.
+    lc_tap: StrapGuard<'t, T>,
+    bootstrap: StrapGuard<'t, T>,
+}
+
+impl<T: Straps + ?Sized> LcTapStraps<'_, T> {
+    /// Removes the straps now, rather than when they go out of scope.
+    fn remove(self) {
+        drop(self);
+    }
+
+    /// Leaves the straps applied after they are gone.
+    fn keep(self) {
+        self.lc_tap.keep();
+        self.bootstrap.keep();
+    }
+}
+
+/// Applies the straps to connect to the LC TAP.
+fn apply_lc_tap_straps<T: Straps + ?Sized>(straps: &T) -> Result<LcTapStraps<'_, T>> {
+    let bootstrap = StrapGuard::apply(straps, "ROM_BOOTSTRAP", "bootstrap")?;
+    let lc_tap = StrapGuard::apply(straps, "PINMUX_TAP_LC", "LC TAP")?;
+    Ok(LcTapStraps { lc_tap, bootstrap })
+}
+
+/// Applies the straps to connect to the LC TAP and resets the DUT to latch them.
+fn apply_lc_transition_straps(transport: &TransportWrapper) -> Result<LcTapStraps<'_>> {
+    let straps = apply_lc_tap_straps(transport)?;
+    transport
+        .reset_target(LC_RESET_DELAY, true)
+        .context("Could not reset chip.")
+        .status(OtLibStatus::TransportError)?;
+    Ok(straps)
+}
+
+/// Connects to the LC TAP, which must be strapped, see `apply_lc_transition_straps(...)`.
+fn connect_lc_tap<'t>(
+    transport: &'t TransportWrapper,
+    jtag_params: &JtagParams,
+) -> Result<JtagGuard<'t>> {
+    jtag_params
+        .create(transport)
+        .and_then(|chain| chain.connect(JtagTap::LcTap))
+        .context("Could not connect to LC TAP.")
+        .status(OtLibStatus::JtagError)
+        .map(JtagGuard::new)
+}
+
+/// Transitions the DUT to `lc_state` over the LC TAP, as described at `OtLibLcTransition(...)`,
+/// and writes the LC state read back to `out_state`.
+#[allow(clippy::too_many_arguments)]
+fn lc_transition(
+    transport: &TransportWrapper,
+    jtag_params: &JtagParams,
+    lc_token: Option<&Zeroizing<[u32; 4]>>,
+    lc_state: DifLcCtrlState,
+    use_external_clk: bool,
+    force: bool,
+    reset_tap_straps: OtLibJtagTap,
+    out_state: &mut u32,
+) -> Result<()> {
+    // Connect to LC TAP. The straps are removed on every exit path.
+    let straps = apply_lc_transition_straps(transport)?;
+    let mut jtag = connect_lc_tap(transport, jtag_params)?;
+
+    // Re-triggering a transition into the current state can fault the DUT.
+    let state = read_lc_state(&mut *jtag)?;
+    if state == lc_state.redundant_encoding() && !force {
+        log_warn!(
+            "DUT is already in LC state {:#x}, skipping the transition.",
+            state
+        );
+        *out_state = state;
+        jtag.disconnect();
+        straps.remove();
+        return Ok(());
+    }
+
+    trigger_lc_transition(
+        transport,
+        jtag.into_inner(),
+        lc_state,
+        // `trigger_lc_transition(...)` takes its own copy of the token by value, which cannot be
+        // wiped.
+        lc_token.map(|token| **token),
+        use_external_clk,
+        LC_RESET_DELAY,
+        reset_tap_straps.tap(),
+    )
+    .context("Could not perform LC transition.")
+    .status(OtLibStatus::LcTransitionError)?;
+    if reset_tap_straps != OtLibJtagTap::LcTap {
+        // The chip may be about to run ROM, so the LC state cannot be read back.
+        straps.remove();
+        return Ok(());
+    }
+
+    // Check that LC state has transitioned to the target state.
+    jtag = connect_lc_tap(transport, jtag_params)?;
+    let state = read_lc_state(&mut *jtag)?;
+    *out_state = state;
+
+    jtag.disconnect();
+    straps.remove();
+
+    if state != lc_state.redundant_encoding() {
+        bail_status!(
+            OtLibStatus::LcMismatch,
+            "LC state ({:#x}) does not match the target state ({:#x}).",
+            state,
+            lc_state.redundant_encoding()
+        );
+    }
+    Ok(())
+}
+
+/// Walks the DUT through the LC states of `steps` in order, as described at
+/// `OtLibLcTransitionSequence(...)`, writing the index of the current step to `out_step` and the
+/// LC state read back after it to `out_state`.
+fn lc_transition_sequence(
+    transport: &TransportWrapper,
+    jtag_params: &JtagParams,
+    steps: &[(DifLcCtrlState, Option<Zeroizing<[u32; 4]>>)],
+    use_external_clk: bool,
+    out_step: &mut usize,
+    out_state: &mut u32,
+) -> Result<()> {
+    let straps = apply_lc_transition_straps(transport)?;
+    for (step, (lc_state, lc_token)) in steps.iter().enumerate() {
+        *out_step = step;
+        let mut jtag = connect_lc_tap(transport, jtag_params)?;
+        let state = read_lc_state(&mut *jtag)?;
+        if state == lc_state.redundant_encoding() {
+            log_warn!(
+                "DUT is already in LC state {:#x}, skipping step {}.",
+                state,
+                step
+            );
+            *out_state = state;
+            jtag.disconnect();
+            continue;
+        }
+
+        // Keep the LC TAP strapped, so that the next step can reconnect to it.
+        trigger_lc_transition(
+            transport,
+            jtag.into_inner(),
+            *lc_state,
+            // `trigger_lc_transition(...)` takes its own copy of the token by value, which cannot
+            // be wiped.
+            lc_token.as_ref().map(|token| **token),
+            use_external_clk,
+            LC_RESET_DELAY,
+            Some(JtagTap::LcTap),
+        )
+        .with_context(|| format!("Could not perform LC transition step {}.", step))
+        .status(OtLibStatus::LcTransitionError)?;
+
+        jtag = connect_lc_tap(transport, jtag_params)?;
+        let state = read_lc_state(&mut *jtag)?;
+        *out_state = state;
+        jtag.disconnect();
+        if state != lc_state.redundant_encoding() {
+            bail_status!(
+                OtLibStatus::LcMismatch,
+                "LC state ({:#x}) after step {} does not match its target state ({:#x}).",
+                state,
+                step,
+                lc_state.redundant_encoding()
+            );
+        }
+    }
+    *out_step = steps.len();
+    straps.remove();
+    Ok(())
+}
+
+/// Runs `lc_transition(...)` for one of the LC transition FFI functions, once they have checked
+/// `out_state` and unpacked `lc_token`.
+#[allow(clippy::too_many_arguments)]
+fn lc_transition_ffi(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    lc_token: Option<Zeroizing<[u32; 4]>>,
+    lc_state: DifLcCtrlState,
+    use_external_clk: bool,
+    force: bool,
+    reset_tap_straps: OtLibJtagTap,
+    out_state: *mut u32,
+) -> Result<()> {
+    with_jtag_transport(
+        transport,
+        openocd_path,
+        adapter_speed_khz,
+        jtag_log_stdio,
+        |transport, jtag_params| {
+            // SAFETY: `out_state` was checked to be non-null and must point to a writable `u32`.
+            let out_state = unsafe { &mut *out_state };
+            lc_transition(
+                transport,
+                jtag_params,
+                lc_token.as_ref(),
+                lc_state,
+                use_external_clk,
+                force,
+                reset_tap_straps,
+                out_state,
+            )
+        },
+    )
+}
+
+/// Transitions the DUT to `target_lc_state` and writes the LC state read back afterwards to
+/// `out_state`.
+///
+/// If the DUT is already in `target_lc_state` the transition is skipped, unless `force` is set.
+///
+/// `reset_tap_straps` selects the TAP strapped after the transition, if any. The LC state is only
+/// read back and checked when it is `OtLibJtagTap::LcTap`; for transitions into states with ROM
+/// execution enabled, reconnecting to the LC TAP would reset the chip, so the caller should select
+/// another TAP or none, in which case `out_state` is left untouched.
+/// `token` is written to the LC controller, which compares its hash against the hashed tokens
+/// provisioned into OTP. The transitions out of RAW and TEST_LOCKED*, as well as those into
+/// DEV, PROD, PROD_END and RMA, take a `LC_TOKEN_SIZE` byte token; the others, e.g. into SCRAP or
+/// TEST_LOCKED*, take none (a `token_size` of 0). Pass the raw token, as held by the
+/// manufacturer, unless the DUT expects the hashed form of it, i.e. the value stored in OTP, in
+/// which case set `token_is_hashed` to hash it with `hash_lc_token(...)` before it is written.
+///
+/// `use_external_clk` should only be set for transitions early in the flow, e.g. out of RAW or
+/// into TEST_UNLOCKED*, where AST is not calibrated yet and the internal clock cannot be relied
+/// upon. Transitions after AST calibration should leave it unset.
+#[no_mangle]
+pub extern "C" fn OtLibLcTransition(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    token: *const u8,
+    token_size: usize,
+    token_is_hashed: bool,
+    target_lc_state: u32,
+    use_external_clk: bool,
+    force: bool,
+    reset_tap_straps: OtLibJtagTap,
+    out_state: *mut u32,
+) -> OtLibStatus {
+    ffi_call("OtLibLcTransition", || {
+        let _timer = OpTimer::start(OtLibOp::LcTransition);
+        check_not_null(out_state, "out_state")?;
+        // A null token is only allowed for transitions that do not require one.
+        // The token is wiped when it goes out of scope, on both the success and error paths.
+        let lc_token = lc_token(token, token_size, token_is_hashed)?;
+        lc_transition_ffi(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            lc_token,
+            DifLcCtrlState(target_lc_state),
+            use_external_clk,
+            force,
+            reset_tap_straps,
+            out_state,
+        )
+    })
+}
+
+/// Same as `OtLibLcTransition(...)`, with the token given as the NUL-terminated hex string
+/// `token_hex`, as stored by provisioning recipes, instead of raw bytes.
+///
+/// `token_hex` must be empty, for transitions that do not require a token, or `2 * LC_TOKEN_SIZE`
+/// hex digits encoding the token bytes in order; anything else is reported as
+/// `OtLibStatus::InvalidArgument`.
+#[no_mangle]
+pub extern "C" fn OtLibLcTransitionHex(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    token_hex: *const c_char,
+    token_is_hashed: bool,
+    target_lc_state: u32,
+    use_external_clk: bool,
+    force: bool,
+    reset_tap_straps: OtLibJtagTap,
+    out_state: *mut u32,
+) -> OtLibStatus {
+    ffi_call("OtLibLcTransitionHex", || {
+        let _timer = OpTimer::start(OtLibOp::LcTransition);
+        check_not_null(token_hex, "token_hex")?;
+        check_not_null(out_state, "out_state")?;
+        let lc_token = lc_token_from_hex(cstr_to_str(token_hex)?, token_is_hashed)?;
+        lc_transition_ffi(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            lc_token,
+            DifLcCtrlState(target_lc_state),
+            use_external_clk,
+            force,
+            reset_tap_straps,
+            out_state,
+        )
+    })
+}
+
+/// Transitions the DUT into RMA with the RMA unlock token of `token_size` bytes at `token`, and
+/// writes the LC state read back afterwards to `out_state`.
+///
+/// Same as an `OtLibLcTransition(...)` into RMA over the LC TAP, with the AST calibrated, i.e.
+/// with the internal clock, and reset back into the LC TAP to check the resulting state. The
+/// transition also wipes the flash, so it can take a while. A DUT already in RMA is left as is.
+#[no_mangle]
+pub extern "C" fn OtLibRmaUnlock(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    token: *const u8,
+    token_size: usize,
+    out_state: *mut u32,
+) -> OtLibStatus {
+    ffi_call("OtLibRmaUnlock", || {
+        let _timer = OpTimer::start(OtLibOp::LcTransition);
+        check_not_null(out_state, "out_state")?;
+        // Unlike the other transitions, RMA always requires a token.
+        if token_size != LC_TOKEN_SIZE {
+            bail_status!(
+                OtLibStatus::InvalidArgument,
+                "RMA unlock token must be {} bytes long, got {} bytes.",
+                LC_TOKEN_SIZE,
+                token_size
+            );
+        }
+        let lc_token = lc_token(token, token_size, /*token_is_hashed=*/ false)?;
+        lc_transition_ffi(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            lc_token,
+            DifLcCtrlState::Rma,
+            /*use_external_clk=*/ false,
+            /*force=*/ false,
+            OtLibJtagTap::LcTap,
+            out_state,
+        )
+    })
+}
+
+/// Unlocks the DUT out of RAW into `target_lc_state` with a volatile raw unlock, and writes the
+/// LC state read back afterwards to `out_state`.
+///
+/// Unlike `OtLibLcTransition(...)`, the volatile unlock is not programmed into OTP and does not
+/// increment the LC transition count: the DUT is back in RAW after its next reset. The chip is
+/// therefore reset once, to strap the LC TAP before the unlock, but never after it: the unlock
+/// switches over to `post_transition_tap` without a reset, so it must not be
+/// `OtLibJtagTap::None`. The LC state is only read back and checked when it is
+/// `OtLibJtagTap::LcTap`; otherwise `out_state` is left untouched. Once the unlock went through,
+/// the straps are left applied, as removing them only takes effect on a reset, which would undo
+/// the unlock; they are removed if the unlock fails.
+///
+/// `token` is the raw unlock token of `token_size` bytes, which must be `LC_TOKEN_SIZE`; it is
+/// hashed with `hash_lc_token(...)` before it is written, as the volatile unlock compares it with
+/// the hashed token directly. `use_external_clk` is as described at `OtLibLcTransition(...)`.
+/// A DUT whose LC controller does not support volatile raw unlock fails with
+/// `OtLibStatus::LcTransitionError`.
+#[no_mangle]
+pub extern "C" fn OtLibVolatileRawUnlock(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    token: *const u8,
+    token_size: usize,
+    target_lc_state: u32,
+    use_external_clk: bool,
+    post_transition_tap: OtLibJtagTap,
+    out_state: *mut u32,
+) -> OtLibStatus {
+    ffi_call("OtLibVolatileRawUnlock", || {
+        let _timer = OpTimer::start(OtLibOp::LcTransition);
+        check_not_null(out_state, "out_state")?;
+        let Some(post_transition_tap) = post_transition_tap.tap() else {
+            bail_status!(
+                OtLibStatus::InvalidArgument,
+                "A volatile raw unlock must be followed by a TAP connection."
+            );
+        };
+        // The hashed token is empty for an empty token, which is rejected here.
+        let lc_token = lc_token(token, token_size, /*token_is_hashed=*/ true)?;
+
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                let lc_state = DifLcCtrlState(target_lc_state);
+                let straps = apply_lc_transition_straps(transport)?;
+                let jtag = connect_lc_tap(transport, jtag_params)?;
+                let mut jtag = trigger_volatile_raw_unlock(
+                    transport,
+                    jtag.into_inner(),
+                    lc_state,
+                    // `trigger_volatile_raw_unlock(...)` takes its own copy of the token by value,
+                    // which cannot be wiped.
+                    lc_token.as_ref().map(|token| **token),
+                    use_external_clk,
+                    post_transition_tap,
+                    jtag_params,
+                    /*expect_raw_unlock_supported=*/ true,
+                )
+                .context("Could not perform volatile raw unlock.")
+                .status(OtLibStatus::LcTransitionError)
+                .map(JtagGuard::new)?;
+                if post_transition_tap != JtagTap::LcTap {
+                    jtag.disconnect();
+                    straps.keep();
+                    return Ok(());
+                }
+
+                let state = read_lc_state(&mut *jtag)?;
+                // SAFETY: `out_state` was checked to be non-null and must point to a writable
+                // `u32`.
+                unsafe { *out_state = state };
+                jtag.disconnect();
+                straps.keep();
+                if state != lc_state.redundant_encoding() {
+                    bail_status!(
+                        OtLibStatus::LcMismatch,
+                        "LC state ({:#x}) does not match the target state ({:#x}).",
+                        state,
+                        lc_state.redundant_encoding()
+                    );
+                }
+                Ok(())
+            },
+        )
+    })
+}
+
+/// Transitions the DUT through the `n` LC states at `states` in order, with the token of each step
+/// in the frame of the same index at `tokens`, reading the LC state back after each step.
+///
+/// The LC TAP is strapped once, before the first step, and only released after the last one, so
+/// that consecutive transitions, e.g. RAW to TEST_UNLOCKED0 to DEV, do not pay the strap and reset
+/// overhead each. Each token frame holds a raw token as described at `OtLibLcTransition(...)`,
+/// with a `size` of 0 for transitions that do not require one. `use_external_clk` applies to all
+/// steps. A step whose target state the DUT is already in is skipped.
+///
+/// The sequence stops at the first failure, the LC state read back after a step not matching its
+/// target being reported as `OtLibStatus::LcMismatch`. `out_step` is set to the index of the
+/// failed step, or to `n` on success, and `out_state` to the LC state read back last.
+#[no_mangle]
+pub extern "C" fn OtLibLcTransitionSequence(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    states: *const u32,
+    tokens: *const DutSpiFrame,
+    n: usize,
+    use_external_clk: bool,
+    out_step: *mut usize,
+    out_state: *mut u32,
+) -> OtLibStatus {
+    ffi_call("OtLibLcTransitionSequence", || {
+        let _timer = OpTimer::start(OtLibOp::LcTransition);
+        check_not_null(states, "states")?;
+        check_not_null(tokens, "tokens")?;
+        check_not_null(out_step, "out_step")?;
+        check_not_null(out_state, "out_state")?;
+
+        // SAFETY: `states` and `tokens` were checked to be non-null and must point to `n` states
+        // and token frames.
+        let (states, tokens) = unsafe {
+            (
+                slice::from_raw_parts(states, n),
+                slice::from_raw_parts(tokens, n),
+            )
+        };
+        // The tokens are wiped when they go out of scope, on both the success and error paths.
+        let steps = states
+            .iter()
+            .zip(tokens)
+            .enumerate()
+            .map(|(step, (&state, frame))| {
+                let token = frame.payload.get(..frame.size).ok_or_else(|| {
+                    anyhow!(
+                        "Token frame of {} bytes exceeds the frame payload.",
+                        frame.size
+                    )
+                });
+                let lc_token = token
+                    .and_then(|token| lc_token(token.as_ptr(), token.len(), false))
+                    .with_context(|| format!("Invalid token for LC transition step {}.", step))
+                    .status(OtLibStatus::InvalidArgument)?;
+                Ok((DifLcCtrlState(state), lc_token))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                // SAFETY: `out_step` and `out_state` were checked to be non-null and must point to
+                // a writable `usize` and `u32`.
+                let (out_step, out_state) = unsafe { (&mut *out_step, &mut *out_state) };
+                lc_transition_sequence(
+                    transport,
+                    jtag_params,
+                    &steps,
+                    use_external_clk,
+                    out_step,
+                    out_state,
+                )
+            },
+        )
+    })
+}
+
+/// Connects to the LC TAP to run `f`, strapping the TAP for the duration of `f` only.
+///
+/// The JTAG connection is closed and the straps are removed even if `f` fails.
+fn with_lc_tap<T>(
+    transport: &TransportWrapper,
+    jtag_params: &JtagParams,
+    f: impl FnOnce(&mut dyn Jtag) -> Result<T>,
+) -> Result<T> {
+    let lc_tap = StrapGuard::apply(transport, "PINMUX_TAP_LC", "LC TAP")?;
+    transport
+        .reset_target(LC_RESET_DELAY, true)
+        .context("Could not reset chip.")
+        .status(OtLibStatus::TransportError)?;
+    let mut jtag = connect_lc_tap(transport, jtag_params)?;
+    let value = f(&mut *jtag)?;
+    jtag.disconnect();
+    lc_tap.remove();
+    Ok(value)
+}
+
+/// Reads the LC controller register `reg` over the LC TAP, strapping the TAP for the duration of
+/// the read only.
+fn read_lc_ctrl_reg_over_lc_tap(
+    transport: &TransportWrapper,
+    jtag_params: &JtagParams,
+    reg: LcCtrlReg,
+) -> Result<u32> {
+    with_lc_tap(transport, jtag_params, |jtag| {
+        jtag.read_lc_ctrl_reg(&reg)
+            .with_context(|| format!("Could not read {:?}.", reg))
+            .status(OtLibStatus::JtagError)
+    })
+}
+
+/// Reads the LC state of the DUT over the LC TAP into `out_state`, without transitioning it.
+///
+/// The LC TAP is strapped, and the DUT reset to latch the straps, for the duration of the read
+/// only. `out_state` receives the raw value of the LC controller `LC_STATE` register, the
+/// redundant encoding of the state, e.g. `DifLcCtrlState::Prod.redundant_encoding()` in PROD.
+///
+/// Null arguments are reported as `OtLibStatus::InvalidArgument` and a transport without JTAG as
+/// `OtLibStatus::Unsupported`. A failure to strap or reset the DUT is reported as
+/// `OtLibStatus::TransportError`, a failure to connect to the LC TAP or to read the register as
+/// `OtLibStatus::JtagError`; `out_state` is then left unchanged. A failure to disconnect or remove
+/// the straps after a successful read is reported as `OtLibStatus::JtagTeardownError`, with
+/// `out_state` written.
+#[no_mangle]
+pub extern "C" fn OtLibReadLcState(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    out_state: *mut u32,
+) -> OtLibStatus {
+    ffi_call("OtLibReadLcState", || {
+        check_not_null(out_state, "out_state")?;
+
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                let state =
+                    read_lc_ctrl_reg_over_lc_tap(transport, jtag_params, LcCtrlReg::LcState)?;
+                // SAFETY: `out_state` was checked to be non-null and must point to a writable
+                // `u32`.
+                unsafe { *out_state = state };
+                Ok(())
+            },
+        )
+    })
+}
+
+/// Checks that the DUT is in LC state `expected_state` without transitioning it, and writes the LC
+/// state read back to `out_state`.
+///
+/// A DUT in any other state is reported as `OtLibStatus::LcMismatch`, with both states in the
+/// message returned by `OtLibGetLastError(...)`.
+#[no_mangle]
+pub extern "C" fn OtLibVerifyLcState(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    expected_state: u32,
+    out_state: *mut u32,
+) -> OtLibStatus {
+    ffi_call("OtLibVerifyLcState", || {
+        check_not_null(out_state, "out_state")?;
+
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                let state =
+                    read_lc_ctrl_reg_over_lc_tap(transport, jtag_params, LcCtrlReg::LcState)?;
+                // SAFETY: `out_state` was checked to be non-null and must point to a writable
+                // `u32`.
+                unsafe { *out_state = state };
+                check_lc_state(state, expected_state)
+            },
+        )
+    })
+}
+
+/// Checks that the LC state register value `state` is the redundant encoding of the LC state
+/// `expected_state`, failing with `OtLibStatus::LcMismatch` otherwise.
+fn check_lc_state(state: u32, expected_state: u32) -> Result<()> {
+    let expected = DifLcCtrlState(expected_state).redundant_encoding();
+    if state != expected {
+        bail_status!(
+            OtLibStatus::LcMismatch,
+            "LC state ({:#x}) does not match the expected state ({:#x}).",
+            state,
+            expected
+        );
+    }
+    Ok(())
+}
+
+/// Reads the number of LC transitions the DUT has gone through into `out_count`.
+///
+/// The LC controller allows a limited number of transitions (24) over the lifetime of the chip
+/// and refuses any further one, so provisioning policies can check this count before starting a
+/// flow that transitions the DUT.
+#[no_mangle]
+pub extern "C" fn OtLibReadLcTransitionCount(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    out_count: *mut u32,
+) -> OtLibStatus {
+    ffi_call("OtLibReadLcTransitionCount", || {
+        check_not_null(out_count, "out_count")?;
+
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                let count = read_lc_ctrl_reg_over_lc_tap(
+                    transport,
+                    jtag_params,
+                    LcCtrlReg::LcTransitionCnt,
+                )?;
+                // SAFETY: `out_count` was checked to be non-null and must point to a writable
+                // `u32`.
+                unsafe { *out_count = count };
+                Ok(())
+            },
+        )
+    })
+}
+
+/// Size in bytes of the device ID.
+const DEVICE_ID_SIZE: usize = 32;
+
+/// LC controller registers holding the device ID, least significant word first.
+const DEVICE_ID_REGS: [LcCtrlReg; 8] = [
+    LcCtrlReg::DeviceId0,
+    LcCtrlReg::DeviceId1,
+    LcCtrlReg::DeviceId2,
+    LcCtrlReg::DeviceId3,
+    LcCtrlReg::DeviceId4,
+    LcCtrlReg::DeviceId5,
+    LcCtrlReg::DeviceId6,
+    LcCtrlReg::DeviceId7,
+];
+
+/// Reads the device ID with `read_reg`, which reads an LC controller register, e.g. over the LC
+/// TAP, and returns it little endian with its least significant word first.
+fn read_device_id(
+    mut read_reg: impl FnMut(&LcCtrlReg) -> Result<u32>,
+) -> Result<[u8; DEVICE_ID_SIZE]> {
+    let mut device_id = [0u8; DEVICE_ID_SIZE];
+    for (bytes, reg) in device_id.chunks_exact_mut(4).zip(&DEVICE_ID_REGS) {
+        let word = read_reg(reg)
+            .with_context(|| format!("Could not read {:?}.", reg))
+            .status(OtLibStatus::JtagError)?;
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    Ok(device_id)
+}
+
+/// Reads the hardware device ID of the DUT over the LC TAP into `out` of `len` bytes.
+///
+/// The LC controller registers holding the device ID are only reachable over the LC TAP, which is
+/// strapped for the duration of the read only.
+///
+/// The device ID is `DEVICE_ID_SIZE` bytes, stored little endian with its least significant word
+/// first; a smaller `len` is reported as `OtLibStatus::BufferTooSmall`.
+#[no_mangle]
+pub extern "C" fn OtLibReadDeviceId(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    out: *mut u8,
+    len: usize,
+) -> OtLibStatus {
+    ffi_call("OtLibReadDeviceId", || {
+        check_not_null(out, "out")?;
+        if len < DEVICE_ID_SIZE {
+            bail_status!(
+                OtLibStatus::BufferTooSmall,
+                "The device ID is {} bytes, but the buffer only holds {}.",
+                DEVICE_ID_SIZE,
+                len
+            );
+        }
+
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                let device_id = with_lc_tap(transport, jtag_params, |jtag| {
+                    read_device_id(|reg| jtag.read_lc_ctrl_reg(reg))
+                })?;
+
+                // SAFETY: `out` was checked to be non-null and must point to `len` writable bytes.
+                let out = unsafe { slice::from_raw_parts_mut(out, DEVICE_ID_SIZE) };
+                out.copy_from_slice(&device_id);
+                Ok(())
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::bail;
+    use zeroize::Zeroize;
+
+    use crate::ffi::tests::dangling;
+    use crate::strap::tests::MockStraps;
+
+    #[test]
+    fn reset_no_lock_rejects_null_openocd_path() {
+        let status = OtLibResetNoLock(dangling(), std::ptr::null_mut(), 0, false, 50);
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn lc_tap_straps_are_removed_on_failure() {
+        let straps = MockStraps {
+            fail_on: Some("PINMUX_TAP_LC"),
+            ..Default::default()
+        };
+        assert!(apply_lc_tap_straps(&straps).is_err());
+        assert!(straps.applied.borrow().is_empty());
+
+        // A failure after both straps are applied, e.g. connecting to the LC TAP.
+        let straps = MockStraps::default();
+        let result: Result<()> = (|| {
+            let _straps = apply_lc_tap_straps(&straps)?;
+            assert_eq!(*straps.applied.borrow(), ["ROM_BOOTSTRAP", "PINMUX_TAP_LC"]);
+            bail_status!(OtLibStatus::JtagError, "Could not connect to LC TAP.");
+        })();
+        assert_eq!(
+            OtLibStatus::from_error(&result.unwrap_err()),
+            OtLibStatus::JtagError
+        );
+        assert!(straps.applied.borrow().is_empty());
+    }
+
+    #[test]
+    fn read_device_id_rejects_short_buffer() {
+        let status = OtLibReadDeviceId(dangling(), dangling(), 0, false, dangling(), 16);
+        assert_eq!(status, OtLibStatus::BufferTooSmall);
+    }
+
+    #[test]
+    fn device_id_is_read_least_significant_word_first() {
+        let mut reads = 0;
+        let device_id = read_device_id(|_| {
+            reads += 1;
+            Ok(0x0102_0300 | reads)
+        })
+        .unwrap();
+        assert_eq!(reads, 8);
+        assert_eq!(
+            device_id[..8],
+            [0x01, 0x03, 0x02, 0x01, 0x02, 0x03, 0x02, 0x01]
+        );
+        assert_eq!(device_id[28..], [0x08, 0x03, 0x02, 0x01]);
+    }
+
+    #[test]
+    fn device_id_read_failure_names_the_register() {
+        let mut reads = 0;
+        let err = read_device_id(|_| {
+            reads += 1;
+            if reads == 4 {
+                bail!("JTAG timeout");
+            }
+            Ok(0)
+        })
+        .unwrap_err();
+        assert_eq!(reads, 4);
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::JtagError);
+        assert!(format!("{:#}", err).starts_with("Could not read DeviceId3."));
+    }
+
+    #[test]
+    fn lc_transition_rejects_null_token() {
+        let status = OtLibLcTransition(
+            dangling(),
+            dangling(),
+            0,
+            false,
+            std::ptr::null(),
+            16,
+            false,
+            0,
+            false,
+            false,
+            OtLibJtagTap::LcTap,
+            dangling(),
+        );
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn lc_transition_rejects_null_out_state() {
+        let token = [0u8; 16];
+        let status = OtLibLcTransition(
+            dangling(),
+            dangling(),
+            0,
+            false,
+            token.as_ptr(),
+            16,
+            false,
+            0,
+            false,
+            false,
+            OtLibJtagTap::LcTap,
+            std::ptr::null_mut(),
+        );
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn lc_token_accepts_empty_and_full_tokens() {
+        assert!(lc_token(std::ptr::null(), 0, false).unwrap().is_none());
+        let bytes: Vec<u8> = (0..16).collect();
+        assert_eq!(
+            lc_token(bytes.as_ptr(), 16, false).unwrap().as_deref(),
+            Some(&[0x03020100, 0x07060504, 0x0b0a0908, 0x0f0e0d0c])
+        );
+    }
+
+    #[test]
+    fn hashed_lc_token_must_not_be_empty() {
+        let err = lc_token(std::ptr::null(), 0, true).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn lc_token_is_wiped() {
+        let sentinel = [0xa5u8; 16];
+        let mut token = lc_token(sentinel.as_ptr(), 16, false).unwrap().unwrap();
+        assert_eq!(*token, [0xa5a5a5a5; 4]);
+        // `Zeroizing` wipes the token through the same call when it is dropped.
+        token.zeroize();
+        assert_eq!(*token, [0; 4]);
+    }
+
+    #[test]
+    fn lc_token_rejects_other_sizes() {
+        let bytes = [0u8; 20];
+        for size in [15, 20] {
+            let err = lc_token(bytes.as_ptr(), size, false).unwrap_err();
+            assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+        }
+        let status = OtLibLcTransition(
+            dangling(),
+            dangling(),
+            0,
+            false,
+            bytes.as_ptr(),
+            15,
+            false,
+            0,
+            false,
+            false,
+            OtLibJtagTap::LcTap,
+            dangling(),
+        );
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn lc_token_from_hex_matches_raw_bytes() {
+        assert!(lc_token_from_hex("", false).unwrap().is_none());
+        assert_eq!(
+            lc_token_from_hex("000102030405060708090a0B0c0D0e0F", false)
+                .unwrap()
+                .as_deref(),
+            Some(&[0x03020100, 0x07060504, 0x0b0a0908, 0x0f0e0d0c])
+        );
+        for token_hex in [
+            "0001",
+            "000102030405060708090a0b0c0d0e0g",
+            "+0102030405060708090a0b0c0d0e0f",
+        ] {
+            let err = lc_token_from_hex(token_hex, false).unwrap_err();
+            assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn rma_unlock_requires_token() {
+        let token = [0u8; 16];
+        for size in [0, 15] {
+            let status = OtLibRmaUnlock(
+                dangling(),
+                dangling(),
+                0,
+                false,
+                token.as_ptr(),
+                size,
+                dangling(),
+            );
+            assert_eq!(status, OtLibStatus::InvalidArgument);
+        }
+        let status = OtLibRmaUnlock(
+            dangling(),
+            dangling(),
+            0,
+            false,
+            std::ptr::null(),
+            16,
+            dangling(),
+        );
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn lc_transition_sequence_rejects_bad_tokens() {
+        let states = [0u32; 2];
+        // SAFETY: An all-zero frame is a valid, empty frame.
+        let mut tokens: [DutSpiFrame; 2] = unsafe { std::mem::zeroed() };
+        tokens[1].size = 15;
+        let mut step = 0;
+        let status = OtLibLcTransitionSequence(
+            dangling(),
+            dangling(),
+            0,
+            false,
+            states.as_ptr(),
+            tokens.as_ptr(),
+            2,
+            false,
+            &mut step,
+            dangling(),
+        );
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn read_lc_transition_count_rejects_null_out_count() {
+        let status =
+            OtLibReadLcTransitionCount(dangling(), dangling(), 0, false, std::ptr::null_mut());
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn verify_lc_state_rejects_null_out_state() {
+        let status = OtLibVerifyLcState(dangling(), dangling(), 0, false, 0, std::ptr::null_mut());
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn lc_state_must_match_expected_state() {
+        let unlocked = DifLcCtrlState::TestUnlocked0;
+        assert!(check_lc_state(unlocked.redundant_encoding(), unlocked.0).is_ok());
+
+        let state = DifLcCtrlState::Raw.redundant_encoding();
+        let status = ffi_call("test", || check_lc_state(state, unlocked.0));
+        assert_eq!(status, OtLibStatus::LcMismatch);
+        let mut buf = [0 as c_char; 128];
+        crate::status::OtLibGetLastError(buf.as_mut_ptr(), buf.len());
+        // SAFETY: `OtLibGetLastError(...)` always NUL terminates `buf`.
+        let msg = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) }
+            .to_str()
+            .unwrap();
+        assert!(msg.contains(&format!("({:#x})", state)), "{}", msg);
+        assert!(
+            msg.contains(&format!("({:#x})", unlocked.redundant_encoding())),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn volatile_raw_unlock_requires_token_and_tap() {
+        let token = [0u8; 16];
+        for (size, tap) in [(0, OtLibJtagTap::LcTap), (16, OtLibJtagTap::None)] {
+            let status = OtLibVolatileRawUnlock(
+                dangling(),
+                dangling(),
+                0,
+                false,
+                token.as_ptr(),
+                size,
+                0,
+                false,
+                tap,
+                dangling(),
+            );
+            assert_eq!(status, OtLibStatus::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn read_lc_state_rejects_null_out_state() {
+        let status = OtLibReadLcState(dangling(), dangling(), 0, false, std::ptr::null_mut());
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+}
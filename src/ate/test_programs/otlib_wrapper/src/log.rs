@@ -0,0 +1,249 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::cell::{Cell, RefCell};
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::raw::c_char;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+
+use crate::ffi::{check_not_null, cstr_to_str};
+use crate::status::{ffi_call, OtLibStatus, StatusContext};
+
+/// Severity of a message logged by the library.
+///
+/// NOTE: must match the definition of `DutLogLevel` in src/ate/test_programs/dut_lib/dut_lib.h
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtLibLogLevel {
+    Info = 0,
+    Warn = 1,
+    Error = 2,
+}
+
+/// Receives the messages logged by the library, see `OtLibSetLogCallback(...)`.
+pub type OtLibLogCallback = extern "C" fn(level: i32, msg: *const c_char);
+
+thread_local! {
+    /// Callback installed by `OtLibSetLogCallback(...)` on this thread.
+    static LOG_CALLBACK: Cell<Option<OtLibLogCallback>> = const { Cell::new(None) };
+
+    /// Log file opened by `OtLibSetLogFile(...)` on this thread.
+    static LOG_FILE: RefCell<Option<File>> = const { RefCell::new(None) };
+}
+
+/// Installs `cb` as the receiver of the messages the library logs on the calling thread.
+///
+/// `msg` is a NUL-terminated line without trailing newline, only valid for the duration of the
+/// call. A null `cb` uninstalls the callback.
+///
+/// The callback is not process-wide: like the last error and the transport it is used with, it
+/// belongs to the calling thread, so that the threads driving different DUTs keep separate logs.
+/// Messages logged on any other thread do not reach it, and are printed unless that thread
+/// installed its own.
+#[no_mangle]
+pub extern "C" fn OtLibSetLogCallback(cb: Option<OtLibLogCallback>) {
+    LOG_CALLBACK.with(|callback| callback.set(cb));
+}
+
+/// Appends the messages the library logs on the calling thread to the file at `path`, each line
+/// prefixed with a timestamp.
+///
+/// An empty `path` closes the current log file of the calling thread.
+#[no_mangle]
+pub extern "C" fn OtLibSetLogFile(path: *const c_char) -> OtLibStatus {
+    ffi_call("OtLibSetLogFile", || {
+        check_not_null(path, "path")?;
+        let path = cstr_to_str(path)?;
+        let file = match path {
+            "" => None,
+            path => Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Could not open log file {}.", path))
+                    .status(OtLibStatus::InvalidArgument)?,
+            ),
+        };
+        LOG_FILE.with(|log_file| *log_file.borrow_mut() = file);
+        Ok(())
+    })
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp with millisecond precision, as the console
+/// timestamps of `UartConsole`.
+fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    // Converts days since the epoch into a civil date, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Hands `msg` to the log callback and log file of the calling thread, or prints it if there are
+/// none.
+pub(crate) fn log(level: OtLibLogLevel, msg: &str) {
+    let cb = LOG_CALLBACK.with(Cell::get);
+    if let Some(cb) = cb {
+        // Interior NULs would truncate the message on the C side.
+        let msg = CString::new(msg.replace('\0', "\\0")).unwrap_or_default();
+        cb(level as i32, msg.as_ptr());
+    }
+    let has_file = LOG_FILE.with(|log_file| match log_file.borrow_mut().as_mut() {
+        Some(file) => {
+            let timestamp = format_timestamp(SystemTime::now());
+            for line in msg.lines() {
+                // Logging must not fail the operation being logged.
+                let _ = writeln!(file, "[{}] {}", timestamp, line);
+            }
+            true
+        }
+        None => false,
+    });
+    if cb.is_none() && !has_file {
+        match level {
+            OtLibLogLevel::Info => println!("{}", msg),
+            _ => eprintln!("{}", msg),
+        }
+    }
+}
+
+/// Logs an informational message, see `log::log(...)`.
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::OtLibLogLevel::Info, &format!($($arg)*))
+    };
+}
+pub(crate) use log_info;
+
+/// Logs a warning, see `log::log(...)`.
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::OtLibLogLevel::Warn, &format!($($arg)*))
+    };
+}
+pub(crate) use log_warn;
+
+/// Logs an error, see `log::log(...)`.
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::OtLibLogLevel::Error, &format!($($arg)*))
+    };
+}
+pub(crate) use log_error;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::ffi::CStr;
+    use std::time::Duration;
+
+    thread_local! {
+        /// Messages received by `record(...)` on this thread, with their level.
+        pub(crate) static LOGGED: RefCell<Vec<(i32, String)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Log callback that appends the messages it receives to `LOGGED`.
+    pub(crate) extern "C" fn record(level: i32, msg: *const c_char) {
+        // SAFETY: `log(...)` passes a valid NUL-terminated string.
+        let msg = unsafe { CStr::from_ptr(msg) }.to_str().unwrap().to_string();
+        LOGGED.with(|logged| logged.borrow_mut().push((level, msg)));
+    }
+
+    #[test]
+    fn log_callback_receives_messages() {
+        OtLibSetLogCallback(Some(record));
+        log_info!("Waiting for {} ...", "ROM_EXT");
+        log_warn!("warning");
+        log_error!("error");
+        OtLibSetLogCallback(None);
+
+        assert_eq!(
+            LOGGED.with(RefCell::take),
+            vec![
+                (0, "Waiting for ROM_EXT ...".to_string()),
+                (1, "warning".to_string()),
+                (2, "error".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn log_callbacks_are_per_thread() {
+        std::thread::scope(|scope| {
+            for slot in 0..2 {
+                scope.spawn(move || {
+                    OtLibSetLogCallback(Some(record));
+                    for _ in 0..100 {
+                        log_info!("slot {}", slot);
+                    }
+                    OtLibSetLogCallback(None);
+                    let logged = LOGGED.with(RefCell::take);
+                    assert_eq!(logged.len(), 100);
+                    assert!(logged
+                        .iter()
+                        .all(|(_, msg)| *msg == format!("slot {}", slot)));
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn timestamps_are_rfc3339() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_251_199_123);
+        assert_eq!(format_timestamp(time), "2024-02-29T23:59:59.123Z");
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn log_file_receives_timestamped_lines() {
+        let path = std::env::temp_dir().join(format!("otlib_log_{}.txt", std::process::id()));
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        assert_eq!(OtLibSetLogFile(c_path.as_ptr()), OtLibStatus::Ok);
+        log_info!("Owner Firmware has booted.");
+        assert_eq!(
+            OtLibSetLogFile(CString::default().as_ptr()),
+            OtLibStatus::Ok
+        );
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(contents
+            .lines()
+            .any(|line| line.starts_with('[') && line.ends_with("Z] Owner Firmware has booted.")));
+    }
+
+    #[test]
+    fn log_file_rejects_null_path() {
+        assert_eq!(
+            OtLibSetLogFile(std::ptr::null()),
+            OtLibStatus::InvalidArgument
+        );
+    }
+}
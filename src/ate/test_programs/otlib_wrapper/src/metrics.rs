@@ -0,0 +1,89 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::cell::RefCell;
+use std::time::Instant;
+
+/// Kind of a timed operation, see `OtLibGetLastOpDurationUs(...)`.
+///
+/// NOTE: must match the definition of `DutOp` in src/ate/test_programs/dut_lib/dut_lib.h
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtLibOp {
+    /// `OtLibFpgaLoadBitstream(...)` and `OtLibFpgaLoadBitstreamBuf(...)`.
+    BitstreamLoad = 0,
+    /// `OtLibBootstrap(...)`.
+    Bootstrap = 1,
+    /// `OtLibConsoleRx(...)`, `OtLibConsoleRxStream(...)` and their `OtLibConsoleHandle*(...)`
+    /// variants.
+    ConsoleRx = 2,
+    /// The `OtLibConsoleTx*(...)` and `OtLibConsoleHandleTx*(...)` functions.
+    ConsoleTx = 3,
+    /// The `OtLibLcTransition*(...)` functions, `OtLibRmaUnlock(...)` and
+    /// `OtLibVolatileRawUnlock(...)`.
+    LcTransition = 4,
+    /// `OtLibLoadSramElf(...)` and `OtLibLoadSramVmem(...)`.
+    SramLoad = 5,
+    /// `OtLibConsoleTransact(...)` and `OtLibConsoleHandleTransact(...)`.
+    ConsoleTransact = 6,
+}
+
+const NUM_OPS: usize = OtLibOp::ConsoleTransact as usize + 1;
+
+thread_local! {
+    /// Duration in microseconds of the most recent operation of each kind on this thread.
+    static LAST_OP_DURATIONS_US: RefCell<[u64; NUM_OPS]> = const { RefCell::new([0; NUM_OPS]) };
+}
+
+/// Records the time until it is dropped as the duration of the most recent `op`.
+pub(crate) struct OpTimer {
+    op: OtLibOp,
+    start: Instant,
+}
+
+impl OpTimer {
+    pub(crate) fn start(op: OtLibOp) -> Self {
+        OpTimer {
+            op,
+            start: Instant::now(),
+        }
+    }
+}
+
+/// Returns the time elapsed since `start` in microseconds, saturated to `u64::MAX`.
+pub(crate) fn elapsed_us(start: Instant) -> u64 {
+    u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX)
+}
+
+impl Drop for OpTimer {
+    fn drop(&mut self) {
+        let elapsed_us = elapsed_us(self.start);
+        LAST_OP_DURATIONS_US
+            .with(|durations| durations.borrow_mut()[self.op as usize] = elapsed_us);
+    }
+}
+
+/// Returns the duration in microseconds of the most recent operation of kind `op` on the calling
+/// thread, whether it succeeded or not, or 0 if there was none.
+#[no_mangle]
+pub extern "C" fn OtLibGetLastOpDurationUs(op: OtLibOp) -> u64 {
+    LAST_OP_DURATIONS_US.with(|durations| durations.borrow()[op as usize])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn op_timer_records_duration_of_its_op() {
+        assert_eq!(OtLibGetLastOpDurationUs(OtLibOp::Bootstrap), 0);
+        {
+            let _timer = OpTimer::start(OtLibOp::Bootstrap);
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert!(OtLibGetLastOpDurationUs(OtLibOp::Bootstrap) >= 2000);
+        assert_eq!(OtLibGetLastOpDurationUs(OtLibOp::LcTransition), 0);
+    }
+}
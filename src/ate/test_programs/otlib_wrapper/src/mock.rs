@@ -0,0 +1,88 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use opentitanlib::io::console::ConsoleDevice;
+
+/// In-memory console that replays a scripted byte stream, for the unit tests of the console
+/// helpers that run without a DUT.
+///
+/// Only built for the tests; the exported functions themselves need a transport and are not
+/// driven through it.
+pub struct MockConsole {
+    /// Bytes still to be received by the host.
+    rx: RefCell<VecDeque<u8>>,
+    /// Maximum number of bytes returned by each `console_read(...)`.
+    chunk_size: usize,
+    /// Bytes written by the host.
+    tx: RefCell<Vec<u8>>,
+}
+
+impl MockConsole {
+    /// Creates a console that delivers `script` to the host, then nothing.
+    pub fn new(script: &[u8]) -> Self {
+        Self::with_chunk_size(script, usize::MAX)
+    }
+
+    /// Creates a console that delivers `script` to the host at most `chunk_size` bytes per read,
+    /// like a SPI console delivering one frame at a time.
+    pub fn with_chunk_size(script: &[u8], chunk_size: usize) -> Self {
+        MockConsole {
+            rx: RefCell::new(script.iter().copied().collect()),
+            chunk_size,
+            tx: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Returns the bytes written by the host so far.
+    pub fn written(&self) -> Vec<u8> {
+        self.tx.borrow().clone()
+    }
+}
+
+impl ConsoleDevice for MockConsole {
+    /// Returns the next scripted bytes, or waits for `timeout` and returns none once the script
+    /// is exhausted, as a DUT that stopped talking would.
+    fn console_read(&self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        let mut rx = self.rx.borrow_mut();
+        if rx.is_empty() {
+            std::thread::sleep(timeout);
+            return Ok(0);
+        }
+        let len = buf.len().min(self.chunk_size).min(rx.len());
+        for (dst, src) in buf.iter_mut().zip(rx.drain(..len)) {
+            *dst = src;
+        }
+        Ok(len)
+    }
+
+    fn console_write(&self, buf: &[u8]) -> Result<()> {
+        self.tx.borrow_mut().extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_console_replays_script_in_chunks() {
+        let console = MockConsole::with_chunk_size(b"RESP_OK", 4);
+        let mut buf = [0u8; 16];
+        assert_eq!(console.console_read(&mut buf, Duration::ZERO).unwrap(), 4);
+        assert_eq!(&buf[..4], b"RESP");
+        assert_eq!(console.console_read(&mut buf, Duration::ZERO).unwrap(), 3);
+        assert_eq!(&buf[..3], b"_OK");
+        assert_eq!(console.console_read(&mut buf, Duration::ZERO).unwrap(), 0);
+
+        console.console_write(b"{}").unwrap();
+        assert_eq!(console.written(), b"{}");
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::os::raw::c_char;
+use std::slice;
+
+use anyhow::{Context, Result};
+
+use opentitanlib::dif::otp_ctrl::Partition;
+use opentitanlib::test_utils::otp_ctrl::OtpPartition;
+
+use crate::ffi::{check_not_null, cstr_to_str};
+use crate::jtag::{with_jtag_transport, with_riscv_tap};
+use crate::status::{bail_status, ffi_call, OtLibStatus, StatusContext};
+use crate::transport::OtLibTransport;
+
+/// Looks up the OTP partition `name`, as named in the OTP memory map (e.g. `HW_CFG0`).
+///
+/// An unknown partition is reported as `OtLibStatus::InvalidArgument`.
+fn otp_partition(name: &str) -> Result<Partition> {
+    Ok(match name {
+        "VENDOR_TEST" => Partition::VENDOR_TEST,
+        "CREATOR_SW_CFG" => Partition::CREATOR_SW_CFG,
+        "OWNER_SW_CFG" => Partition::OWNER_SW_CFG,
+        "HW_CFG0" => Partition::HW_CFG0,
+        "HW_CFG1" => Partition::HW_CFG1,
+        "SECRET0" => Partition::SECRET0,
+        "SECRET1" => Partition::SECRET1,
+        "SECRET2" => Partition::SECRET2,
+        "LIFE_CYCLE" => Partition::LIFE_CYCLE,
+        _ => bail_status!(
+            OtLibStatus::InvalidArgument,
+            "Unknown OTP partition: {}.",
+            name
+        ),
+    })
+}
+
+/// Copies the OTP `words` into `buf`, little endian.
+///
+/// Fails with `OtLibStatus::BufferTooSmall` if `buf` cannot hold all of them.
+fn copy_otp_words(words: &[u32], buf: &mut [u8]) -> Result<()> {
+    let size = std::mem::size_of_val(words);
+    if buf.len() < size {
+        bail_status!(
+            OtLibStatus::BufferTooSmall,
+            "The OTP partition is {} bytes, but the buffer only holds {}.",
+            size,
+            buf.len()
+        );
+    }
+    for (chunk, word) in buf.chunks_exact_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    Ok(())
+}
+
+/// Reads the OTP partition `partition` over the RISC-V TAP into `buf` of `len` bytes.
+///
+/// `partition` is named as in the OTP memory map, e.g. `HW_CFG0`. Partitions that are read-locked
+/// or not readable by software, such as the secret partitions once locked, fail with the error of
+/// the OTP controller.
+#[no_mangle]
+pub extern "C" fn OtLibReadOtp(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    partition: *const c_char,
+    buf: *mut u8,
+    len: usize,
+) -> OtLibStatus {
+    ffi_call("OtLibReadOtp", || {
+        check_not_null(partition, "partition")?;
+        check_not_null(buf, "buf")?;
+        let partition = otp_partition(cstr_to_str(partition)?)?;
+
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                let words = with_riscv_tap(transport, jtag_params, /*reset=*/ true, |jtag| {
+                    OtpPartition::read(jtag, partition)
+                        .with_context(|| format!("Could not read OTP partition {:?}.", partition))
+                        .status(OtLibStatus::JtagError)
+                })?;
+                // SAFETY: `buf` was checked to be non-null and must point to `len` writable bytes.
+                let buf = unsafe { slice::from_raw_parts_mut(buf, len) };
+                copy_otp_words(&words, buf)
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_partitions_are_rejected() {
+        assert_eq!(otp_partition("HW_CFG0").unwrap(), Partition::HW_CFG0);
+        let err = otp_partition("hw_cfg0").unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn otp_words_must_fit_into_buffer() {
+        let mut buf = [0u8; 8];
+        copy_otp_words(&[0x0403_0201, 0x0807_0605], &mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4, 5, 6, 7, 8]);
+        let err = copy_otp_words(&[0, 0, 0], &mut buf).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::BufferTooSmall);
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::rc::Rc;
+
+use anyhow::{Context, Result};
+
+use opentitanlib::io::spi::{SpiError, SpiParams, Target, TransferMode};
+use opentitanlib::transport::TransportError;
+
+use crate::log::log_warn;
+use crate::status::{OtLibStatus, StatusContext, StatusError};
+
+/// SPI clock polarity and phase.
+///
+/// NOTE: must match the definition of `DutSpiMode` in src/ate/test_programs/dut_lib/dut_lib.h
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtLibSpiMode {
+    /// The default mode of the SPI interface.
+    Default = 0,
+    /// CPOL 0, CPHA 0.
+    Mode0 = 1,
+    /// CPOL 0, CPHA 1.
+    Mode1 = 2,
+    /// CPOL 1, CPHA 0.
+    Mode2 = 3,
+    /// CPOL 1, CPHA 1.
+    Mode3 = 4,
+}
+
+impl OtLibSpiMode {
+    fn transfer_mode(self) -> Option<TransferMode> {
+        match self {
+            OtLibSpiMode::Default => None,
+            OtLibSpiMode::Mode0 => Some(TransferMode::Mode0),
+            OtLibSpiMode::Mode1 => Some(TransferMode::Mode1),
+            OtLibSpiMode::Mode2 => Some(TransferMode::Mode2),
+            OtLibSpiMode::Mode3 => Some(TransferMode::Mode3),
+        }
+    }
+}
+
+/// Builds the SPI parameters for the FFI arguments `speed_hz` and `mode`. A `speed_hz` of 0 and
+/// `OtLibSpiMode::Default` keep the defaults of the SPI interface.
+pub(crate) fn spi_params(speed_hz: u32, mode: OtLibSpiMode) -> SpiParams {
+    SpiParams {
+        speed: (speed_hz != 0).then_some(speed_hz),
+        mode: mode.transfer_mode(),
+        ..Default::default()
+    }
+}
+
+/// Whether `params` changes any of the defaults of the SPI interface.
+pub(crate) fn is_default(params: &SpiParams) -> bool {
+    params.speed.is_none() && params.mode.is_none()
+}
+
+/// The clock speed and mode a SPI interface had before they were changed, restored when this is
+/// dropped.
+///
+/// The transport hands out the same SPI target to every call, so without restoring them, a call
+/// that keeps the defaults would run with the settings of the previous call.
+pub(crate) struct SpiSettings {
+    spi: Rc<dyn Target>,
+    mode: Option<TransferMode>,
+    speed: Option<u32>,
+}
+
+impl Drop for SpiSettings {
+    fn drop(&mut self) {
+        let restored = self
+            .mode
+            .map_or(Ok(()), |mode| self.spi.set_transfer_mode(mode))
+            .and_then(|_| {
+                self.speed
+                    .map_or(Ok(()), |speed| self.spi.set_max_speed(speed))
+            });
+        if let Err(e) = restored {
+            log_warn!("Unable to restore the SPI settings: {:#}", e);
+        }
+    }
+}
+
+/// Classifies a failure to change the settings of a SPI interface.
+///
+/// Only a value the SPI interface rejects is the caller's fault.
+fn spi_settings_status(err: &anyhow::Error) -> OtLibStatus {
+    if err.chain().any(|cause| cause.is::<SpiError>()) {
+        OtLibStatus::InvalidArgument
+    } else if err.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<TransportError>(),
+            Some(TransportError::UnsupportedOperation)
+        )
+    }) {
+        OtLibStatus::Unsupported
+    } else {
+        OtLibStatus::TransportError
+    }
+}
+
+impl SpiSettings {
+    /// Saves the settings of `spi` that `params` changes, to be restored when this is dropped.
+    pub(crate) fn save(spi: &Rc<dyn Target>, params: &SpiParams) -> Result<Self> {
+        let mut settings = SpiSettings {
+            spi: spi.clone(),
+            mode: None,
+            speed: None,
+        };
+        if params.mode.is_some() {
+            settings.mode = Some(
+                spi.get_transfer_mode()
+                    .context("Unable to read the SPI mode.")
+                    .status(OtLibStatus::TransportError)?,
+            );
+        }
+        if params.speed.is_some() {
+            settings.speed = Some(
+                spi.get_max_speed()
+                    .context("Unable to read the SPI speed.")
+                    .status(OtLibStatus::TransportError)?,
+            );
+        }
+        Ok(settings)
+    }
+}
+
+/// Applies the clock speed and mode of `params` to `spi`, if set, until the returned
+/// `SpiSettings` is dropped.
+///
+/// Values the SPI interface does not support are reported as `OtLibStatus::InvalidArgument`.
+pub(crate) fn configure_spi(spi: &Rc<dyn Target>, params: &SpiParams) -> Result<SpiSettings> {
+    let settings = SpiSettings::save(spi, params)?;
+    if let Some(mode) = params.mode {
+        spi.set_transfer_mode(mode)
+            .with_context(|| format!("Unable to set SPI mode {:?}.", mode))
+            .map_err(|e| StatusError::new(spi_settings_status(&e), e))?;
+    }
+    if let Some(speed) = params.speed {
+        spi.set_max_speed(speed)
+            .with_context(|| format!("Unable to set SPI speed {} Hz.", speed))
+            .map_err(|e| StatusError::new(spi_settings_status(&e), e))?;
+    }
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unspecified_spi_params_keep_defaults() {
+        let params = spi_params(0, OtLibSpiMode::Default);
+        assert!(is_default(&params));
+        assert_eq!(params.speed, None);
+        assert_eq!(params.mode, None);
+        assert_eq!(params.bus, None);
+    }
+
+    #[test]
+    fn spi_settings_failures_are_classified() {
+        let invalid = anyhow::Error::from(SpiError::InvalidSpeed(1)).context("set speed");
+        assert_eq!(spi_settings_status(&invalid), OtLibStatus::InvalidArgument);
+        assert_eq!(
+            spi_settings_status(&TransportError::UnsupportedOperation.into()),
+            OtLibStatus::Unsupported
+        );
+        assert_eq!(
+            spi_settings_status(&anyhow::anyhow!("USB transfer failed")),
+            OtLibStatus::TransportError
+        );
+    }
+
+    #[test]
+    fn spi_params_hold_speed_and_mode() {
+        let params = spi_params(4_000_000, OtLibSpiMode::Mode3);
+        assert!(!is_default(&params));
+        assert_eq!(params.speed, Some(4_000_000));
+        assert_eq!(params.mode, Some(TransferMode::Mode3));
+        assert!(!is_default(&spi_params(0, OtLibSpiMode::Mode0)));
+    }
+}
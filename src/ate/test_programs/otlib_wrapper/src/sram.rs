@@ -0,0 +1,270 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+
+use opentitanlib::app::TransportWrapper;
+use opentitanlib::io::jtag::JtagParams;
+use opentitanlib::test_utils::load_sram_program::{
+    ExecutionMode, ExecutionResult, SramProgramParams,
+};
+
+use crate::ffi::{check_file_exists, check_not_null, cstr_to_str};
+use crate::jtag::{with_jtag_transport, with_riscv_tap};
+use crate::log::log_info;
+use crate::metrics::{OpTimer, OtLibOp};
+use crate::status::{bail_status, ffi_call, OtLibStatus, StatusContext};
+use crate::transport::OtLibTransport;
+
+/// State of an SRAM program after it was loaded.
+///
+/// NOTE: must match the definition of `DutSramExecution` in src/ate/test_programs/dut_lib/dut_lib.h
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtLibSramExecution {
+    /// The program is still executing, either because `wait_for_done` was not set or it has not
+    /// completed yet.
+    Executing = 0,
+    /// The program completed. The result holds the value opentitanlib reports in its
+    /// `ExecutionResult::ExecutionDone` once execution stopped, not one returned by the program.
+    Done = 1,
+    /// The program reported an error. The result holds the value opentitanlib reports in its
+    /// `ExecutionResult::ExecutionError` once execution stopped, if any, or 0.
+    Error = 2,
+}
+
+/// Splits an `ExecutionResult` into the state and the value reported to the caller.
+fn sram_execution(result: ExecutionResult) -> Result<(OtLibSramExecution, u32)> {
+    match result {
+        ExecutionResult::Executing => Ok((OtLibSramExecution::Executing, 0)),
+        ExecutionResult::ExecutionDone(value) => Ok((OtLibSramExecution::Done, value)),
+        ExecutionResult::ExecutionError(value) => {
+            Ok((OtLibSramExecution::Error, value.unwrap_or(0)))
+        }
+        _ => bail_status!(
+            OtLibStatus::SramProgramError,
+            "Unexpected SRAM program execution result: {:?}.",
+            result
+        ),
+    }
+}
+
+/// Writes the state and value of the executed SRAM program to the caller's out-parameters.
+///
+/// A program that reported an error is still returned as `OtLibStatus::SramProgramError` once the
+/// out-parameters are written.
+fn report_sram_execution(
+    result: ExecutionResult,
+    out_execution: *mut OtLibSramExecution,
+    out_result: *mut u32,
+) -> Result<()> {
+    let (execution, value) = sram_execution(result)?;
+    // SAFETY: The out-parameters were checked to be non-null and must point to writable memory.
+    unsafe {
+        *out_execution = execution;
+        *out_result = value;
+    }
+    match execution {
+        OtLibSramExecution::Executing => log_info!("SRAM program loaded and is executing."),
+        OtLibSramExecution::Done => log_info!("SRAM program loaded execution completed."),
+        OtLibSramExecution::Error => bail_status!(
+            OtLibStatus::SramProgramError,
+            "SRAM program load/execution failed: {:?}.",
+            result
+        ),
+    }
+    Ok(())
+}
+
+/// Loads `sram_program` into the DUT's SRAM over the RISC-V TAP and executes it, waiting for it to
+/// complete if `wait_for_done` is set.
+fn load_sram_program(
+    transport: &TransportWrapper,
+    jtag_params: &JtagParams,
+    sram_program: SramProgramParams,
+    wait_for_done: bool,
+    timeout_ms: u64,
+) -> Result<ExecutionResult> {
+    with_riscv_tap(transport, jtag_params, /*reset=*/ true, |jtag| {
+        // Reset and halt the CPU to ensure we are in a known state.
+        jtag.reset(/*run=*/ false).status(OtLibStatus::JtagError)?;
+
+        // Load the SRAM program into DUT over JTAG and execute it.
+        let mut mode = ExecutionMode::Jump;
+        if wait_for_done {
+            mode = ExecutionMode::JumpAndWait(Duration::from_millis(timeout_ms));
+        }
+        sram_program
+            .load_and_execute(jtag, mode)
+            .status(OtLibStatus::SramProgramError)
+    })
+}
+
+/// Describes an SRAM program built as an ELF.
+fn elf_program(elf: &str, skip_crc: bool) -> SramProgramParams {
+    SramProgramParams {
+        elf: Some(PathBuf::from(elf)),
+        vmem: None,
+        load_addr: None,
+        skip_crc,
+    }
+}
+
+/// Describes an SRAM program built as a `.vmem` image to be loaded at `load_addr`.
+fn vmem_program(vmem: &str, load_addr: u32, skip_crc: bool) -> SramProgramParams {
+    SramProgramParams {
+        elf: None,
+        vmem: Some(PathBuf::from(vmem)),
+        load_addr: Some(load_addr),
+        skip_crc,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn OtLibLoadSramElf(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    sram_elf: *mut c_char,
+    skip_crc: bool,
+    wait_for_done: bool,
+    timeout_ms: u64,
+    out_execution: *mut OtLibSramExecution,
+    out_result: *mut u32,
+) -> OtLibStatus {
+    ffi_call("OtLibLoadSramElf", || {
+        let _timer = OpTimer::start(OtLibOp::SramLoad);
+        check_not_null(sram_elf, "sram_elf")?;
+        check_not_null(out_execution, "out_execution")?;
+        check_not_null(out_result, "out_result")?;
+
+        // Unpack the program path string.
+        let sram_elf_in = cstr_to_str(sram_elf)?;
+        check_file_exists(Path::new(sram_elf_in), "sram_elf")?;
+
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                let result = load_sram_program(
+                    transport,
+                    jtag_params,
+                    elf_program(sram_elf_in, skip_crc),
+                    wait_for_done,
+                    timeout_ms,
+                )?;
+                report_sram_execution(result, out_execution, out_result)
+            },
+        )
+    })
+}
+
+/// Same as `OtLibLoadSramElf(...)`, for a program built as a `.vmem` image to be loaded at
+/// `load_addr`.
+#[no_mangle]
+pub extern "C" fn OtLibLoadSramVmem(
+    transport: *const OtLibTransport,
+    openocd_path: *mut c_char,
+    adapter_speed_khz: u32,
+    jtag_log_stdio: bool,
+    sram_vmem: *mut c_char,
+    load_addr: u32,
+    skip_crc: bool,
+    wait_for_done: bool,
+    timeout_ms: u64,
+    out_execution: *mut OtLibSramExecution,
+    out_result: *mut u32,
+) -> OtLibStatus {
+    ffi_call("OtLibLoadSramVmem", || {
+        let _timer = OpTimer::start(OtLibOp::SramLoad);
+        check_not_null(sram_vmem, "sram_vmem")?;
+        check_not_null(out_execution, "out_execution")?;
+        check_not_null(out_result, "out_result")?;
+
+        // Unpack the program path string.
+        let sram_vmem_in = cstr_to_str(sram_vmem)?;
+        check_file_exists(Path::new(sram_vmem_in), "sram_vmem")?;
+
+        with_jtag_transport(
+            transport,
+            openocd_path,
+            adapter_speed_khz,
+            jtag_log_stdio,
+            |transport, jtag_params| {
+                let result = load_sram_program(
+                    transport,
+                    jtag_params,
+                    vmem_program(sram_vmem_in, load_addr, skip_crc),
+                    wait_for_done,
+                    timeout_ms,
+                )?;
+                report_sram_execution(result, out_execution, out_result)
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::tests::dangling;
+
+    #[test]
+    fn load_sram_vmem_rejects_null_vmem() {
+        let status = OtLibLoadSramVmem(
+            dangling(),
+            dangling(),
+            0,
+            false,
+            std::ptr::null_mut(),
+            0,
+            false,
+            false,
+            0,
+            dangling(),
+            dangling(),
+        );
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn execution_results_are_reported() {
+        let done = sram_execution(ExecutionResult::ExecutionDone(0x2000_0000)).unwrap();
+        assert_eq!(done, (OtLibSramExecution::Done, 0x2000_0000));
+        let executing = sram_execution(ExecutionResult::Executing).unwrap();
+        assert_eq!(executing, (OtLibSramExecution::Executing, 0));
+        let error = sram_execution(ExecutionResult::ExecutionError(Some(7))).unwrap();
+        assert_eq!(error, (OtLibSramExecution::Error, 7));
+        let err = sram_execution(ExecutionResult::HaltedAtStart).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::SramProgramError);
+
+        let mut execution = OtLibSramExecution::Executing;
+        let mut value = 0;
+        let err = report_sram_execution(
+            ExecutionResult::ExecutionError(None),
+            &mut execution,
+            &mut value,
+        )
+        .unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::SramProgramError);
+        assert_eq!(execution, OtLibSramExecution::Error);
+    }
+
+    #[test]
+    fn skip_crc_reaches_sram_program_params() {
+        assert!(!elf_program("prog.elf", false).skip_crc);
+        assert!(elf_program("prog.elf", true).skip_crc);
+        let vmem = vmem_program("prog.vmem", 0x1000_0000, true);
+        assert!(vmem.skip_crc);
+        assert_eq!(vmem.load_addr, Some(0x1000_0000));
+        assert_eq!(vmem.elf, None);
+    }
+}
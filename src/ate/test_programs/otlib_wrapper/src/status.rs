@@ -0,0 +1,324 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+
+use anyhow::{anyhow, Result};
+
+use opentitanlib::io::console::ConsoleError;
+
+use crate::ffi::copy_to_cstr_buf;
+use crate::log::{log_error, log_warn};
+
+/// Status code returned by the FFI functions exported by this library.
+///
+/// NOTE: must match the definition of `OtLibStatus` in src/ate/test_programs/dut_lib/dut_lib.cc
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtLibStatus {
+    Ok = 0,
+    /// An argument passed in by the caller is invalid.
+    InvalidArgument = 1,
+    /// The transport could not be created or a transport operation (reset, strapping, GPIO, ...)
+    /// failed.
+    TransportError = 2,
+    /// Connecting to, or operating over, JTAG failed.
+    JtagError = 3,
+    /// Bootstrapping a binary into the DUT's flash failed.
+    BootstrapError = 4,
+    /// Reading from or writing to the console failed.
+    ConsoleError = 5,
+    /// The expected console message was not received in time.
+    ConsoleTimeout = 6,
+    /// The CRC of a received console payload did not match.
+    CrcMismatch = 7,
+    /// Loading or executing an SRAM program failed.
+    SramProgramError = 8,
+    /// A life cycle transition (or lock) failed.
+    LcTransitionError = 9,
+    /// The DUT did not boot as expected.
+    BootError = 10,
+    /// An error that does not fall into any of the categories above.
+    InternalError = 11,
+    /// The library panicked. The panic was caught before it could unwind into the caller.
+    Panic = 12,
+    /// The FPGA bitstream file does not exist.
+    BitstreamNotFound = 13,
+    /// Programming the FPGA with the bitstream failed.
+    FpgaProgramError = 14,
+    /// The ROM did not come up on the freshly programmed FPGA in time.
+    RomTimeout = 15,
+    /// The operation is not supported by the backend of the transport.
+    Unsupported = 16,
+    /// The caller-provided buffer is too small. The required size was written back to the caller.
+    BufferTooSmall = 17,
+    /// The device responded with RESP_ERR. The error JSON was copied to the caller.
+    DeviceError = 18,
+    /// The LC state read back after a transition does not match the target state. The state read
+    /// back was written back to the caller.
+    LcMismatch = 19,
+    /// The transport is in use by another call, see `OtLibTransport`.
+    Busy = 20,
+    /// A polled operation has not completed yet; poll again.
+    WouldBlock = 21,
+    /// The operation succeeded, and its results were written back to the caller, but closing the
+    /// JTAG connection or removing the straps afterwards failed, see `defer_teardown_error(...)`.
+    /// The next JTAG operation on the same transport may fail.
+    JtagTeardownError = 22,
+    /// The operation was aborted by `OtLibRequestCancel(...)`.
+    Cancelled = 23,
+}
+
+impl OtLibStatus {
+    /// Returns the status code that best describes `err`.
+    pub(crate) fn from_error(err: &anyhow::Error) -> Self {
+        // `StatusContext::status` never tags an error that already carries a status, so the
+        // outermost status in the chain is the one attached closest to the failure.
+        if let Some(status) = err
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<StatusError>())
+            .map(|e| e.status)
+        {
+            return status;
+        }
+        if err.chain().any(|cause| cause.is::<ConsoleError>()) {
+            return OtLibStatus::ConsoleError;
+        }
+        OtLibStatus::InternalError
+    }
+}
+
+/// An error tagged with the `OtLibStatus` it should be reported as.
+#[derive(Debug)]
+pub(crate) struct StatusError {
+    status: OtLibStatus,
+    error: anyhow::Error,
+}
+
+impl StatusError {
+    pub(crate) fn new(status: OtLibStatus, error: impl Into<anyhow::Error>) -> Self {
+        StatusError {
+            status,
+            error: error.into(),
+        }
+    }
+}
+
+impl fmt::Display for StatusError {
+    // Only the outermost message: the causes of `error` are reported through `source()`, so `{:#}`
+    // of an error chain lists each of them once.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl Error for StatusError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// Extension trait used to attach an `OtLibStatus` to the error of a `Result`.
+pub(crate) trait StatusContext<T> {
+    /// Tags the error with `status`, unless it already carries a (more specific) status.
+    fn status(self, status: OtLibStatus) -> Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> StatusContext<T> for std::result::Result<T, E> {
+    fn status(self, status: OtLibStatus) -> Result<T> {
+        self.map_err(|e| {
+            let e = e.into();
+            if e.chain().any(|cause| cause.is::<StatusError>()) {
+                e
+            } else {
+                StatusError::new(status, e).into()
+            }
+        })
+    }
+}
+
+/// Returns early with an error tagged with the given `OtLibStatus`.
+macro_rules! bail_status {
+    ($status:expr, $($arg:tt)*) => {
+        return Err($crate::status::StatusError::new($status, anyhow::anyhow!($($arg)*)).into())
+    };
+}
+pub(crate) use bail_status;
+
+thread_local! {
+    /// Message of the most recent error reported by an FFI function on this thread.
+    static LAST_ERROR: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+thread_local! {
+    /// First teardown failure of the FFI call running on this thread, see
+    /// `defer_teardown_error(...)`.
+    static TEARDOWN_ERROR: RefCell<Option<anyhow::Error>> = const { RefCell::new(None) };
+}
+
+/// Records the failure of `result`, a teardown step such as closing a JTAG connection or removing
+/// straps, without interrupting the FFI call running on this thread.
+///
+/// The teardown must not hide the outcome of the operation: if the call otherwise succeeds,
+/// `ffi_call(...)` reports the first recorded failure as `OtLibStatus::JtagTeardownError`; if it
+/// fails, its own error is reported and the teardown failure is only logged.
+pub(crate) fn defer_teardown_error(result: Result<()>) {
+    if let Err(e) = result {
+        TEARDOWN_ERROR.with(|teardown_error| {
+            teardown_error.borrow_mut().get_or_insert(e);
+        });
+    }
+}
+
+/// Records `err` as the last error of the calling thread, see `OtLibGetLastError(...)`.
+pub(crate) fn set_last_error(err: &anyhow::Error) {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = format!("{:#}", err));
+}
+
+/// Copies the message of the most recent error reported on the calling thread into `buf`.
+///
+/// At most `buf_len - 1` bytes are copied and the result is always NUL terminated. Returns the
+/// length of the full message (excluding the NUL terminator), so a return value `>= buf_len`
+/// indicates the message was truncated. `buf` may be null to only query the length.
+#[no_mangle]
+pub extern "C" fn OtLibGetLastError(buf: *mut c_char, buf_len: usize) -> usize {
+    LAST_ERROR.with(|last_error| copy_to_cstr_buf(&last_error.borrow(), buf, buf_len))
+}
+
+/// Runs `f`, converting a panic into an `OtLibStatus::Panic` error.
+///
+/// Unwinding across the FFI boundary is undefined behavior, so every FFI function must run its
+/// body through this function (usually via `ffi_call`).
+pub(crate) fn catch_panic<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let msg = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic payload".to_string());
+        Err(StatusError::new(OtLibStatus::Panic, anyhow!("panicked: {}", msg)).into())
+    })
+}
+
+/// Runs the body of the FFI function `name` and converts its result into an `OtLibStatus`.
+pub(crate) fn ffi_call<F>(name: &str, f: F) -> OtLibStatus
+where
+    F: FnOnce() -> Result<()>,
+{
+    TEARDOWN_ERROR.with(|teardown_error| teardown_error.take());
+    let result = catch_panic(f);
+    let teardown_error = TEARDOWN_ERROR.with(|teardown_error| teardown_error.take());
+    match (result, teardown_error) {
+        (Ok(()), None) => OtLibStatus::Ok,
+        (Ok(()), Some(e)) => {
+            log_warn!("{} succeeded, but its teardown failed: {:#}", name, e);
+            set_last_error(&e);
+            OtLibStatus::JtagTeardownError
+        }
+        (Err(e), teardown_error) => {
+            if let Some(teardown_error) = teardown_error {
+                log_warn!("{} teardown failed: {:#}", name, teardown_error);
+            }
+            let status = OtLibStatus::from_error(&e);
+            // An incomplete polled operation is expected, and not worth an error in the log.
+            if status != OtLibStatus::WouldBlock {
+                log_error!("{} failed: {:#}", name, e);
+            }
+            set_last_error(&e);
+            status
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn ffi_call_catches_panic() {
+        let status = ffi_call("test", || panic!("deliberate panic"));
+        assert_eq!(status, OtLibStatus::Panic);
+    }
+
+    #[test]
+    fn ffi_call_reports_teardown_error_only_on_success() {
+        let status = ffi_call("test", || {
+            defer_teardown_error(Err(anyhow!("disconnect failed")));
+            defer_teardown_error(Err(anyhow!("strap removal failed")));
+            Ok(())
+        });
+        assert_eq!(status, OtLibStatus::JtagTeardownError);
+        let mut buf = [0 as c_char; 64];
+        OtLibGetLastError(buf.as_mut_ptr(), buf.len());
+        // SAFETY: `OtLibGetLastError(...)` always NUL terminates `buf`.
+        let msg = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+        assert_eq!(msg.to_str().unwrap(), "disconnect failed");
+
+        let status = ffi_call("test", || {
+            defer_teardown_error(Err(anyhow!("disconnect failed")));
+            Err(anyhow!("timeout")).status(OtLibStatus::JtagError)
+        });
+        assert_eq!(status, OtLibStatus::JtagError);
+        assert_eq!(ffi_call("test", || Ok(())), OtLibStatus::Ok);
+    }
+
+    #[test]
+    fn ffi_call_reports_first_attached_status() {
+        let status = ffi_call("test", || {
+            Err(anyhow!("timeout"))
+                .status(OtLibStatus::ConsoleTimeout)
+                .status(OtLibStatus::ConsoleError)
+        });
+        assert_eq!(status, OtLibStatus::ConsoleTimeout);
+    }
+
+    /// The message of the last error of the calling thread, see `OtLibGetLastError(...)`.
+    fn last_error() -> String {
+        let mut buf = vec![0 as c_char; OtLibGetLastError(std::ptr::null_mut(), 0) + 1];
+        OtLibGetLastError(buf.as_mut_ptr(), buf.len());
+        // SAFETY: `OtLibGetLastError(...)` always NUL terminates `buf`.
+        let msg = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+        msg.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn last_error_lists_each_cause_once() {
+        ffi_call("test", || {
+            Err(anyhow!("USB transfer failed"))
+                .context("Could not read OTP.")
+                .status(OtLibStatus::JtagError)
+                .context("Could not verify OTP.")
+        });
+        assert_eq!(
+            last_error(),
+            "Could not verify OTP.: Could not read OTP.: USB transfer failed"
+        );
+
+        ffi_call("test", || {
+            Err(anyhow!("USB transfer failed")).status(OtLibStatus::JtagError)
+        });
+        assert_eq!(last_error(), "USB transfer failed");
+    }
+
+    #[test]
+    fn last_error_is_truncated_and_terminated() {
+        ffi_call("test", || Err(anyhow!("Could not connect to LC TAP.")));
+
+        let len = OtLibGetLastError(std::ptr::null_mut(), 0);
+        assert_eq!(len, "Could not connect to LC TAP.".len());
+
+        let mut buf = [0x55 as c_char; 8];
+        assert_eq!(OtLibGetLastError(buf.as_mut_ptr(), buf.len()), len);
+        let msg = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+        assert_eq!(msg.to_str().unwrap(), "Could n");
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{Context, Result};
+
+use opentitanlib::app::TransportWrapper;
+
+use crate::status::{defer_teardown_error, OtLibStatus, StatusContext};
+
+/// Applies and removes named pin strappings, see `StrapGuard`.
+pub(crate) trait Straps {
+    fn apply_strapping(&self, name: &str) -> Result<()>;
+    fn remove_strapping(&self, name: &str) -> Result<()>;
+}
+
+impl Straps for TransportWrapper {
+    fn apply_strapping(&self, name: &str) -> Result<()> {
+        self.pin_strapping(name)
+            .and_then(|strapping| strapping.apply())
+    }
+
+    fn remove_strapping(&self, name: &str) -> Result<()> {
+        self.pin_strapping(name)
+            .and_then(|strapping| strapping.remove())
+    }
+}
+
+/// A pin strapping applied by `StrapGuard::apply(...)`, which is removed again when the guard is
+/// dropped, so that no exit path leaves the DUT strapped for the next call.
+///
+/// A failure to remove the strapping is a teardown failure, see `defer_teardown_error(...)`: it
+/// does not interrupt the operation, but is reported once the FFI call completes.
+pub(crate) struct StrapGuard<'t, T: Straps + ?Sized = TransportWrapper> {
+    straps: &'t T,
+    name: &'static str,
+    /// Description of the strapping for messages, e.g. "LC TAP".
+    what: &'static str,
+    applied: bool,
+}
+
+impl<'t, T: Straps + ?Sized> StrapGuard<'t, T> {
+    /// Applies the strapping `name`, described as `what` in messages.
+    pub(crate) fn apply(straps: &'t T, name: &'static str, what: &'static str) -> Result<Self> {
+        straps
+            .apply_strapping(name)
+            .with_context(|| format!("Could not apply {} straps.", what))
+            .status(OtLibStatus::TransportError)?;
+        Ok(StrapGuard {
+            straps,
+            name,
+            what,
+            applied: true,
+        })
+    }
+
+    /// Removes the strapping now, rather than when the guard goes out of scope.
+    pub(crate) fn remove(self) {
+        drop(self);
+    }
+
+    /// Leaves the strapping applied after the guard is gone, e.g. for a following call.
+    pub(crate) fn keep(mut self) {
+        self.applied = false;
+    }
+}
+
+impl<T: Straps + ?Sized> Drop for StrapGuard<'_, T> {
+    fn drop(&mut self) {
+        if !self.applied {
+            return;
+        }
+        let what = self.what;
+        defer_teardown_error(
+            self.straps
+                .remove_strapping(self.name)
+                .with_context(|| format!("Could not remove {} straps.", what)),
+        );
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    use anyhow::bail;
+
+    use crate::status::ffi_call;
+
+    /// Records the strappings applied, failing to apply the one named `fail_on` if any, and to
+    /// remove any if `fail_remove` is set.
+    #[derive(Default)]
+    pub(crate) struct MockStraps {
+        pub(crate) applied: RefCell<Vec<String>>,
+        pub(crate) fail_on: Option<&'static str>,
+        pub(crate) fail_remove: bool,
+    }
+
+    impl Straps for MockStraps {
+        fn apply_strapping(&self, name: &str) -> Result<()> {
+            if self.fail_on == Some(name) {
+                bail!("cannot apply {}", name);
+            }
+            self.applied.borrow_mut().push(name.to_string());
+            Ok(())
+        }
+
+        fn remove_strapping(&self, name: &str) -> Result<()> {
+            if self.fail_remove {
+                bail!("cannot remove {}", name);
+            }
+            self.applied.borrow_mut().retain(|applied| applied != name);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn strap_guard_removes_strapping_on_every_path() {
+        let straps = MockStraps::default();
+        let guard = StrapGuard::apply(&straps, "PINMUX_TAP_LC", "LC TAP").unwrap();
+        assert_eq!(*straps.applied.borrow(), ["PINMUX_TAP_LC"]);
+        drop(guard);
+        assert!(straps.applied.borrow().is_empty());
+
+        StrapGuard::apply(&straps, "PINMUX_TAP_LC", "LC TAP")
+            .unwrap()
+            .remove();
+        assert!(straps.applied.borrow().is_empty());
+
+        StrapGuard::apply(&straps, "PINMUX_TAP_LC", "LC TAP")
+            .unwrap()
+            .keep();
+        assert_eq!(*straps.applied.borrow(), ["PINMUX_TAP_LC"]);
+    }
+
+    #[test]
+    fn strap_guard_reports_apply_failure() {
+        let straps = MockStraps {
+            fail_on: Some("ROM_BOOTSTRAP"),
+            ..Default::default()
+        };
+        let err = StrapGuard::apply(&straps, "ROM_BOOTSTRAP", "bootstrap")
+            .err()
+            .unwrap();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::TransportError);
+        assert!(straps.applied.borrow().is_empty());
+    }
+
+    #[test]
+    fn strap_removal_failure_is_a_teardown_error() {
+        let straps = MockStraps {
+            fail_remove: true,
+            ..Default::default()
+        };
+        let status = ffi_call("test", || {
+            StrapGuard::apply(&straps, "PINMUX_TAP_LC", "LC TAP")?.remove();
+            Ok(())
+        });
+        assert_eq!(status, OtLibStatus::JtagTeardownError);
+    }
+}
@@ -0,0 +1,775 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::ops::Deref;
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+use opentitanlib::app::TransportWrapper;
+use opentitanlib::backend::chip_whisperer::ChipWhispererOpts;
+use opentitanlib::backend::proxy::ProxyOpts;
+use opentitanlib::backend::ti50emulator::Ti50EmulatorOpts;
+use opentitanlib::backend::verilator::VerilatorOpts;
+use opentitanlib::backend::{self, BackendOpts};
+
+use crate::cancel::CancelToken;
+use crate::dry_run::dry_run;
+use crate::ffi::{check_not_null, cstr_array_to_vec, cstr_to_str};
+use crate::log::{log_error, log_warn};
+use crate::status::{
+    bail_status, catch_panic, ffi_call, set_last_error, OtLibStatus, StatusContext,
+};
+
+// Default delay between attempts to open the backend while waiting for the FPGA to enumerate.
+const TRANSPORT_INIT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Opaque handle of a transport created by one of the `OtLib*TransportInit(...)` functions.
+///
+/// The handle points to a `TransportHandle` that only this library may inspect; it is cast back
+/// by each function taking a transport, see `acquire_transport(...)`.
+///
+/// A transport, and the consoles opened on it with `OtLibConsoleOpen(...)`, must only be used on
+/// the thread that created the transport: `TransportWrapper` and the console handles share the
+/// interfaces of the transport through `Rc`, which is not thread safe. Only
+/// `OtLibRequestCancel(...)` may be called from another thread. A call made while another call
+/// is using the same transport, e.g. from one of its callbacks, fails with `OtLibStatus::Busy`.
+/// Each DUT of a multi-slot station should thus be driven by its own transport, created and used
+/// on its own thread. The transport is owned by the caller until it is released with
+/// `OtLibFpgaTransportDestroy(...)`, which must not race with other calls.
+#[repr(C)]
+pub struct OtLibTransport {
+    _private: [u8; 0],
+}
+
+/// Lets a single call at a time use the resource it guards.
+struct BusyFlag(AtomicBool);
+
+impl BusyFlag {
+    fn new() -> Self {
+        BusyFlag(AtomicBool::new(false))
+    }
+
+    /// Marks the resource as used until the returned guard is dropped, or fails with
+    /// `OtLibStatus::Busy` if it already is.
+    fn acquire(&self) -> Result<BusyGuard<'_>> {
+        if self
+            .0
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            bail_status!(
+                OtLibStatus::Busy,
+                "The transport is in use by another call."
+            );
+        }
+        Ok(BusyGuard(&self.0))
+    }
+}
+
+struct BusyGuard<'a>(&'a AtomicBool);
+
+impl Drop for BusyGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// What an `OtLibTransport` handle points to.
+struct TransportHandle {
+    transport: TransportWrapper,
+    busy: BusyFlag,
+    cancel: CancelToken,
+}
+
+/// Exclusive use of a transport for the duration of a call, see `acquire_transport(...)`.
+pub(crate) struct TransportGuard<'a> {
+    transport: &'a TransportWrapper,
+    cancel: &'a CancelToken,
+    _busy: BusyGuard<'a>,
+}
+
+impl<'a> TransportGuard<'a> {
+    /// Returns the cancellation requested for the transport by `OtLibRequestCancel(...)`.
+    pub(crate) fn cancel_token(&self) -> &'a CancelToken {
+        self.cancel
+    }
+}
+
+impl Deref for TransportGuard<'_> {
+    type Target = TransportWrapper;
+
+    fn deref(&self) -> &TransportWrapper {
+        self.transport
+    }
+}
+
+/// Casts `transport` back to the transport it was created for, failing with `OtLibStatus::Busy`
+/// if another call is using it.
+///
+/// # Safety
+///
+/// `transport` must be a non-null handle returned by one of the `OtLib*TransportInit(...)`
+/// functions that was not released yet.
+pub(crate) unsafe fn acquire_transport<'a>(
+    transport: *const OtLibTransport,
+) -> Result<TransportGuard<'a>> {
+    let handle: &'a TransportHandle = &*transport.cast::<TransportHandle>();
+    let busy = handle.busy.acquire()?;
+    Ok(TransportGuard {
+        transport: &handle.transport,
+        cancel: &handle.cancel,
+        _busy: busy,
+    })
+}
+
+/// Verilator simulation options passed in from C.
+///
+/// NOTE: must match the definition of `OtLibVerilatorOpts` in
+/// src/ate/test_programs/dut_lib/dut_lib.cc
+#[repr(C)]
+pub struct OtLibVerilatorOpts {
+    pub verilator_bin: *const c_char,
+    pub verilator_rom: *const c_char,
+    /// Array of `verilator_flash_count` flash image paths.
+    pub verilator_flash: *const *const c_char,
+    pub verilator_flash_count: usize,
+    pub verilator_otp: *const c_char,
+    pub verilator_timeout_ms: u64,
+    /// Array of `verilator_args_count` extra arguments passed to the simulator.
+    pub verilator_args: *const *const c_char,
+    pub verilator_args_count: usize,
+}
+
+/// Returns the options selecting the `interface` backend, with the options of all other backends
+/// left empty.
+fn default_backend_opts(interface: &str) -> BackendOpts {
+    BackendOpts {
+        interface: String::from(interface),
+        disable_dft_on_reset: false,
+        conf: vec![],
+        usb_vid: None,
+        usb_pid: None,
+        usb_serial: None,
+        opts: ChipWhispererOpts { uarts: None },
+        openocd_adapter_config: None,
+        verilator_opts: VerilatorOpts {
+            verilator_bin: String::from(""),
+            verilator_rom: String::from(""),
+            verilator_flash: vec![],
+            verilator_otp: String::from(""),
+            verilator_timeout: Duration::from_millis(0),
+            verilator_args: vec![],
+        },
+        proxy_opts: ProxyOpts {
+            proxy: None,
+            port: 0,
+        },
+        ti50emulator_opts: Ti50EmulatorOpts {
+            instance_prefix: String::from(""),
+            executable_directory: PathBuf::new(),
+            executable: String::from(""),
+        },
+    }
+}
+
+/// Calls `open` until it succeeds, at least `retries` more times after the first attempt and
+/// until `timeout_ms` has elapsed, sleeping `retry_delay` between attempts.
+///
+/// The error of the last attempt is returned with the number of attempts made.
+fn open_with_retries<T>(
+    timeout_ms: u64,
+    retries: u32,
+    retry_delay: Duration,
+    mut open: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    let mut attempt: u32 = 1;
+    loop {
+        match open() {
+            Ok(value) => return Ok(value),
+            Err(_) if attempt <= retries || Instant::now() < deadline => {
+                std::thread::sleep(retry_delay);
+                attempt = attempt.saturating_add(1);
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Gave up after {} attempt(s).", attempt))
+            }
+        }
+    }
+}
+
+/// Creates the transport described by `backend_opts`.
+///
+/// Opening the backend is retried `retries` times and until `timeout_ms` has elapsed, waiting
+/// `retry_delay_ms` between attempts, see `open_with_retries(...)`. A `retry_delay_ms` of zero
+/// selects the default delay. Applying the default configuration of the opened transport is
+/// retried the same way within `init_retry_window_ms`, see `apply_default_configuration(...)`.
+fn create_transport(
+    backend_opts: &BackendOpts,
+    timeout_ms: u64,
+    retries: u32,
+    retry_delay_ms: u64,
+    init_retry_window_ms: u64,
+) -> Result<TransportWrapper> {
+    let retry_delay = match retry_delay_ms {
+        0 => TRANSPORT_INIT_RETRY_DELAY,
+        ms => Duration::from_millis(ms),
+    };
+    let transport = open_with_retries(timeout_ms, retries, retry_delay, || {
+        backend::create(backend_opts)
+    })
+    .with_context(|| format!("Could not open the {} backend.", backend_opts.interface))
+    .status(OtLibStatus::TransportError)?;
+    apply_default_configuration(transport, init_retry_window_ms, retry_delay)
+        .context("Could not apply the default transport configuration.")
+        .status(OtLibStatus::TransportError)
+}
+
+/// Applies the default configuration of `transport`, retrying a failed attempt `retry_delay` apart
+/// until `init_retry_window_ms` has elapsed; zero makes a single attempt.
+///
+/// This bounds the retries, not the attempts: an attempt that blocks is not interrupted, and blocks
+/// the caller for as long as opentitanlib does. The configuration is applied on the calling
+/// thread, as `TransportWrapper` shares its interfaces through `Rc` and so cannot be handed to a
+/// helper thread to be abandoned on timeout.
+fn apply_default_configuration(
+    transport: TransportWrapper,
+    init_retry_window_ms: u64,
+    retry_delay: Duration,
+) -> Result<TransportWrapper> {
+    open_with_retries(init_retry_window_ms, 0, retry_delay, || {
+        transport.apply_default_configuration(None)
+    })?;
+    Ok(transport)
+}
+
+/// Hands the transport created by `f` over to the C side.
+///
+/// Returns a null pointer on failure, with the reason available from `OtLibGetLastError(...)`.
+fn init_transport<F>(name: &str, f: F) -> *mut OtLibTransport
+where
+    F: FnOnce() -> Result<TransportWrapper>,
+{
+    match catch_panic(f) {
+        Ok(transport) => Box::into_raw(Box::new(TransportHandle {
+            transport,
+            busy: BusyFlag::new(),
+            cancel: CancelToken::default(),
+        }))
+        .cast::<OtLibTransport>(),
+        Err(e) => {
+            log_error!("{} failed: {:#}", name, e);
+            set_last_error(&e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Returns the transport config files at `paths`, which must exist.
+fn transport_conf(paths: Vec<String>) -> Result<Vec<PathBuf>> {
+    paths
+        .into_iter()
+        .map(PathBuf::from)
+        .map(|path| {
+            if !path.is_file() {
+                bail_status!(
+                    OtLibStatus::InvalidArgument,
+                    "Transport config file not found: {:?}.",
+                    path
+                );
+            }
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Returns the options selecting the FPGA `interface` backend.
+///
+/// A `usb_vid` or `usb_pid` of zero, or an empty `usb_serial`, does not filter the USB devices. An
+/// empty `openocd_adapter_config` selects the default OpenOCD adapter config of the backend.
+fn fpga_backend_opts(
+    interface: &str,
+    disable_dft_on_reset: bool,
+    usb_vid: u16,
+    usb_pid: u16,
+    usb_serial: &str,
+    openocd_adapter_config: &str,
+    conf: Vec<PathBuf>,
+) -> BackendOpts {
+    BackendOpts {
+        disable_dft_on_reset,
+        conf,
+        usb_vid: (usb_vid != 0).then_some(usb_vid),
+        usb_pid: (usb_pid != 0).then_some(usb_pid),
+        usb_serial: (!usb_serial.is_empty()).then(|| usb_serial.to_string()),
+        openocd_adapter_config: (!openocd_adapter_config.is_empty())
+            .then(|| PathBuf::from(openocd_adapter_config)),
+        ..default_backend_opts(interface)
+    }
+}
+
+/// opentitanlib backends of the FPGA boards the provisioning flows run on.
+///
+/// Mirrors the FPGA arms of the `args.interface` match in `backend::create(...)`
+/// (`sw/host/opentitanlib/src/backend/mod.rs`), which has no public list of its interfaces; keep
+/// the two in sync when bumping opentitanlib.
+const FPGA_BACKENDS: &[&str] = &["cw310", "cw340", "hyper310", "hyper340", "hyperdebug"];
+
+/// Checks that `interface` names one of the `FPGA_BACKENDS`, so that a typo is reported as such
+/// rather than as a failure deep inside `backend::create(...)`.
+///
+/// An empty or unsupported `interface` is reported as `OtLibStatus::InvalidArgument`.
+fn check_fpga_interface(interface: &str) -> Result<()> {
+    if interface.is_empty() {
+        bail_status!(OtLibStatus::InvalidArgument, "interface string is empty");
+    }
+    if !FPGA_BACKENDS.contains(&interface) {
+        bail_status!(
+            OtLibStatus::InvalidArgument,
+            "unsupported backend '{}', expected one of: {}",
+            interface,
+            FPGA_BACKENDS.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Creates the transport for the FPGA `fpga` interface, one of `FPGA_BACKENDS`.
+///
+/// Opening the backend is retried `retries` times, `retry_delay_ms` apart, and until `timeout_ms`
+/// has elapsed, which gives the USB device time to enumerate; zero `retries` and `timeout_ms`
+/// make a single attempt. Applying the default configuration of the opened board, which fails if
+/// the board is in a bad state, is retried until `init_retry_window_ms` has elapsed; zero makes a
+/// single attempt. An attempt that blocks is not interrupted.
+/// `disable_dft_on_reset` disables DFT whenever the transport resets the DUT. `usb_vid`,
+/// `usb_pid` and `usb_serial` select one of several boards connected to the host; zero or an
+/// empty string matches any board.
+///
+/// A non-empty `openocd_adapter_config` is the OpenOCD adapter config file used instead of the
+/// backend's default, e.g. for a custom JTAG adapter. All JTAG functions called with the returned
+/// transport connect through that adapter.
+///
+/// `conf` is an array of `conf_len` transport config files, e.g. the pin map of a board with a
+/// non-standard pinout, applied by opentitanlib on top of the interface's own config. Each file
+/// must exist.
+///
+/// Returns a null pointer on failure, with the reason available from `OtLibGetLastError(...)`.
+#[no_mangle]
+pub extern "C" fn OtLibFpgaTransportInit(
+    fpga: *mut c_char,
+    disable_dft_on_reset: bool,
+    usb_vid: u16,
+    usb_pid: u16,
+    usb_serial: *const c_char,
+    openocd_adapter_config: *const c_char,
+    conf: *const *const c_char,
+    conf_len: usize,
+    timeout_ms: u64,
+    retries: u32,
+    retry_delay_ms: u64,
+    init_retry_window_ms: u64,
+) -> *mut OtLibTransport {
+    init_transport("OtLibFpgaTransportInit", || {
+        check_not_null(fpga, "fpga")?;
+        check_not_null(usb_serial, "usb_serial")?;
+        check_not_null(openocd_adapter_config, "openocd_adapter_config")?;
+
+        // Unpack FPGA interface, USB serial number and OpenOCD adapter config strings.
+        let fpga_in = cstr_to_str(fpga)?;
+        check_fpga_interface(fpga_in)?;
+        let usb_serial_in = cstr_to_str(usb_serial)?;
+        let openocd_adapter_config_in = cstr_to_str(openocd_adapter_config)?;
+        let conf_in = transport_conf(cstr_array_to_vec(conf, conf_len)?)?;
+
+        let backend_opts = fpga_backend_opts(
+            fpga_in,
+            disable_dft_on_reset,
+            usb_vid,
+            usb_pid,
+            usb_serial_in,
+            openocd_adapter_config_in,
+            conf_in,
+        );
+        let transport = create_transport(
+            &backend_opts,
+            timeout_ms,
+            retries,
+            retry_delay_ms,
+            init_retry_window_ms,
+        );
+        if usb_serial_in.is_empty() {
+            transport
+        } else {
+            transport.with_context(|| {
+                format!("Could not open the FPGA board with USB serial number {usb_serial_in}.")
+            })
+        }
+    })
+}
+
+/// Creates a transport connected to a Verilator simulation of the DUT.
+///
+/// Behaves like `OtLibFpgaTransportInit(...)`; the returned transport can be used with all
+/// other functions of this library and must be released with `OtLibFpgaTransportDestroy(...)`.
+#[no_mangle]
+pub extern "C" fn OtLibVerilatorTransportInit(
+    opts: *const OtLibVerilatorOpts,
+    timeout_ms: u64,
+) -> *mut OtLibTransport {
+    init_transport("OtLibVerilatorTransportInit", || {
+        check_not_null(opts, "opts")?;
+
+        // SAFETY: The Verilator options must be set by the caller and be valid.
+        let opts = unsafe { &*opts };
+
+        let mut backend_opts = default_backend_opts("verilator");
+        backend_opts.verilator_opts = VerilatorOpts {
+            verilator_bin: cstr_to_str(opts.verilator_bin)?.to_string(),
+            verilator_rom: cstr_to_str(opts.verilator_rom)?.to_string(),
+            verilator_flash: cstr_array_to_vec(opts.verilator_flash, opts.verilator_flash_count)?,
+            verilator_otp: cstr_to_str(opts.verilator_otp)?.to_string(),
+            verilator_timeout: Duration::from_millis(opts.verilator_timeout_ms),
+            verilator_args: cstr_array_to_vec(opts.verilator_args, opts.verilator_args_count)?,
+        };
+        create_transport(&backend_opts, timeout_ms, 0, 0, 0)
+    })
+}
+
+/// Converts the proxy `port` argument, which must be in the range 1..=65535.
+fn proxy_port(port: u32) -> Result<u16> {
+    match u16::try_from(port) {
+        Ok(port) if port != 0 => Ok(port),
+        _ => bail_status!(
+            OtLibStatus::InvalidArgument,
+            "Proxy port {} is out of range.",
+            port
+        ),
+    }
+}
+
+/// Creates a transport connected to the FPGA through the session proxy at `proxy`:`port`.
+///
+/// Connecting to the proxy is retried until `timeout_ms` has elapsed; an unreachable proxy is
+/// then reported as a null pointer, like any other `OtLibFpgaTransportInit(...)` failure.
+#[no_mangle]
+pub extern "C" fn OtLibProxyTransportInit(
+    proxy: *const c_char,
+    port: u32,
+    timeout_ms: u64,
+) -> *mut OtLibTransport {
+    init_transport("OtLibProxyTransportInit", || {
+        check_not_null(proxy, "proxy")?;
+
+        // Unpack proxy host string.
+        let proxy_in = cstr_to_str(proxy)?;
+        if proxy_in.is_empty() {
+            bail_status!(OtLibStatus::InvalidArgument, "`proxy` must not be empty.");
+        }
+
+        let mut backend_opts = default_backend_opts("proxy");
+        backend_opts.proxy_opts = ProxyOpts {
+            proxy: Some(proxy_in.to_string()),
+            port: proxy_port(port)?,
+        };
+        create_transport(&backend_opts, timeout_ms, 0, 0, 0)
+    })
+}
+
+/// Creates a transport connected to an instance of the Ti50 emulator.
+///
+/// The emulator has no FPGA and no JTAG interface: `OtLibFpgaLoadBitstream(...)`,
+/// `OtLibLoadSramElf(...)`, `OtLibResetAndLock(...)` and `OtLibLcTransition(...)` fail with
+/// `OtLibStatus::Unsupported` on the returned transport. The console functions and
+/// `OtLibCheckTransportImgBoot(...)` work for the consoles provided by the emulated DUT.
+#[no_mangle]
+pub extern "C" fn OtLibTi50EmulatorTransportInit(
+    instance_prefix: *const c_char,
+    executable_directory: *const c_char,
+    executable: *const c_char,
+    timeout_ms: u64,
+) -> *mut OtLibTransport {
+    init_transport("OtLibTi50EmulatorTransportInit", || {
+        check_not_null(instance_prefix, "instance_prefix")?;
+        check_not_null(executable_directory, "executable_directory")?;
+        check_not_null(executable, "executable")?;
+
+        let mut backend_opts = default_backend_opts("ti50emulator");
+        backend_opts.ti50emulator_opts = Ti50EmulatorOpts {
+            instance_prefix: cstr_to_str(instance_prefix)?.to_string(),
+            executable_directory: PathBuf::from(cstr_to_str(executable_directory)?),
+            executable: cstr_to_str(executable)?.to_string(),
+        };
+        create_transport(&backend_opts, timeout_ms, 0, 0, 0)
+    })
+}
+
+/// Releases a transport created by `OtLibFpgaTransportInit(...)`.
+///
+/// The transport pointer must not be used after this call. A null pointer is ignored.
+#[no_mangle]
+pub extern "C" fn OtLibFpgaTransportDestroy(transport: *mut OtLibTransport) {
+    if transport.is_null() {
+        return;
+    }
+
+    // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned by
+    // the call to one of the `OtLib*TransportInit(...)` functions above, and must not be used after
+    // this call.
+    let transport = unsafe { Box::from_raw(transport.cast::<TransportHandle>()) }.transport;
+
+    // Remove any pin strappings this library may have left applied before releasing the
+    // transport (and the underlying USB handle).
+    // There is no status to report a failure with, so it is only logged.
+    for name in ["PINMUX_TAP_RISCV", "PINMUX_TAP_LC", "ROM_BOOTSTRAP"] {
+        if let Ok(strapping) = transport.pin_strapping(name) {
+            if let Err(e) = strapping.remove() {
+                log_warn!("Could not remove {} straps: {:#}", name, e);
+            }
+        }
+    }
+}
+
+/// Resets the DUT, holding reset for `reset_delay_ms` milliseconds, without loading anything.
+#[no_mangle]
+pub extern "C" fn OtLibResetTarget(
+    transport: *const OtLibTransport,
+    reset_delay_ms: u64,
+) -> OtLibStatus {
+    ffi_call("OtLibResetTarget", || {
+        check_not_null(transport, "transport")?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`.
+        let guard = unsafe { acquire_transport(transport)? };
+        let transport: &TransportWrapper = &guard;
+        if dry_run() {
+            return Ok(());
+        }
+        transport
+            .reset_target(Duration::from_millis(reset_delay_ms), true)
+            .context("Failed to reset the DUT.")
+            .status(OtLibStatus::TransportError)
+    })
+}
+
+/// Aborts the long-running operation of another thread on `transport`, which then fails with
+/// `OtLibStatus::Cancelled`: the console waits, including those over consoles opened on the
+/// transport with `OtLibConsoleOpen(...)`, and the bootstrap retries.
+///
+/// Unlike the other functions, this one may be called while another call is using the transport.
+/// If none is waiting, the next long-running wait on the transport is cancelled instead.
+#[no_mangle]
+pub extern "C" fn OtLibRequestCancel(transport: *const OtLibTransport) -> OtLibStatus {
+    ffi_call("OtLibRequestCancel", || {
+        check_not_null(transport, "transport")?;
+
+        // SAFETY: The transport wrapper pointer passed from C side should be the pointer returned
+        // by the call to `OtLibFpgaTransportInit(...)`. Only the cancellation, which is shared
+        // with the call using the transport, is accessed.
+        let handle: &TransportHandle = unsafe { &*transport.cast::<TransportHandle>() };
+        handle.cancel.cancel();
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn init_with_bogus_interface_returns_null() {
+        let interface = CString::new("bogus").unwrap();
+        let transport = OtLibFpgaTransportInit(
+            interface.as_ptr() as *mut c_char,
+            false,
+            0,
+            0,
+            c"".as_ptr(),
+            c"".as_ptr(),
+            std::ptr::null(),
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+        assert!(transport.is_null());
+    }
+
+    #[test]
+    fn init_with_null_interface_returns_null() {
+        let transport = OtLibFpgaTransportInit(
+            std::ptr::null_mut(),
+            false,
+            0,
+            0,
+            c"".as_ptr(),
+            c"".as_ptr(),
+            std::ptr::null(),
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+        assert!(transport.is_null());
+    }
+
+    #[test]
+    fn init_rejects_empty_and_unknown_interfaces() {
+        for (fpga, error) in [
+            (&b"\0"[..], "interface string is empty"),
+            (b"hyper311\0", "unsupported backend 'hyper311'"),
+        ] {
+            let transport = OtLibFpgaTransportInit(
+                fpga.as_ptr() as *mut c_char,
+                false,
+                0,
+                0,
+                c"".as_ptr(),
+                c"".as_ptr(),
+                std::ptr::null(),
+                0,
+                0,
+                0,
+                0,
+                0,
+            );
+            assert!(transport.is_null());
+            let mut buf = [0 as c_char; 128];
+            crate::status::OtLibGetLastError(buf.as_mut_ptr(), buf.len());
+            let msg = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+            assert!(msg.to_str().unwrap().starts_with(error), "{:?}", msg);
+        }
+        let err = check_fpga_interface("").unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+        assert!(check_fpga_interface("hyper340").is_ok());
+    }
+
+    #[test]
+    fn disable_dft_on_reset_reaches_backend_opts() {
+        assert!(!fpga_backend_opts("hyper310", false, 0, 0, "", "", vec![]).disable_dft_on_reset);
+        assert!(fpga_backend_opts("hyper310", true, 0, 0, "", "", vec![]).disable_dft_on_reset);
+    }
+
+    #[test]
+    fn usb_filters_reach_backend_opts() {
+        let opts = fpga_backend_opts("hyper310", false, 0, 0, "", "", vec![]);
+        assert_eq!(
+            (opts.usb_vid, opts.usb_pid, opts.usb_serial),
+            (None, None, None)
+        );
+
+        let opts = fpga_backend_opts("hyper310", false, 0x2b3e, 0xc310, "50203A", "", vec![]);
+        assert_eq!(opts.usb_vid, Some(0x2b3e));
+        assert_eq!(opts.usb_pid, Some(0xc310));
+        assert_eq!(opts.usb_serial.as_deref(), Some("50203A"));
+    }
+
+    #[test]
+    fn openocd_adapter_config_reaches_backend_opts() {
+        let opts = fpga_backend_opts("hyper310", false, 0, 0, "", "", vec![]);
+        assert_eq!(opts.openocd_adapter_config, None);
+        let opts = fpga_backend_opts(
+            "hyper310",
+            false,
+            0,
+            0,
+            "",
+            "/etc/openocd/adapter.cfg",
+            vec![],
+        );
+        assert_eq!(
+            opts.openocd_adapter_config,
+            Some(PathBuf::from("/etc/openocd/adapter.cfg"))
+        );
+    }
+
+    #[test]
+    fn transport_conf_files_must_exist() {
+        let err = transport_conf(vec![String::from("/nonexistent/pinmap.json")]).unwrap_err();
+        assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+
+        let path = std::env::temp_dir().join(format!("otlib_conf_{}.json", std::process::id()));
+        std::fs::write(&path, b"{}").unwrap();
+        let conf = transport_conf(vec![path.to_string_lossy().into_owned()]);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(conf.unwrap(), vec![path.clone()]);
+        assert_eq!(
+            fpga_backend_opts("hyper310", false, 0, 0, "", "", vec![path.clone()]).conf,
+            vec![path]
+        );
+    }
+
+    #[test]
+    fn verilator_init_with_null_opts_returns_null() {
+        let transport = OtLibVerilatorTransportInit(std::ptr::null(), 0);
+        assert!(transport.is_null());
+    }
+
+    #[test]
+    fn reset_target_rejects_null_transport() {
+        let status = OtLibResetTarget(std::ptr::null(), 50);
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn request_cancel_rejects_null_transport() {
+        let status = OtLibRequestCancel(std::ptr::null());
+        assert_eq!(status, OtLibStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn open_is_retried_the_given_number_of_times() {
+        let mut attempts = 0;
+        let result: Result<()> = open_with_retries(0, 2, Duration::ZERO, || {
+            attempts += 1;
+            bail_status!(OtLibStatus::TransportError, "No CW310 found.");
+        });
+        assert_eq!(attempts, 3);
+        assert!(format!("{:#}", result.unwrap_err()).starts_with("Gave up after 3 attempt(s)."));
+
+        let mut attempts = 0;
+        let result = open_with_retries(0, 5, Duration::ZERO, || {
+            attempts += 1;
+            match attempts {
+                2 => Ok(attempts),
+                _ => bail_status!(OtLibStatus::TransportError, "No CW310 found."),
+            }
+        });
+        assert_eq!(result.unwrap(), 2);
+    }
+
+    #[test]
+    fn busy_flag_admits_one_call_at_a_time() {
+        let (first, second) = (BusyFlag::new(), BusyFlag::new());
+        let guard = first.acquire().unwrap();
+        std::thread::scope(|scope| {
+            // Another thread can use another transport, but not the one in use.
+            scope.spawn(|| {
+                let _guard = second.acquire().unwrap();
+                let err = first.acquire().err().unwrap();
+                assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::Busy);
+            });
+        });
+        drop(guard);
+        std::thread::scope(|scope| {
+            scope.spawn(|| assert!(first.acquire().is_ok()));
+        });
+    }
+
+    #[test]
+    fn proxy_port_must_be_in_range() {
+        assert_eq!(proxy_port(9900).unwrap(), 9900);
+        for port in [0, 65536] {
+            let err = proxy_port(port).unwrap_err();
+            assert_eq!(OtLibStatus::from_error(&err), OtLibStatus::InvalidArgument);
+        }
+    }
+}
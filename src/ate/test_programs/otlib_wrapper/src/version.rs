@@ -0,0 +1,58 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::os::raw::c_char;
+
+use crate::ffi::copy_to_cstr_buf;
+
+/// Version of this library.
+const OTLIB_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Revision of opentitanlib this library was built against, set by the build through the
+/// `OPENTITANLIB_REVISION` environment variable.
+const OPENTITANLIB_REVISION: &str = match option_env!("OPENTITANLIB_REVISION") {
+    Some(revision) => revision,
+    None => "unknown",
+};
+
+/// Returns the versions reported by `OtLibGetVersion(...)`.
+fn version() -> String {
+    format!(
+        "otlib {} / opentitanlib {}",
+        OTLIB_VERSION, OPENTITANLIB_REVISION
+    )
+}
+
+/// Copies the versions of this library and of opentitanlib into `buf`, to be recorded with the
+/// provisioning results, e.g. `otlib 0.1.0 / opentitanlib Earlgrey-A2-Orchestrator-RC2`.
+///
+/// Same buffer semantics as `OtLibGetLastError(...)`: the result is truncated to `buf_len - 1`
+/// bytes and NUL terminated, and the length of the full string is returned. `buf` may be null to
+/// only query the length.
+#[no_mangle]
+pub extern "C" fn OtLibGetVersion(buf: *mut c_char, buf_len: usize) -> usize {
+    copy_to_cstr_buf(&version(), buf, buf_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn version_names_both_components() {
+        let len = OtLibGetVersion(std::ptr::null_mut(), 0);
+        let mut buf = vec![0x55 as c_char; len + 1];
+        assert_eq!(OtLibGetVersion(buf.as_mut_ptr(), buf.len()), len);
+        // SAFETY: `buf` was NUL terminated by `OtLibGetVersion(...)`.
+        let version = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_str().unwrap();
+        assert_eq!(
+            version,
+            format!(
+                "otlib {} / opentitanlib {}",
+                OTLIB_VERSION, OPENTITANLIB_REVISION
+            )
+        );
+    }
+}